@@ -1,29 +1,185 @@
 use crate::error::WalletError;
 use kaspa_addresses::{Address, Prefix, Version};
+use kaspa_consensus_core::tx::ScriptPublicKey;
+use kaspa_txscript::{pay_to_address_script, pay_to_script_hash_script};
 use secp256k1::PublicKey;
+use std::fmt;
 
-pub fn generate_address(public_key: &PublicKey, network: &str) -> Result<String, WalletError> {
-    let prefix = match network.trim() {
-        "mainnet" => Prefix::Mainnet,
-        "testnet-10" | "testnet10" | "testnet" => Prefix::Testnet,
-        "testnet-11" | "testnet11" => Prefix::Testnet,
-        "simnet" => Prefix::Simnet,
-        _ => {
-            return Err(WalletError::Network(format!(
-                "Unknown network: {}",
-                network
-            )))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureScheme {
+    #[default]
+    Schnorr,
+    Ecdsa,
+}
+
+impl SignatureScheme {
+    pub const ALL: [SignatureScheme; 2] = [SignatureScheme::Schnorr, SignatureScheme::Ecdsa];
+
+    pub fn from_name(name: &str) -> Result<Self, WalletError> {
+        match name.trim().to_lowercase().as_str() {
+            "schnorr" => Ok(SignatureScheme::Schnorr),
+            "ecdsa" => Ok(SignatureScheme::Ecdsa),
+            _ => Err(WalletError::InvalidParameters(format!(
+                "Unknown signature scheme: {} (expected schnorr or ecdsa)",
+                name
+            ))),
         }
-    };
+    }
+
+    pub fn address_version(self) -> Version {
+        match self {
+            SignatureScheme::Schnorr => Version::PubKey,
+            SignatureScheme::Ecdsa => Version::PubKeyECDSA,
+        }
+    }
+
+    pub fn from_address_version(version: Version) -> Option<Self> {
+        match version {
+            Version::PubKey => Some(SignatureScheme::Schnorr),
+            Version::PubKeyECDSA => Some(SignatureScheme::Ecdsa),
+            _ => None,
+        }
+    }
+}
 
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::Schnorr => write!(f, "schnorr"),
+            SignatureScheme::Ecdsa => write!(f, "ecdsa"),
+        }
+    }
+}
+
+pub fn network_prefix(network: &str) -> Result<Prefix, WalletError> {
+    match network.trim() {
+        "mainnet" => Ok(Prefix::Mainnet),
+        "testnet-10" | "testnet10" | "testnet" => Ok(Prefix::Testnet),
+        "testnet-11" | "testnet11" => Ok(Prefix::Testnet),
+        "simnet" => Ok(Prefix::Simnet),
+        _ => Err(WalletError::Network(format!(
+            "Unknown network: {}",
+            network
+        ))),
+    }
+}
+
+pub fn address_from_public_key(
+    public_key: &PublicKey,
+    prefix: Prefix,
+    scheme: SignatureScheme,
+) -> Address {
     let pubkey_bytes = public_key.serialize();
-    let xonly_pubkey = &pubkey_bytes[1..];
+    let payload = match scheme {
+        SignatureScheme::Schnorr => &pubkey_bytes[1..],
+        SignatureScheme::Ecdsa => &pubkey_bytes[..],
+    };
+
+    Address::new(prefix, scheme.address_version(), payload)
+}
 
-    let address = Address::new(prefix, Version::PubKey, xonly_pubkey);
+/// The locking script does not depend on the network prefix, so any prefix
+/// yields the same script public key.
+pub fn pay_to_public_key_script(
+    public_key: &PublicKey,
+    scheme: SignatureScheme,
+) -> ScriptPublicKey {
+    pay_to_address_script(&address_from_public_key(
+        public_key,
+        Prefix::Mainnet,
+        scheme,
+    ))
+}
+
+/// The `Version::ScriptHash` address paying to the BLAKE2b hash of
+/// `redeem_script`. Spending its outputs reveals the script along with the
+/// data that satisfies it.
+pub fn address_from_redeem_script(redeem_script: &[u8], prefix: Prefix) -> Address {
+    // OP_BLAKE2B OP_DATA_32 <hash> OP_EQUAL
+    let script_public_key = pay_to_script_hash_script(redeem_script);
+    Address::new(
+        prefix,
+        Version::ScriptHash,
+        &script_public_key.script()[2..34],
+    )
+}
+
+pub fn generate_address(
+    public_key: &PublicKey,
+    network: &str,
+    scheme: SignatureScheme,
+) -> Result<String, WalletError> {
+    let prefix = network_prefix(network)?;
+    let address = address_from_public_key(public_key, prefix, scheme);
     Ok(address.to_string())
 }
 
+pub fn parse_address(address: &str) -> Result<Address, WalletError> {
+    Address::try_from(address.trim()).map_err(|_| WalletError::InvalidAddressFormat)
+}
+
 pub fn validate_address(address: &str) -> Result<bool, WalletError> {
-    let _ = Address::try_from(address).map_err(|_| WalletError::InvalidAddressFormat)?;
+    let _ = parse_address(address)?;
     Ok(true)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{Secp256k1, SecretKey};
+
+    fn test_public_key() -> PublicKey {
+        let secret_key = SecretKey::from_slice(&[0x01; 32]).unwrap();
+        PublicKey::from_secret_key(&Secp256k1::new(), &secret_key)
+    }
+
+    #[test]
+    fn test_schnorr_address_uses_xonly_key() {
+        let public_key = test_public_key();
+        let address =
+            address_from_public_key(&public_key, Prefix::Mainnet, SignatureScheme::Schnorr);
+
+        assert_eq!(address.version, Version::PubKey);
+        assert_eq!(address.payload.as_slice(), &public_key.serialize()[1..]);
+    }
+
+    #[test]
+    fn test_ecdsa_address_uses_compressed_key() {
+        let public_key = test_public_key();
+        let address = address_from_public_key(&public_key, Prefix::Testnet, SignatureScheme::Ecdsa);
+
+        assert_eq!(address.version, Version::PubKeyECDSA);
+        assert_eq!(address.payload.as_slice(), &public_key.serialize()[..]);
+        assert!(validate_address(&address.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_script_hash_address() {
+        let redeem_script = [0x51];
+        let address = address_from_redeem_script(&redeem_script, Prefix::Testnet);
+
+        assert_eq!(address.version, Version::ScriptHash);
+        assert_eq!(
+            pay_to_address_script(&address),
+            pay_to_script_hash_script(&redeem_script)
+        );
+        assert!(validate_address(&address.to_string()).unwrap());
+        assert_ne!(
+            address,
+            address_from_redeem_script(&[0x52], Prefix::Testnet)
+        );
+    }
+
+    #[test]
+    fn test_scheme_from_name() {
+        assert_eq!(
+            SignatureScheme::from_name("ECDSA").unwrap(),
+            SignatureScheme::Ecdsa
+        );
+        assert_eq!(
+            SignatureScheme::from_name("schnorr").unwrap(),
+            SignatureScheme::Schnorr
+        );
+        assert!(SignatureScheme::from_name("rsa").is_err());
+    }
+}
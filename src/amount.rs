@@ -0,0 +1,174 @@
+use crate::error::{WalletError, WalletResult};
+use std::fmt;
+use std::str::FromStr;
+
+pub const SOMPI_PER_KAS: u64 = 100_000_000;
+const KAS_DECIMALS: usize = 8;
+
+/// An amount of KAS, held as a whole number of sompi so that parsing and
+/// printing never round.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn from_sompi(sompi: u64) -> Self {
+        Self(sompi)
+    }
+
+    pub fn sompi(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Parses an amount such as `1.5`, `1.5 KAS` or `150000000 sompi`.
+    /// Without a unit the amount is in KAS, with at most 8 decimals.
+    pub fn parse(text: &str) -> WalletResult<Self> {
+        let invalid = |reason: &str| {
+            WalletError::InvalidParameters(format!("Invalid amount {:?}: {}", text, reason))
+        };
+        let trimmed = text.trim();
+        if trimmed.starts_with('-') {
+            return Err(invalid("amounts cannot be negative"));
+        }
+
+        let lower = trimmed.to_ascii_lowercase();
+        let (number, in_sompi) = if let Some(number) = lower.strip_suffix("sompi") {
+            (number.trim_end(), true)
+        } else if let Some(number) = lower.strip_suffix("kas") {
+            (number.trim_end(), false)
+        } else {
+            (lower.as_str(), false)
+        };
+        let number = number.strip_prefix('+').unwrap_or(number);
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid("expected a number"));
+        }
+        if !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid("expected a decimal number"));
+        }
+        if in_sompi && !fraction.is_empty() {
+            return Err(invalid("sompi cannot be divided"));
+        }
+        if fraction.len() > KAS_DECIMALS {
+            return Err(invalid("KAS has at most 8 decimals"));
+        }
+
+        let too_large = || invalid("amount is too large");
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| too_large())?
+        };
+        if in_sompi {
+            return Ok(Self(whole));
+        }
+        let fraction: u64 = format!("{:0<width$}", fraction, width = KAS_DECIMALS)
+            .parse()
+            .map_err(|_| invalid("expected a decimal number"))?;
+        whole
+            .checked_mul(SOMPI_PER_KAS)
+            .and_then(|sompi| sompi.checked_add(fraction))
+            .map(Self)
+            .ok_or_else(too_large)
+    }
+
+    /// The amount in KAS with all 8 decimals and no unit, e.g. `1.50000000`.
+    pub fn to_kas_string(self) -> String {
+        format!(
+            "{}.{:0width$}",
+            self.0 / SOMPI_PER_KAS,
+            self.0 % SOMPI_PER_KAS,
+            width = KAS_DECIMALS
+        )
+    }
+}
+
+impl FromStr for Amount {
+    type Err = WalletError;
+
+    fn from_str(text: &str) -> WalletResult<Self> {
+        Self::parse(text)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} KAS", self.to_kas_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sompi(text: &str) -> u64 {
+        Amount::parse(text).unwrap().sompi()
+    }
+
+    #[test]
+    fn test_parse_kas() {
+        assert_eq!(sompi("1"), SOMPI_PER_KAS);
+        assert_eq!(sompi("0.00000001"), 1);
+        assert_eq!(sompi(" 12.5 "), 1_250_000_000);
+        assert_eq!(sompi(".5"), 50_000_000);
+        assert_eq!(sompi("5."), 5 * SOMPI_PER_KAS);
+        assert_eq!(sompi("0.1"), 10_000_000);
+        // 0.1 + 0.2 in floating point is 0.30000000000000004.
+        assert_eq!(sompi("0.3"), sompi("0.1") + sompi("0.2"));
+        assert_eq!(sompi("184467440737.09551615"), u64::MAX);
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(sompi("1.5 KAS"), 150_000_000);
+        assert_eq!(sompi("1.5kas"), 150_000_000);
+        assert_eq!(sompi("150000000 sompi"), 150_000_000);
+        assert_eq!(sompi("1SOMPI"), 1);
+        assert!(Amount::parse("1.5 sompi").is_err());
+        assert!(Amount::parse("KAS").is_err());
+        assert!(Amount::parse("1 BTC").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_amounts() {
+        for text in [
+            "",
+            ".",
+            "-1",
+            "- 1",
+            "1e3",
+            "1,5",
+            "0.000000001",
+            "184467440737.09551616",
+            "184467440738",
+            "18446744073709551616 sompi",
+            "NaN",
+        ] {
+            assert!(Amount::parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn test_format_round_trips() {
+        assert_eq!(
+            Amount::from_sompi(1_250_000_000).to_kas_string(),
+            "12.50000000"
+        );
+        assert_eq!(Amount::from_sompi(1).to_string(), "0.00000001 KAS");
+        for value in [0, 1, 99_999_999, SOMPI_PER_KAS, u64::MAX] {
+            let amount = Amount::from_sompi(value);
+            assert_eq!(amount.to_string().parse::<Amount>().unwrap(), amount);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::error::{WalletError, WalletResult};
+use hmac::{Hmac, Mac};
+use ripemd::Ripemd160;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+
+type HmacSha512 = Hmac<Sha512>;
+
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+pub const KASPA_COIN_TYPE: u32 = 111111;
+
+/// Version bytes of serialized extended public keys: Kaspa's `kpub` and
+/// `ktub`, and Bitcoin's `xpub` and `tpub`, which other tools may export.
+pub const KPUB_VERSION: [u8; 4] = [0x03, 0x8f, 0x33, 0x2e];
+pub const KTUB_VERSION: [u8; 4] = [0x03, 0x90, 0xa2, 0x41];
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+const MASTER_KEY_HMAC_KEY: &[u8] = b"Bitcoin seed";
+const EXTENDED_KEY_LEN: usize = 78;
+
+#[derive(Clone)]
+pub struct ExtendedPrivateKey {
+    secret_key: SecretKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+}
+
+impl ExtendedPrivateKey {
+    pub fn new_master(seed: &[u8]) -> WalletResult<Self> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(WalletError::KeyGeneration(format!(
+                "Seed must be between 16 and 64 bytes, got {}",
+                seed.len()
+            )));
+        }
+
+        let (key, chain_code) = hmac_split(MASTER_KEY_HMAC_KEY, &[seed])?;
+        let secret_key = SecretKey::from_slice(&key[..])?;
+
+        Ok(Self {
+            secret_key,
+            chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+        })
+    }
+
+    pub fn derive_child(&self, child_number: u32) -> WalletResult<Self> {
+        let depth = self.depth.checked_add(1).ok_or_else(|| {
+            WalletError::KeyGeneration("Maximum derivation depth exceeded".to_string())
+        })?;
+
+        let index = child_number.to_be_bytes();
+        let (tweak, chain_code) = if child_number >= HARDENED_OFFSET {
+            let secret = Zeroizing::new(self.secret_key.secret_bytes());
+            hmac_split(&self.chain_code, &[&[0u8][..], &secret[..], &index[..]])?
+        } else {
+            hmac_split(
+                &self.chain_code,
+                &[&self.public_key().serialize()[..], &index[..]],
+            )?
+        };
+
+        let tweak = Scalar::from_be_bytes(*tweak).map_err(|_| {
+            WalletError::KeyGeneration(format!("Invalid child key at index {}", child_number))
+        })?;
+        let secret_key = self.secret_key.add_tweak(&tweak)?;
+
+        Ok(Self {
+            secret_key,
+            chain_code,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number,
+        })
+    }
+
+    pub fn derive_path(&self, path: &str) -> WalletResult<Self> {
+        parse_derivation_path(path)?
+            .into_iter()
+            .try_fold(self.clone(), |key, child_number| {
+                key.derive_child(child_number)
+            })
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.secret_key)
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        key_fingerprint(&self.public_key())
+    }
+
+    /// The public half, which derives the same non-hardened children
+    /// without the secret key.
+    pub fn extended_public_key(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            public_key: self.public_key(),
+            chain_code: self.chain_code,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+        }
+    }
+}
+
+impl Drop for ExtendedPrivateKey {
+    fn drop(&mut self) {
+        self.secret_key.non_secure_erase();
+        self.chain_code.zeroize();
+    }
+}
+
+impl fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedPrivateKey")
+            .field("secret_key", &"<redacted>")
+            .field("chain_code", &"<redacted>")
+            .field("depth", &self.depth)
+            .field("parent_fingerprint", &hex::encode(self.parent_fingerprint))
+            .field("child_number", &self.child_number)
+            .finish()
+    }
+}
+
+/// An extended public key, as shared by cosigners to build watch-only or
+/// multisig addresses. Only non-hardened children can be derived from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    public_key: PublicKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+}
+
+impl ExtendedPublicKey {
+    pub fn derive_child(&self, child_number: u32) -> WalletResult<Self> {
+        if child_number >= HARDENED_OFFSET {
+            return Err(WalletError::KeyGeneration(
+                "Hardened children cannot be derived from a public key".to_string(),
+            ));
+        }
+        let depth = self.depth.checked_add(1).ok_or_else(|| {
+            WalletError::KeyGeneration("Maximum derivation depth exceeded".to_string())
+        })?;
+
+        let (tweak, chain_code) = hmac_split(
+            &self.chain_code,
+            &[
+                &self.public_key.serialize()[..],
+                &child_number.to_be_bytes()[..],
+            ],
+        )?;
+        let tweak = Scalar::from_be_bytes(*tweak).map_err(|_| {
+            WalletError::KeyGeneration(format!("Invalid child key at index {}", child_number))
+        })?;
+        let public_key = self.public_key.add_exp_tweak(&Secp256k1::new(), &tweak)?;
+
+        Ok(Self {
+            public_key,
+            chain_code,
+            depth,
+            parent_fingerprint: key_fingerprint(&self.public_key),
+            child_number,
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Base58Check serialization with the given version bytes, e.g.
+    /// [`KPUB_VERSION`] for a mainnet `kpub...` string.
+    pub fn encode(&self, version: [u8; 4]) -> String {
+        let mut data = Vec::with_capacity(EXTENDED_KEY_LEN + 4);
+        data.extend_from_slice(&version);
+        data.push(self.depth);
+        data.extend_from_slice(&self.parent_fingerprint);
+        data.extend_from_slice(&self.child_number.to_be_bytes());
+        data.extend_from_slice(&self.chain_code);
+        data.extend_from_slice(&self.public_key.serialize());
+        let checksum = Sha256::digest(Sha256::digest(&data));
+        data.extend_from_slice(&checksum[..4]);
+        bs58::encode(data).into_string()
+    }
+
+    /// Parses a `kpub`, `ktub`, `xpub` or `tpub` string.
+    pub fn parse(text: &str) -> WalletResult<Self> {
+        let invalid = |reason: &str| {
+            WalletError::InvalidParameters(format!("Invalid extended public key: {}", reason))
+        };
+        let data = bs58::decode(text.trim())
+            .into_vec()
+            .map_err(|_| invalid("not base58"))?;
+        if data.len() != EXTENDED_KEY_LEN + 4 {
+            return Err(invalid("wrong length"));
+        }
+        let (payload, checksum) = data.split_at(EXTENDED_KEY_LEN);
+        if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+        if ![KPUB_VERSION, KTUB_VERSION, XPUB_VERSION, TPUB_VERSION]
+            .contains(&payload[..4].try_into().expect("4 bytes"))
+        {
+            return Err(invalid("not a public key version"));
+        }
+
+        Ok(Self {
+            depth: payload[4],
+            parent_fingerprint: payload[5..9].try_into().expect("4 bytes"),
+            child_number: u32::from_be_bytes(payload[9..13].try_into().expect("4 bytes")),
+            chain_code: payload[13..45].try_into().expect("32 bytes"),
+            public_key: PublicKey::from_slice(&payload[45..])
+                .map_err(|_| invalid("bad public key"))?,
+        })
+    }
+}
+
+pub fn key_fingerprint(public_key: &PublicKey) -> [u8; 4] {
+    let sha = Sha256::digest(public_key.serialize());
+    let hash = Ripemd160::digest(sha);
+    let mut fingerprint = [0u8; 4];
+    fingerprint.copy_from_slice(&hash[..4]);
+    fingerprint
+}
+
+/// Parses paths such as `m/44'/111111'/0'/0/0`; `h` and `H` are accepted
+/// as hardened markers as well as `'`.
+pub fn parse_derivation_path(path: &str) -> WalletResult<Vec<u32>> {
+    let invalid = || WalletError::InvalidParameters(format!("Invalid derivation path: {}", path));
+
+    let mut segments = path.trim().split('/');
+    if segments.next() != Some("m") {
+        return Err(invalid());
+    }
+
+    segments
+        .map(|segment| {
+            let (number, hardened) = match segment.strip_suffix(['\'', 'h', 'H']) {
+                Some(number) => (number, true),
+                None => (segment, false),
+            };
+            let index: u32 = number.parse().map_err(|_| invalid())?;
+            if index >= HARDENED_OFFSET {
+                return Err(invalid());
+            }
+            Ok(if hardened {
+                index + HARDENED_OFFSET
+            } else {
+                index
+            })
+        })
+        .collect()
+}
+
+pub fn kaspa_account_path(account: u32) -> String {
+    format!("m/44'/{}'/{}'", KASPA_COIN_TYPE, account)
+}
+
+/// Returns the two halves of HMAC-SHA512; the left half is key material.
+fn hmac_split(key: &[u8], data: &[&[u8]]) -> WalletResult<(Zeroizing<[u8; 32]>, [u8; 32])> {
+    let mut mac = HmacSha512::new_from_slice(key)
+        .map_err(|e| WalletError::KeyGeneration(format!("HMAC error: {}", e)))?;
+    for chunk in data {
+        mac.update(chunk);
+    }
+    let mut output = mac.finalize().into_bytes();
+
+    let mut left = Zeroizing::new([0u8; 32]);
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&output[..32]);
+    right.copy_from_slice(&output[32..]);
+    output.as_mut_slice().zeroize();
+    Ok((left, right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BIP-32 test vector 1.
+    const SEED: &str = "000102030405060708090a0b0c0d0e0f";
+
+    #[test]
+    fn test_master_key() {
+        let master = ExtendedPrivateKey::new_master(&hex::decode(SEED).unwrap()).unwrap();
+
+        assert_eq!(
+            hex::encode(master.chain_code()),
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"
+        );
+        assert_eq!(
+            hex::encode(master.secret_key().secret_bytes()),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(master.depth(), 0);
+    }
+
+    #[test]
+    fn test_derive_hardened_and_normal() {
+        let master = ExtendedPrivateKey::new_master(&hex::decode(SEED).unwrap()).unwrap();
+
+        let child = master.derive_path("m/0'").unwrap();
+        assert_eq!(
+            hex::encode(child.chain_code()),
+            "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141"
+        );
+        assert_eq!(
+            hex::encode(child.secret_key().secret_bytes()),
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+        );
+        assert_eq!(child.parent_fingerprint(), master.fingerprint());
+
+        let grandchild = master.derive_path("m/0H/1").unwrap();
+        assert_eq!(
+            hex::encode(grandchild.chain_code()),
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"
+        );
+        assert_eq!(
+            hex::encode(grandchild.secret_key().secret_bytes()),
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+        );
+        assert_eq!(grandchild.depth(), 2);
+    }
+
+    #[test]
+    fn test_debug_redacts_key_material() {
+        let master = ExtendedPrivateKey::new_master(&hex::decode(SEED).unwrap()).unwrap();
+        let debug = format!("{:?} {:#?}", master, master);
+
+        assert!(!debug.contains(&hex::encode(master.secret_key().secret_bytes())));
+        assert!(!debug.contains(&hex::encode(master.chain_code())));
+        assert!(!debug.contains(&format!("{:?}", master.secret_key().secret_bytes())));
+        assert!(!debug.contains(&format!("{:?}", master.chain_code())));
+    }
+
+    #[test]
+    fn test_extended_public_key() {
+        let master = ExtendedPrivateKey::new_master(&hex::decode(SEED).unwrap()).unwrap();
+        let xpub = master.derive_path("m/0'").unwrap().extended_public_key();
+        assert_eq!(
+            xpub.encode(XPUB_VERSION),
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"
+        );
+
+        // Public derivation matches private derivation for normal children.
+        let child = xpub.derive_child(1).unwrap();
+        assert_eq!(
+            child,
+            master.derive_path("m/0'/1").unwrap().extended_public_key()
+        );
+        assert!(xpub.derive_child(HARDENED_OFFSET).is_err());
+
+        let kpub = child.encode(KPUB_VERSION);
+        assert!(kpub.starts_with("kpub"));
+        assert_eq!(ExtendedPublicKey::parse(&kpub).unwrap(), child);
+        assert!(child.encode(KTUB_VERSION).starts_with("ktub"));
+
+        let mut corrupted = kpub.into_bytes();
+        corrupted[20] = if corrupted[20] == b'a' { b'b' } else { b'a' };
+        assert!(ExtendedPublicKey::parse(std::str::from_utf8(&corrupted).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_derivation_path() {
+        assert_eq!(
+            parse_derivation_path("m/44'/111111'/0'").unwrap(),
+            vec![
+                44 + HARDENED_OFFSET,
+                KASPA_COIN_TYPE + HARDENED_OFFSET,
+                HARDENED_OFFSET
+            ]
+        );
+        assert_eq!(parse_derivation_path("m").unwrap(), Vec::<u32>::new());
+        assert!(parse_derivation_path("44'/0").is_err());
+        assert!(parse_derivation_path("m/x").is_err());
+        assert!(parse_derivation_path("m/2147483648").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::address::{network_prefix, parse_address};
+use crate::error::{WalletError, WalletResult};
+use crate::pskt::{Pskt, PsktEncoding};
+use crate::wallet::KaspaWallet;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::SignableTransaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+pub const BUNDLE_VERSION: u32 = 1;
+
+const BUNDLE_FORMAT: &str = "kasparustwallet-signing-bundle";
+
+/// Versioned JSON envelope carrying a payment between the online machine
+/// that prepares it and the air-gapped machine holding the key.
+///
+/// The PSKT inside holds the transaction and the UTXO entries the signer
+/// needs for the sighash, so signing never touches the network. `checksum`
+/// covers every other field to catch corruption or edits in transit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningBundle {
+    pub format: String,
+    pub version: u32,
+    pub network: String,
+    /// Watch-only address the inputs belong to and change returns to.
+    pub address: String,
+    /// Base64 PSKT, unsigned until the offline machine signs it.
+    pub pskt: String,
+    pub checksum: String,
+}
+
+#[derive(Serialize)]
+struct BundleContents<'a> {
+    format: &'a str,
+    version: u32,
+    network: &'a str,
+    address: &'a str,
+    pskt: &'a str,
+}
+
+impl SigningBundle {
+    pub fn new(address: &Address, pskt: &Pskt) -> WalletResult<Self> {
+        if address.prefix != network_prefix(&pskt.network)? {
+            return Err(WalletError::InvalidParameters(format!(
+                "Address {} does not belong to {}",
+                address, pskt.network
+            )));
+        }
+        let mut bundle = Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            network: pskt.network.clone(),
+            address: address.to_string(),
+            pskt: String::new(),
+            checksum: String::new(),
+        };
+        bundle.set_pskt(pskt)?;
+        Ok(bundle)
+    }
+
+    /// The PSKT inside, after checking the envelope.
+    pub fn pskt(&self) -> WalletResult<Pskt> {
+        self.verify()?;
+        let pskt = Pskt::decode(self.pskt.as_bytes())?;
+        if pskt.network != self.network {
+            return Err(WalletError::BundleCorrupted(format!(
+                "PSKT is for {} but the bundle for {}",
+                pskt.network, self.network
+            )));
+        }
+        Ok(pskt)
+    }
+
+    pub fn set_pskt(&mut self, pskt: &Pskt) -> WalletResult<()> {
+        self.pskt = String::from_utf8(pskt.encode(PsktEncoding::Base64)).expect("base64 is ASCII");
+        self.checksum = self.compute_checksum()?;
+        Ok(())
+    }
+
+    /// Signs every input with `wallet` and finalizes the transaction, so
+    /// the bundle is ready to broadcast. Returns the number of inputs signed.
+    pub fn sign(&mut self, wallet: &KaspaWallet) -> WalletResult<usize> {
+        let mut pskt = self.pskt()?;
+        let signed = wallet.sign_pskt(&mut pskt)?;
+        if signed == 0 {
+            return Err(WalletError::InvalidParameters(format!(
+                "None of the inputs belong to {}",
+                wallet.get_address()
+            )));
+        }
+        pskt.finalize()?;
+        self.set_pskt(&pskt)?;
+        Ok(signed)
+    }
+
+    /// The signed transaction, ready to submit.
+    pub fn signed_transaction(&self) -> WalletResult<SignableTransaction> {
+        let pskt = self.pskt()?;
+        if !pskt.is_finalized() {
+            return Err(WalletError::InvalidParameters(
+                "The bundle has not been signed yet".to_string(),
+            ));
+        }
+        pskt.extract()
+    }
+
+    pub fn verify(&self) -> WalletResult<()> {
+        if self.format != BUNDLE_FORMAT {
+            return Err(WalletError::BundleCorrupted(format!(
+                "unknown format {:?}",
+                self.format
+            )));
+        }
+        if self.version != BUNDLE_VERSION {
+            return Err(WalletError::BundleCorrupted(format!(
+                "unsupported bundle version {}",
+                self.version
+            )));
+        }
+        if self.compute_checksum()? != self.checksum {
+            return Err(WalletError::BundleCorrupted(
+                "checksum mismatch".to_string(),
+            ));
+        }
+        let address = parse_address(&self.address)?;
+        if address.prefix != network_prefix(&self.network)? {
+            return Err(WalletError::BundleCorrupted(format!(
+                "address {} does not belong to {}",
+                self.address, self.network
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> WalletResult<Self> {
+        let contents = fs::read_to_string(path)?;
+        let bundle: Self = serde_json::from_str(&contents)
+            .map_err(|e| WalletError::BundleCorrupted(format!("invalid bundle JSON: {}", e)))?;
+        bundle.verify()?;
+        Ok(bundle)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> WalletResult<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::Io(format!("Failed to serialize bundle: {}", e)))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn compute_checksum(&self) -> WalletResult<String> {
+        let contents = serde_json::to_vec(&BundleContents {
+            format: &self.format,
+            version: self.version,
+            network: &self.network,
+            address: &self.address,
+            pskt: &self.pskt,
+        })
+        .map_err(|e| WalletError::Io(format!("Failed to serialize bundle: {}", e)))?;
+        Ok(hex::encode(Sha256::digest(contents)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{Amount, SOMPI_PER_KAS};
+    use crate::mock_node::MockNode;
+    use crate::node::NodeApi;
+    use crate::send::{self, PaymentRequest};
+    use crate::test_util::test_wallet;
+
+    async fn prepared_bundle(node: &MockNode, from: &Address) -> SigningBundle {
+        let recipient = test_wallet(0x43).address();
+        let request =
+            PaymentRequest::new(vec![(recipient, Amount::from_sompi(2 * SOMPI_PER_KAS))], 1);
+        let tx = send::build_unsigned_payment(node, from, &request)
+            .await
+            .unwrap();
+        SigningBundle::new(from, &Pskt::new("testnet-10", &tx).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_prepare_sign_broadcast() {
+        let wallet = test_wallet(0x42);
+        let node = MockNode::new()
+            .with_utxo(&wallet.address(), 3 * SOMPI_PER_KAS)
+            .with_utxo(&wallet.address(), SOMPI_PER_KAS);
+
+        // Online: only the address is known.
+        let bundle = prepared_bundle(&node, &wallet.address()).await;
+        let path = std::env::temp_dir().join(format!(
+            "kasparustwallet-bundle-{}.json",
+            std::process::id()
+        ));
+        bundle.save(&path).unwrap();
+        assert!(bundle.signed_transaction().is_err());
+
+        // Offline: keystore and bundle only.
+        let mut offline = SigningBundle::load(&path).unwrap();
+        assert_eq!(offline.sign(&wallet).unwrap(), 1);
+        offline.save(&path).unwrap();
+        assert!(offline.sign(&test_wallet(0x44)).is_err());
+
+        // Online again.
+        let signed = SigningBundle::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let tx = signed.signed_transaction().unwrap();
+        assert_eq!(tx.tx.id(), bundle.pskt().unwrap().id());
+        node.submit(&tx.tx).await.unwrap();
+        assert_eq!(
+            node.balance(&test_wallet(0x43).address()).await.unwrap(),
+            2 * SOMPI_PER_KAS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tampered_bundle_is_rejected() {
+        let wallet = test_wallet(0x42);
+        let node = MockNode::new().with_utxo(&wallet.address(), 3 * SOMPI_PER_KAS);
+        let bundle = prepared_bundle(&node, &wallet.address()).await;
+        bundle.verify().unwrap();
+
+        let mut redirected = bundle.clone();
+        redirected.address = test_wallet(0x44).get_address();
+        assert!(matches!(
+            redirected.verify(),
+            Err(WalletError::BundleCorrupted(_))
+        ));
+
+        let mut newer = bundle.clone();
+        newer.version = BUNDLE_VERSION + 1;
+        newer.checksum = newer.compute_checksum().unwrap();
+        assert!(matches!(newer.pskt(), Err(WalletError::BundleCorrupted(_))));
+
+        let mut other_network = bundle;
+        other_network.network = "mainnet".to_string();
+        other_network.checksum = other_network.compute_checksum().unwrap();
+        assert!(other_network.verify().is_err());
+    }
+}
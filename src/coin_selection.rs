@@ -0,0 +1,501 @@
+use crate::error::{WalletError, WalletResult};
+use crate::node::NodeApi;
+use crate::transaction::Utxo;
+use kaspa_addresses::Address;
+use rand::seq::SliceRandom;
+use std::fmt;
+
+/// Smallest change output worth creating, in sompi. Smaller change is left
+/// to the fee instead. This is kaspad's relay dust limit for a
+/// pay-to-public-key output at the minimum relay fee.
+pub const DUST_THRESHOLD: u64 = 600;
+
+/// Upper bound on branch-and-bound search steps before falling back.
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// DAA score a coinbase output has to age by before it can be spent. This
+/// is the mainnet and testnet value since Crescendo, 100 seconds at ten
+/// blocks per second.
+pub const COINBASE_MATURITY: u64 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Fewest inputs: spend the biggest UTXOs first.
+    #[default]
+    LargestFirst,
+    /// Consolidate: spend the smallest UTXOs first.
+    SmallestFirst,
+    /// Look for a set of inputs that needs no change output, falling back to
+    /// largest-first when there is none.
+    BranchAndBound,
+    /// Spend UTXOs in random order so the selection does not reveal which
+    /// coins the wallet holds.
+    Random,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::LargestFirst,
+        Strategy::SmallestFirst,
+        Strategy::BranchAndBound,
+        Strategy::Random,
+    ];
+
+    pub fn from_name(name: &str) -> WalletResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "largest-first" | "largest" => Ok(Self::LargestFirst),
+            "smallest-first" | "smallest" => Ok(Self::SmallestFirst),
+            "branch-and-bound" | "bnb" => Ok(Self::BranchAndBound),
+            "random" => Ok(Self::Random),
+            _ => Err(WalletError::InvalidParameters(format!(
+                "Unknown coin selection strategy: {} (expected largest-first, \
+                 smallest-first, branch-and-bound or random)",
+                name
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strategy::LargestFirst => "largest-first",
+            Strategy::SmallestFirst => "smallest-first",
+            Strategy::BranchAndBound => "branch-and-bound",
+            Strategy::Random => "random",
+        })
+    }
+}
+
+/// The inputs chosen for a payment. `input_total = amount + fee + change`,
+/// and `change` is either zero or at least the dust threshold.
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub inputs: Vec<Utxo>,
+    pub fee: u64,
+    pub change: u64,
+}
+
+impl Selection {
+    pub fn input_total(&self) -> u64 {
+        self.inputs.iter().map(|utxo| utxo.entry.amount).sum()
+    }
+
+    pub fn has_change(&self) -> bool {
+        self.change > 0
+    }
+}
+
+/// Chooses inputs for a payment. `fee(inputs, outputs)` must return the fee
+/// in sompi for a transaction spending `inputs` with `outputs` outputs.
+/// Branch-and-bound prunes on the assumption that the fee does not drop as
+/// inputs are added; storage mass can break that, which only costs it the
+/// occasional exact match.
+pub struct CoinSelector<F> {
+    strategy: Strategy,
+    dust_threshold: u64,
+    fee: F,
+}
+
+impl<F: Fn(&[Utxo], usize) -> u64> CoinSelector<F> {
+    pub fn new(strategy: Strategy, fee: F) -> Self {
+        Self {
+            strategy,
+            dust_threshold: DUST_THRESHOLD,
+            fee,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_dust_threshold(mut self, dust_threshold: u64) -> Self {
+        self.dust_threshold = dust_threshold;
+        self
+    }
+
+    /// Selects inputs from `utxos` to pay `amount` sompi across `outputs`
+    /// payment outputs, plus a change output when one is worth creating.
+    pub fn select(&self, utxos: &[Utxo], amount: u64, outputs: usize) -> WalletResult<Selection> {
+        if amount == 0 {
+            return Err(WalletError::InvalidParameters(
+                "Amount must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut ordered = utxos.to_vec();
+        let selection = match self.strategy {
+            Strategy::LargestFirst => {
+                ordered.sort_by(|a, b| b.entry.amount.cmp(&a.entry.amount));
+                self.accumulate(ordered, amount, outputs)
+            }
+            Strategy::SmallestFirst => {
+                ordered.sort_by(|a, b| a.entry.amount.cmp(&b.entry.amount));
+                self.accumulate(ordered, amount, outputs)
+            }
+            Strategy::Random => {
+                ordered.shuffle(&mut rand::thread_rng());
+                self.accumulate(ordered, amount, outputs)
+            }
+            Strategy::BranchAndBound => {
+                ordered.sort_by(|a, b| b.entry.amount.cmp(&a.entry.amount));
+                match self.branch_and_bound(&ordered, amount, outputs) {
+                    Some(selection) => Some(selection),
+                    None => self.accumulate(ordered, amount, outputs),
+                }
+            }
+        };
+
+        selection.ok_or_else(|| WalletError::InsufficientFunds {
+            required: amount.saturating_add((self.fee)(utxos, outputs)),
+            available: utxos.iter().map(|utxo| utxo.entry.amount).sum(),
+        })
+    }
+
+    /// Takes UTXOs in the given order until they cover the payment.
+    fn accumulate(&self, ordered: Vec<Utxo>, amount: u64, outputs: usize) -> Option<Selection> {
+        let mut selected = Vec::new();
+        let mut total = 0u64;
+        for utxo in ordered {
+            total = total.saturating_add(utxo.entry.amount);
+            selected.push(utxo);
+            if let Some((fee, change)) = self.settle(&selected, total, amount, outputs) {
+                return Some(Selection {
+                    inputs: selected,
+                    fee,
+                    change,
+                });
+            }
+        }
+        None
+    }
+
+    /// Splits the surplus of `total` over `amount` into fee and change, or
+    /// returns `None` if `selected` does not cover the payment.
+    fn settle(
+        &self,
+        selected: &[Utxo],
+        total: u64,
+        amount: u64,
+        outputs: usize,
+    ) -> Option<(u64, u64)> {
+        let fee = (self.fee)(selected, outputs);
+        let excess = total.checked_sub(amount.checked_add(fee)?)?;
+        if excess < self.dust_threshold {
+            return Some((fee + excess, 0));
+        }
+
+        let fee_with_change = (self.fee)(selected, outputs + 1);
+        match amount
+            .checked_add(fee_with_change)
+            .and_then(|needed| total.checked_sub(needed))
+        {
+            Some(change) if change >= self.dust_threshold => Some((fee_with_change, change)),
+            _ => Some((fee + excess, 0)),
+        }
+    }
+
+    /// Depth-first search over the UTXOs (sorted largest first) for a set
+    /// that covers the payment with less than the dust threshold left over.
+    fn branch_and_bound(&self, sorted: &[Utxo], amount: u64, outputs: usize) -> Option<Selection> {
+        let mut remaining = vec![0u64; sorted.len() + 1];
+        for index in (0..sorted.len()).rev() {
+            remaining[index] = remaining[index + 1].saturating_add(sorted[index].entry.amount);
+        }
+
+        let mut search = BnbSearch {
+            selector: self,
+            utxos: sorted,
+            remaining,
+            amount,
+            outputs,
+            tries: 0,
+            selected: Vec::new(),
+        };
+        if !search.search(0, 0) {
+            return None;
+        }
+
+        let inputs = search.selected;
+        let total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+        Some(Selection {
+            fee: total - amount,
+            inputs,
+            change: 0,
+        })
+    }
+}
+
+/// Whether `utxo` can be spent in a block at `virtual_daa_score`.
+/// Everything can except coinbase outputs younger than
+/// [`COINBASE_MATURITY`].
+pub fn is_mature(utxo: &Utxo, virtual_daa_score: u64) -> bool {
+    !utxo.entry.is_coinbase
+        || utxo.entry.block_daa_score.saturating_add(COINBASE_MATURITY) <= virtual_daa_score
+}
+
+/// The UTXOs of `addresses` that can be spent now. kaspad rejects a
+/// transaction with an immature coinbase input, so those are left out
+/// before any selection.
+pub async fn spendable_utxos(node: &dyn NodeApi, addresses: &[Address]) -> WalletResult<Vec<Utxo>> {
+    let mut utxos = node
+        .utxos(addresses)
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to fetch UTXOs: {}", e)))?;
+    if utxos.iter().any(|utxo| utxo.entry.is_coinbase) {
+        let virtual_daa_score = node
+            .virtual_daa_score()
+            .await
+            .map_err(|e| WalletError::Network(format!("Failed to get DAA score: {}", e)))?;
+        utxos.retain(|utxo| is_mature(utxo, virtual_daa_score));
+    }
+    Ok(utxos)
+}
+
+struct BnbSearch<'a, F> {
+    selector: &'a CoinSelector<F>,
+    utxos: &'a [Utxo],
+    remaining: Vec<u64>,
+    amount: u64,
+    outputs: usize,
+    tries: usize,
+    selected: Vec<Utxo>,
+}
+
+impl<F: Fn(&[Utxo], usize) -> u64> BnbSearch<'_, F> {
+    fn search(&mut self, index: usize, total: u64) -> bool {
+        self.tries += 1;
+        if self.tries > BNB_MAX_TRIES {
+            return false;
+        }
+
+        let needed = self
+            .amount
+            .saturating_add((self.selector.fee)(&self.selected, self.outputs));
+        if total >= needed {
+            // Adding inputs only grows the surplus, so this branch ends here.
+            return total - needed < self.selector.dust_threshold;
+        }
+        if index == self.utxos.len() || total.saturating_add(self.remaining[index]) < needed {
+            return false;
+        }
+
+        let utxo = &self.utxos[index];
+        self.selected.push(utxo.clone());
+        if self.search(index + 1, total.saturating_add(utxo.entry.amount)) {
+            return true;
+        }
+        self.selected.pop();
+        self.search(index + 1, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_node::MockNode;
+    use crate::test_util::{test_wallet, utxo};
+    use kaspa_consensus_core::tx::ScriptPublicKey;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn utxos(amounts: &[u64]) -> Vec<Utxo> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| {
+                utxo(index as u32, *amount, ScriptPublicKey::from_vec(0, vec![]))
+            })
+            .collect()
+    }
+
+    /// 100 sompi per input and 50 per output.
+    fn linear_fee(inputs: &[Utxo], outputs: usize) -> u64 {
+        100 * inputs.len() as u64 + 50 * outputs as u64
+    }
+
+    fn amounts(selection: &Selection) -> Vec<u64> {
+        selection
+            .inputs
+            .iter()
+            .map(|utxo| utxo.entry.amount)
+            .collect()
+    }
+
+    #[test]
+    fn test_strategy_names() {
+        for strategy in Strategy::ALL {
+            assert_eq!(
+                Strategy::from_name(&strategy.to_string()).unwrap(),
+                strategy
+            );
+        }
+        assert_eq!(
+            Strategy::from_name("bnb").unwrap(),
+            Strategy::BranchAndBound
+        );
+        assert!(Strategy::from_name("fifo").is_err());
+    }
+
+    #[test]
+    fn test_largest_and_smallest_first() {
+        let set = utxos(&[3_000, 10_000, 4_000]);
+
+        let largest = CoinSelector::new(Strategy::LargestFirst, linear_fee)
+            .select(&set, 6_750, 1)
+            .unwrap();
+        assert_eq!(amounts(&largest), vec![10_000]);
+        assert_eq!(largest.fee, 200);
+        assert_eq!(largest.change, 3_050);
+
+        let smallest = CoinSelector::new(Strategy::SmallestFirst, linear_fee)
+            .select(&set, 6_750, 1)
+            .unwrap();
+        assert_eq!(amounts(&smallest), vec![3_000, 4_000]);
+        assert_eq!(smallest.change, 0);
+        assert_eq!(smallest.fee, 250);
+    }
+
+    #[test]
+    fn test_branch_and_bound_finds_changeless_match() {
+        let set = utxos(&[10_000, 4_000, 3_000]);
+        let selection = CoinSelector::new(Strategy::BranchAndBound, linear_fee)
+            .select(&set, 6_750, 1)
+            .unwrap();
+        assert_eq!(amounts(&selection), vec![4_000, 3_000]);
+        assert!(!selection.has_change());
+        assert_eq!(selection.fee, 250);
+
+        // No exact match: falls back to largest-first with change.
+        let selection = CoinSelector::new(Strategy::BranchAndBound, linear_fee)
+            .select(&set, 1_000, 1)
+            .unwrap();
+        assert_eq!(amounts(&selection), vec![10_000]);
+        assert!(selection.has_change());
+    }
+
+    #[test]
+    fn test_dust_change_goes_to_fee() {
+        let set = utxos(&[7_500]);
+        let selection = CoinSelector::new(Strategy::LargestFirst, linear_fee)
+            .select(&set, 7_000, 1)
+            .unwrap();
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 500);
+
+        let selection = CoinSelector::new(Strategy::LargestFirst, linear_fee)
+            .with_dust_threshold(100)
+            .select(&set, 7_000, 1)
+            .unwrap();
+        assert_eq!(selection.change, 300);
+        assert_eq!(selection.fee, 200);
+    }
+
+    #[test]
+    fn test_insufficient_funds() {
+        let set = utxos(&[1_000, 2_000]);
+        let result = CoinSelector::new(Strategy::Random, linear_fee).select(&set, 3_000, 1);
+        assert!(matches!(
+            result,
+            Err(WalletError::InsufficientFunds {
+                required: 3_250,
+                available: 3_000
+            })
+        ));
+        assert!(CoinSelector::new(Strategy::LargestFirst, linear_fee)
+            .select(&[], 1, 1)
+            .is_err());
+        assert!(CoinSelector::new(Strategy::LargestFirst, linear_fee)
+            .select(&set, 0, 1)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_immature_coinbase_is_not_spendable() {
+        let address = test_wallet(0x42).address();
+        let node = MockNode::new().with_virtual_daa_score(1_000);
+        let reward = node.add_coinbase_utxo(&address, 50_000_000);
+        let payment = node.add_utxo(&address, 20_000_000);
+
+        node.set_virtual_daa_score(1_000 + COINBASE_MATURITY - 1);
+        let spendable = spendable_utxos(&node, std::slice::from_ref(&address))
+            .await
+            .unwrap();
+        assert_eq!(spendable.len(), 1);
+        assert_eq!(spendable[0].outpoint, payment.outpoint);
+
+        node.set_virtual_daa_score(1_000 + COINBASE_MATURITY);
+        let spendable = spendable_utxos(&node, std::slice::from_ref(&address))
+            .await
+            .unwrap();
+        assert!(spendable
+            .iter()
+            .any(|utxo| utxo.outpoint == reward.outpoint));
+        assert_eq!(spendable.len(), 2);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_selection_is_balanced(
+            amounts in prop::collection::vec(1u64..2_000_000_000, 0..40),
+            target in 1u64..20_000_000_000,
+            outputs in 1usize..4,
+        ) {
+            let set = utxos(&amounts);
+            let available: u64 = amounts.iter().sum();
+
+            for strategy in Strategy::ALL {
+                match CoinSelector::new(strategy, linear_fee).select(&set, target, outputs) {
+                    Ok(selection) => {
+                        prop_assert_eq!(
+                            selection.input_total(),
+                            target + selection.fee + selection.change
+                        );
+                        prop_assert!(
+                            selection.change == 0 || selection.change >= DUST_THRESHOLD
+                        );
+                        let output_count = outputs + usize::from(selection.has_change());
+                        prop_assert!(
+                            selection.fee >= linear_fee(&selection.inputs, output_count)
+                        );
+
+                        let outpoints: HashSet<_> =
+                            selection.inputs.iter().map(|utxo| utxo.outpoint).collect();
+                        prop_assert_eq!(outpoints.len(), selection.inputs.len());
+                        prop_assert!(outpoints
+                            .iter()
+                            .all(|outpoint| set.iter().any(|utxo| utxo.outpoint == *outpoint)));
+                    }
+                    Err(WalletError::InsufficientFunds { required, available: reported }) => {
+                        prop_assert_eq!(reported, available);
+                        prop_assert!(available < required);
+                        prop_assert_eq!(required, target + linear_fee(&set, outputs));
+                    }
+                    Err(other) => prop_assert!(false, "unexpected error {}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn prop_branch_and_bound_finds_exact_subsets(
+            amounts in prop::collection::vec(1_000u64..50_000, 1..15),
+            picks in prop::collection::vec(any::<bool>(), 15),
+        ) {
+            // Build a target that some subset pays exactly.
+            let set = utxos(&amounts);
+            let subset: Vec<Utxo> = set
+                .iter()
+                .zip(&picks)
+                .filter(|(_, pick)| **pick)
+                .map(|(utxo, _)| utxo.clone())
+                .collect();
+            let total: u64 = subset.iter().map(|utxo| utxo.entry.amount).sum();
+            let fee = linear_fee(&subset, 1);
+            prop_assume!(!subset.is_empty() && total > fee);
+
+            let selection = CoinSelector::new(Strategy::BranchAndBound, linear_fee)
+                .select(&set, total - fee, 1)
+                .unwrap();
+            prop_assert!(!selection.has_change());
+            prop_assert!(selection.fee - linear_fee(&selection.inputs, 1) < DUST_THRESHOLD);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::{WalletError, WalletResult};
+use crate::mass;
+use crate::transaction::Utxo;
+use crate::wallet::KaspaWallet;
+use kaspa_consensus_core::tx::{
+    SignableTransaction, TransactionOutpoint, TransactionOutput, UtxoEntry,
+};
+
+/// Consolidation transactions merging a wallet's UTXOs, in the order they
+/// must be submitted, and the UTXO set the wallet holds once they are.
+pub struct Compound {
+    pub transactions: Vec<SignableTransaction>,
+    pub utxos: Vec<Utxo>,
+}
+
+impl Compound {
+    pub fn fee(&self) -> u64 {
+        self.transactions.iter().map(transaction_fee).sum()
+    }
+}
+
+/// How many of `utxos`, taken in order, can be spent into `outputs` by one
+/// transaction without exceeding the standard mass limit.
+pub fn fitting_inputs(utxos: &[Utxo], outputs: &[TransactionOutput]) -> usize {
+    let mut count = 0;
+    while count < utxos.len()
+        && mass::estimate_compute_mass(&utxos[..=count], outputs)
+            <= mass::MAXIMUM_STANDARD_TRANSACTION_MASS
+    {
+        count += 1;
+    }
+    count
+}
+
+/// Merges `utxos` into transactions paying back to `wallet` until at most
+/// `target` remain. Smallest outputs are merged first, and a merged output
+/// can be merged again, so one call may produce a chain of transactions
+/// each spending the previous one's output.
+pub fn compound(
+    wallet: &KaspaWallet,
+    mut utxos: Vec<Utxo>,
+    target: usize,
+    fee_rate: u64,
+) -> WalletResult<Compound> {
+    if target == 0 {
+        return Err(WalletError::InvalidParameters(
+            "Compounding must leave at least one UTXO".to_string(),
+        ));
+    }
+
+    let script = wallet.script_public_key();
+    let merged = [TransactionOutput::new(0, script.clone())];
+    let mut transactions = Vec::new();
+    utxos.sort_by_key(|utxo| utxo.entry.amount);
+
+    while utxos.len() > target {
+        let count = fitting_inputs(&utxos, &merged).min(utxos.len() - target + 1);
+        if count < 2 {
+            return Err(WalletError::MassLimitExceeded {
+                mass: mass::estimate_compute_mass(&utxos[..2], &merged),
+                limit: mass::MAXIMUM_STANDARD_TRANSACTION_MASS,
+            });
+        }
+
+        let inputs: Vec<Utxo> = utxos.drain(..count).collect();
+        let input_total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+        let fee = mass::estimate_fee_with_change(&inputs, &[], Some(&script), fee_rate);
+        let value = input_total
+            .checked_sub(fee)
+            .filter(|value| *value > 0)
+            .ok_or(WalletError::InsufficientFunds {
+                required: fee,
+                available: input_total,
+            })?;
+
+        let tx =
+            wallet.create_transaction(inputs, vec![(wallet.get_address(), value)], fee_rate)?;
+        let output = Utxo::new(
+            TransactionOutpoint::new(tx.tx.id(), 0),
+            UtxoEntry::new(value, script.clone(), 0, false),
+        );
+        let position = utxos.partition_point(|utxo| utxo.entry.amount < value);
+        utxos.insert(position, output);
+        transactions.push(tx);
+    }
+
+    Ok(Compound {
+        transactions,
+        utxos,
+    })
+}
+
+pub fn transaction_fee(tx: &SignableTransaction) -> u64 {
+    let input_total: u64 = tx.entries.iter().flatten().map(|entry| entry.amount).sum();
+    let output_total: u64 = tx.tx.outputs.iter().map(|output| output.value).sum();
+    input_total.saturating_sub(output_total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::signer::verify_input;
+    use crate::test_util::{secret_key, test_wallet, utxo};
+    use secp256k1::{PublicKey, Secp256k1};
+
+    fn payouts(wallet: &KaspaWallet, count: u32) -> Vec<Utxo> {
+        (0..count)
+            .map(|index| utxo(index, SOMPI_PER_KAS, wallet.script_public_key()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fitting_inputs() {
+        let wallet = test_wallet(0x42);
+        let utxos = payouts(&wallet, 200);
+        let merged = [TransactionOutput::new(0, wallet.script_public_key())];
+
+        // 94 + 412 for the output, then 1118 per signed input.
+        assert_eq!(fitting_inputs(&utxos, &merged), 88);
+        assert_eq!(fitting_inputs(&utxos[..3], &merged), 3);
+    }
+
+    #[test]
+    fn test_compound_chains_transactions() {
+        let wallet = test_wallet(0x42);
+        let result = compound(&wallet, payouts(&wallet, 200), 1, 1).unwrap();
+
+        // 88 + 88 inputs, then 24 left plus the two merged outputs.
+        assert_eq!(result.transactions.len(), 3);
+        assert_eq!(result.utxos.len(), 1);
+        let last = result.transactions.last().unwrap();
+        assert_eq!(last.tx.inputs.len(), 26);
+        let first_id = result.transactions[0].tx.id();
+        assert!(last
+            .tx
+            .inputs
+            .iter()
+            .any(|input| input.previous_outpoint.transaction_id == first_id));
+        assert_eq!(
+            result.utxos[0].entry.amount,
+            200 * SOMPI_PER_KAS - result.fee()
+        );
+
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key(0x42));
+        for tx in &result.transactions {
+            let tx_mass = mass::transaction_mass(tx).unwrap();
+            assert!(tx_mass <= mass::MAXIMUM_STANDARD_TRANSACTION_MASS);
+            assert_eq!(transaction_fee(tx), mass::fee_for_mass(tx_mass, 1));
+            verify_input(tx, 0, &public_key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_compound_to_target() {
+        let wallet = test_wallet(0x42);
+        let result = compound(&wallet, payouts(&wallet, 10), 4, 1).unwrap();
+        assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.transactions[0].tx.inputs.len(), 7);
+        assert_eq!(result.utxos.len(), 4);
+
+        let untouched = compound(&wallet, payouts(&wallet, 3), 5, 1).unwrap();
+        assert!(untouched.transactions.is_empty());
+        assert!(compound(&wallet, payouts(&wallet, 3), 0, 1).is_err());
+    }
+}
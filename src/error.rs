@@ -14,10 +14,36 @@ pub enum WalletError {
     #[error("Invalid address format")]
     InvalidAddressFormat,
 
+    #[error("Transaction error: {0}")]
+    Transaction(String),
+
+    #[error("Insufficient funds: {required} sompi required, {available} sompi available")]
+    InsufficientFunds { required: u64, available: u64 },
+
+    #[error("Transaction mass {mass} exceeds the standard limit of {limit}")]
+    MassLimitExceeded { mass: u64, limit: u64 },
+
+    #[error("PSKT error: {0}")]
+    Pskt(String),
+
+    #[error("Signing bundle is corrupted or has been modified: {0}")]
+    BundleCorrupted(String),
+
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("Wrong keystore password")]
+    WrongPassword,
+
+    #[error("Keystore is corrupted or has been tampered with: {0}")]
+    KeystoreTampered(String),
+
     #[error("I/O error: {0}")]
     Io(String),
 }
 
+pub type WalletResult<T> = Result<T, WalletError>;
+
 impl From<std::io::Error> for WalletError {
     fn from(error: std::io::Error) -> Self {
         WalletError::Io(error.to_string())
@@ -41,3 +67,9 @@ impl From<secp256k1::Error> for WalletError {
         WalletError::KeyGeneration(format!("Secp256k1 error: {}", error))
     }
 }
+
+impl From<bip39::Error> for WalletError {
+    fn from(error: bip39::Error) -> Self {
+        WalletError::InvalidMnemonic(error.to_string())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::network::NetworkConfig;
+use crate::rpc::{
+    FeeEstimateResponse, GetBalanceByAddressResponse, MempoolEntriesResponse, OutpointResponse,
+    RpcError, ScriptPublicKeyResponse, UtxoEntryResponse, UtxoResponse,
+};
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{Transaction, TransactionId};
+use kaspa_rpc_core::api::rpc::RpcApi;
+use kaspa_rpc_core::{RpcTransaction, RpcUtxosByAddressesEntry};
+
+const GRPC_SCHEME: &str = "grpc://";
+
+/// kaspad gRPC (protowire) connection. The methods mirror `RpcClient` and
+/// return the same response types, so callers can swap transports without
+/// touching the code that uses the results.
+pub struct GrpcClient {
+    url: String,
+    client: kaspa_grpc_client::GrpcClient,
+}
+
+impl GrpcClient {
+    pub async fn connect(url: &str) -> Result<Self, RpcError> {
+        let url = grpc_url(url);
+        let client = kaspa_grpc_client::GrpcClient::connect(url.clone())
+            .await
+            .map_err(|e| RpcError::Connection(format!("{}: {}", url, e)))?;
+        Ok(Self { url, client })
+    }
+
+    /// Connects to the local node endpoint configured for `network`.
+    pub async fn for_network(network: &NetworkConfig) -> Result<Self, RpcError> {
+        Self::connect(&network.rpc_url).await
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn disconnect(&self) -> Result<(), RpcError> {
+        self.client
+            .disconnect()
+            .await
+            .map_err(|e| RpcError::Connection(e.to_string()))
+    }
+
+    pub async fn get_balance_by_address(
+        &self,
+        address: &str,
+    ) -> Result<GetBalanceByAddressResponse, RpcError> {
+        let balance = self
+            .client
+            .get_balance_by_address(parse_address(address)?)
+            .await
+            .map_err(rpc_error)?;
+        Ok(GetBalanceByAddressResponse { balance })
+    }
+
+    pub async fn get_utxos_by_address(&self, address: &str) -> Result<Vec<UtxoResponse>, RpcError> {
+        let entries = self
+            .client
+            .get_utxos_by_addresses(vec![parse_address(address)?])
+            .await
+            .map_err(rpc_error)?;
+        Ok(entries.into_iter().map(utxo_response).collect())
+    }
+
+    /// Mempool transactions touching `address`, including orphans.
+    pub async fn get_mempool_entries_by_address(
+        &self,
+        address: &str,
+    ) -> Result<MempoolEntriesResponse, RpcError> {
+        let address = parse_address(address)?;
+        let entries = self
+            .client
+            .get_mempool_entries_by_addresses(vec![address.clone()], true, false)
+            .await
+            .map_err(rpc_error)?;
+        MempoolEntriesResponse::from_entries(&address, entries)
+    }
+
+    /// Input signature scripts of a transaction in the node's mempool.
+    /// kaspad does not index accepted transactions, so those are not found.
+    pub async fn get_mempool_signature_scripts(
+        &self,
+        transaction_id: TransactionId,
+    ) -> Result<Vec<Vec<u8>>, RpcError> {
+        let entry = self
+            .client
+            .get_mempool_entry(transaction_id, true, false)
+            .await
+            .map_err(rpc_error)?;
+        Ok(entry
+            .transaction
+            .inputs
+            .into_iter()
+            .map(|input| input.signature_script)
+            .collect())
+    }
+
+    pub async fn get_fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        let estimate = self.client.get_fee_estimate().await.map_err(rpc_error)?;
+        Ok(estimate.into())
+    }
+
+    pub async fn get_virtual_daa_score(&self) -> Result<u64, RpcError> {
+        let info = self.client.get_server_info().await.map_err(rpc_error)?;
+        Ok(info.virtual_daa_score)
+    }
+
+    pub async fn get_past_median_time(&self) -> Result<u64, RpcError> {
+        let info = self.client.get_block_dag_info().await.map_err(rpc_error)?;
+        Ok(info.past_median_time)
+    }
+
+    /// Submits a signed transaction and returns the id reported by the node.
+    pub async fn submit_transaction(&self, tx: &Transaction) -> Result<String, RpcError> {
+        let transaction_id = self
+            .client
+            .submit_transaction(RpcTransaction::from(tx), false)
+            .await
+            .map_err(rpc_error)?;
+        Ok(transaction_id.to_string())
+    }
+}
+
+/// kaspad only accepts `grpc://host:port`; `NetworkConfig` stores the bare
+/// `host:port`.
+fn grpc_url(url: &str) -> String {
+    if url.starts_with(GRPC_SCHEME) {
+        url.to_string()
+    } else {
+        format!("{}{}", GRPC_SCHEME, url)
+    }
+}
+
+fn parse_address(address: &str) -> Result<Address, RpcError> {
+    Address::try_from(address)
+        .map_err(|e| RpcError::Rpc(format!("Invalid address {}: {}", address, e)))
+}
+
+fn rpc_error(error: kaspa_rpc_core::RpcError) -> RpcError {
+    RpcError::Rpc(error.to_string())
+}
+
+fn utxo_response(entry: RpcUtxosByAddressesEntry) -> UtxoResponse {
+    let script = entry.utxo_entry.script_public_key;
+    UtxoResponse {
+        address: entry.address.map(|address| address.to_string()),
+        outpoint: OutpointResponse {
+            transaction_id: entry.outpoint.transaction_id.to_string(),
+            index: entry.outpoint.index,
+        },
+        utxo_entry: UtxoEntryResponse {
+            amount: entry.utxo_entry.amount,
+            script_public_key: ScriptPublicKeyResponse {
+                version: script.version(),
+                script_public_key: hex::encode(script.script()),
+            },
+            block_daa_score: entry.utxo_entry.block_daa_score,
+            is_coinbase: entry.utxo_entry.is_coinbase,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_wallet, TXID};
+    use crate::transaction::parse_outpoint;
+    use kaspa_consensus_core::tx::{TransactionOutpoint, UtxoEntry};
+    use kaspa_rpc_core::{RpcFeeEstimate, RpcFeerateBucket};
+
+    #[test]
+    fn test_grpc_url_adds_scheme() {
+        assert_eq!(
+            grpc_url(&NetworkConfig::mainnet().rpc_url),
+            "grpc://127.0.0.1:16110"
+        );
+        assert_eq!(grpc_url("grpc://node.lan:16210"), "grpc://node.lan:16210");
+    }
+
+    #[test]
+    fn test_utxo_entry_matches_rest_response() {
+        let wallet = test_wallet(0x42);
+        let entry = RpcUtxosByAddressesEntry {
+            address: Some(wallet.address()),
+            outpoint: TransactionOutpoint::new(TXID.parse().unwrap(), 3).into(),
+            utxo_entry: UtxoEntry::new(5_000, wallet.script_public_key(), 77, false).into(),
+        };
+
+        let response = utxo_response(entry);
+        assert_eq!(
+            response.address.as_deref(),
+            Some(wallet.get_address().as_str())
+        );
+
+        let utxo = response.to_utxo().unwrap();
+        assert_eq!(utxo.outpoint, parse_outpoint(TXID, 3).unwrap());
+        assert_eq!(utxo.entry.amount, 5_000);
+        assert_eq!(utxo.entry.block_daa_score, 77);
+        assert_eq!(utxo.entry.script_public_key, wallet.script_public_key());
+    }
+
+    #[test]
+    fn test_fee_estimate_conversion() {
+        let estimate = RpcFeeEstimate {
+            priority_bucket: RpcFeerateBucket {
+                feerate: 3.0,
+                estimated_seconds: 1.0,
+            },
+            normal_buckets: vec![RpcFeerateBucket {
+                feerate: 2.0,
+                estimated_seconds: 10.0,
+            }],
+            low_buckets: vec![],
+        };
+
+        let response = FeeEstimateResponse::from(estimate);
+        assert_eq!(response.priority_bucket.feerate, 3.0);
+        assert_eq!(response.normal_buckets[0].estimated_seconds, 10.0);
+        assert!(response.low_buckets.is_empty());
+    }
+}
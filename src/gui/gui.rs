@@ -1,9 +1,11 @@
-use crate::address::validate_address;
+use crate::address::{validate_address, SignatureScheme};
+use crate::amount::Amount;
 use crate::error::WalletError;
+use crate::key_source::parse_private_key;
+use crate::secret::SecretString;
 use crate::wallet::KaspaWallet;
 use iced::widget::{button, column, pick_list, row, text, text_input, Column, Container};
 use iced::{Element, Length};
-use secp256k1::SecretKey;
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,8 +16,9 @@ pub enum Tab {
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    PrivateKeyInput(String),
+    PrivateKeyInput(SecretString),
     NetworkSelected(NetworkOption),
+    SchemeSelected(SignatureScheme),
     CreateWallet,
     LoadWallet,
     RecipientInput(String),
@@ -65,13 +68,15 @@ impl NetworkOption {
 #[derive(Debug, Clone)]
 pub struct OutputRow {
     pub address: String,
-    pub amount: String,
+    pub amount: Amount,
 }
 
-#[derive(Debug, Clone)]
+/// Holds the private key, so it deliberately implements neither `Debug` nor
+/// `Clone`.
 pub struct WalletGui {
-    private_key: String,
+    private_key: SecretString,
     network: NetworkOption,
+    scheme: SignatureScheme,
     wallet: Option<KaspaGuiWallet>,
     current_tab: Tab,
     recipient: String,
@@ -96,8 +101,9 @@ pub struct KaspaGuiWallet {
 impl WalletGui {
     fn new() -> Self {
         Self {
-            private_key: String::new(),
+            private_key: SecretString::default(),
             network: NetworkOption::Mainnet,
+            scheme: SignatureScheme::default(),
             wallet: None,
             current_tab: Tab::Send,
             recipient: String::new(),
@@ -113,10 +119,16 @@ impl WalletGui {
         }
     }
 
+    fn key_wallet(&self) -> Result<KaspaWallet, WalletError> {
+        let secret_key = parse_private_key(self.private_key.expose_secret())?;
+        Ok(
+            KaspaWallet::with_network(secret_key, self.network.to_str())?
+                .with_signature_scheme(self.scheme),
+        )
+    }
+
     fn load_wallet(&mut self) -> Result<(), WalletError> {
-        let secret_key_bytes = hex::decode(&self.private_key)?;
-        let secret_key = SecretKey::from_slice(&secret_key_bytes)?;
-        let wallet = KaspaWallet::with_network(secret_key, self.network.to_str())?;
+        let wallet = self.key_wallet()?;
         self.wallet = Some(KaspaGuiWallet {
             address: wallet.get_address(),
             public_key: wallet.get_public_key(),
@@ -218,9 +230,9 @@ fn update(state: &mut WalletGui, message: Message) {
     match message {
         Message::PrivateKeyInput(key) => {
             state.private_key = key;
-            if KaspaWallet::validate_private_key(&state.private_key) {
+            if parse_private_key(state.private_key.expose_secret()).is_ok() {
                 state.status_message = "Private key is valid".to_string();
-            } else if state.private_key.len() == 64 {
+            } else if state.private_key.expose_secret().len() == 64 {
                 state.status_message = "Invalid private key format".to_string();
             } else {
                 state.status_message = String::new();
@@ -229,10 +241,15 @@ fn update(state: &mut WalletGui, message: Message) {
         Message::NetworkSelected(network) => {
             state.network = network;
         }
+        Message::SchemeSelected(scheme) => {
+            state.scheme = scheme;
+        }
         Message::CreateWallet => {
             let secp = secp256k1::Secp256k1::new();
             let (secret_key, _) = secp.generate_keypair(&mut rand::rngs::OsRng);
-            let wallet = KaspaWallet::with_network(secret_key, state.network.to_str()).unwrap();
+            let wallet = KaspaWallet::with_network(secret_key, state.network.to_str())
+                .unwrap()
+                .with_signature_scheme(state.scheme);
             state.private_key = wallet.get_private_key();
             state.wallet = Some(KaspaGuiWallet {
                 address: wallet.get_address(),
@@ -260,44 +277,22 @@ fn update(state: &mut WalletGui, message: Message) {
             }
         },
         Message::SendTransaction => {
+            // Building a payment needs the node, and the GUI has no async
+            // runtime to reach it without blocking, so hand off to the CLI.
             if state.outputs.is_empty() {
                 state.status_message = "No outputs to send".to_string();
                 return;
             }
-            let parsed_outputs: Result<Vec<(String, u64)>, _> = state
+            let outputs: Vec<String> = state
                 .outputs
                 .iter()
-                .map(|o| {
-                    let amount_kas: f64 = o.amount.parse().unwrap_or(0.0);
-                    let amount_sompi = (amount_kas * 100_000_000.0) as u64;
-                    Ok::<(String, u64), ()>((o.address.clone(), amount_sompi))
-                })
+                .map(|o| format!("--to {} --amount {}", o.address, o.amount.to_kas_string()))
                 .collect();
-            match parsed_outputs {
-                Ok(outputs) => {
-                    if let Err(e) = state.load_wallet() {
-                        state.status_message = format!("Error: {}", e);
-                        return;
-                    }
-                    let secret_key_bytes = hex::decode(&state.private_key).unwrap();
-                    let secret_key = SecretKey::from_slice(&secret_key_bytes).unwrap();
-                    let wallet =
-                        KaspaWallet::with_network(secret_key, state.network.to_str()).unwrap();
-                    match wallet.create_transaction(vec![], outputs, 1000) {
-                        Ok(tx) => {
-                            let serialized = tx.serialize().unwrap();
-                            state.status_message =
-                                format!("Transaction created: {}", hex::encode(&serialized));
-                        }
-                        Err(e) => {
-                            state.status_message = format!("Transaction error: {}", e);
-                        }
-                    }
-                }
-                Err(_) => {
-                    state.status_message = "Invalid output format".to_string();
-                }
-            }
+            state.status_message = format!(
+                "Sending from the GUI is not supported yet. Run: kasparustwallet send --network {} {}",
+                state.network.to_str(),
+                outputs.join(" ")
+            );
         }
         Message::RecipientInput(addr) => {
             state.recipient = addr;
@@ -308,12 +303,11 @@ fn update(state: &mut WalletGui, message: Message) {
         Message::AddOutput => {
             if !state.recipient.is_empty() && !state.amount.is_empty() {
                 if validate_address(&state.recipient).unwrap_or(false) {
-                    match state.amount.parse::<f64>() {
-                        Ok(amount_kas) if amount_kas > 0.0 => {
-                            let amount_sompi = (amount_kas * 100_000_000.0) as u64;
+                    match Amount::parse(&state.amount) {
+                        Ok(amount) if amount > Amount::ZERO => {
                             state.outputs.push(OutputRow {
                                 address: state.recipient.clone(),
-                                amount: format!("{} KAS ({})", amount_kas, amount_sompi),
+                                amount,
                             });
                             state.recipient.clear();
                             state.amount.clear();
@@ -326,9 +320,8 @@ fn update(state: &mut WalletGui, message: Message) {
                         Ok(_) => {
                             state.status_message = "Amount must be greater than 0".to_string();
                         }
-                        Err(_) => {
-                            state.status_message =
-                                "Invalid amount format. Use decimal (e.g., 1.5)".to_string();
+                        Err(e) => {
+                            state.status_message = e.to_string();
                         }
                     }
                 } else {
@@ -352,7 +345,9 @@ fn update(state: &mut WalletGui, message: Message) {
         Message::GenerateAddress => {
             let secp = secp256k1::Secp256k1::new();
             let (secret_key, _) = secp.generate_keypair(&mut rand::rngs::OsRng);
-            let wallet = KaspaWallet::with_network(secret_key, state.network.to_str()).unwrap();
+            let wallet = KaspaWallet::with_network(secret_key, state.network.to_str())
+                .unwrap()
+                .with_signature_scheme(state.scheme);
             state.private_key = wallet.get_private_key();
             state.wallet = Some(KaspaGuiWallet {
                 address: wallet.get_address(),
@@ -453,9 +448,16 @@ fn view(state: &WalletGui) -> Element<Message> {
             Some(state.network.clone()),
             Message::NetworkSelected
         ),
+        text("Signature Scheme:").size(14),
+        pick_list(
+            SignatureScheme::ALL.to_vec(),
+            Some(state.scheme),
+            Message::SchemeSelected
+        ),
         text("Private Key:").size(14),
-        text_input("Enter private key (hex)", &state.private_key)
-            .on_input(Message::PrivateKeyInput),
+        text_input("Enter private key (hex)", state.private_key.expose_secret())
+            .secure(true)
+            .on_input(|key| Message::PrivateKeyInput(key.into())),
         row![
             button("Load Wallet").on_press(Message::LoadWallet),
             button("Create New Wallet").on_press(Message::CreateWallet),
@@ -525,7 +527,11 @@ fn view_send(state: &WalletGui) -> Column<Message> {
                 col.push(
                     row![
                         text(format!("{}: {}", idx, output.address)),
-                        text(&output.amount),
+                        text(format!(
+                            "{} ({} sompi)",
+                            output.amount,
+                            output.amount.sompi()
+                        )),
                         button("Remove").on_press(Message::RemoveOutput(idx)),
                     ]
                     .spacing(10),
@@ -552,7 +558,7 @@ fn view_send(state: &WalletGui) -> Column<Message> {
             button("Clear All").on_press(Message::ClearOutputs),
         ]
         .spacing(10),
-        text("Note: Amount is in KAS. 1 KAS = 100,000,000 sompi").size(12),
+        text("Note: Amount is in KAS unless a unit is given (1.5 KAS, 150000000 sompi)").size(12),
     ]
 }
 
@@ -0,0 +1,176 @@
+use crate::address::{address_from_public_key, SignatureScheme};
+use crate::bip32::{kaspa_account_path, ExtendedPrivateKey, KPUB_VERSION, KTUB_VERSION};
+use crate::error::{WalletError, WalletResult};
+use crate::mnemonic::master_key_from_mnemonic;
+use crate::network::NetworkConfig;
+use bip39::Mnemonic;
+use kaspa_addresses::{Address, Prefix};
+
+pub const RECEIVE_CHAIN: u32 = 0;
+pub const CHANGE_CHAIN: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressChain {
+    Receive,
+    Change,
+}
+
+impl AddressChain {
+    pub fn index(self) -> u32 {
+        match self {
+            AddressChain::Receive => RECEIVE_CHAIN,
+            AddressChain::Change => CHANGE_CHAIN,
+        }
+    }
+}
+
+/// A BIP-44 account on Kaspa's coin type (`m/44'/111111'/account'`) with a
+/// receive chain (`/0/i`) and a change chain (`/1/i`).
+pub struct HdAccount {
+    account_key: ExtendedPrivateKey,
+    account_index: u32,
+    network_config: NetworkConfig,
+    scheme: SignatureScheme,
+}
+
+impl HdAccount {
+    pub fn from_master(
+        master: &ExtendedPrivateKey,
+        account_index: u32,
+        network_config: NetworkConfig,
+    ) -> WalletResult<Self> {
+        if master.depth() != 0 {
+            return Err(WalletError::KeyGeneration(
+                "Accounts must be derived from a master key".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            account_key: master.derive_path(&kaspa_account_path(account_index))?,
+            account_index,
+            network_config,
+            scheme: SignatureScheme::default(),
+        })
+    }
+
+    pub fn from_mnemonic(
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        account_index: u32,
+        network_config: NetworkConfig,
+    ) -> WalletResult<Self> {
+        let master = master_key_from_mnemonic(mnemonic, passphrase)?;
+        Self::from_master(&master, account_index, network_config)
+    }
+
+    pub fn with_signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn derivation_path(&self, chain: AddressChain, index: u32) -> String {
+        format!(
+            "{}/{}/{}",
+            kaspa_account_path(self.account_index),
+            chain.index(),
+            index
+        )
+    }
+
+    /// The account's extended public key, `kpub` on mainnet and `ktub`
+    /// elsewhere, for sharing with multisig cosigners.
+    pub fn account_xpub(&self) -> String {
+        let version = if self.network_config.address_prefix() == Prefix::Mainnet {
+            KPUB_VERSION
+        } else {
+            KTUB_VERSION
+        };
+        self.account_key.extended_public_key().encode(version)
+    }
+
+    pub fn derive_key(&self, chain: AddressChain, index: u32) -> WalletResult<ExtendedPrivateKey> {
+        self.account_key
+            .derive_child(chain.index())?
+            .derive_child(index)
+    }
+
+    pub fn derive_address(&self, chain: AddressChain, index: u32) -> WalletResult<Address> {
+        let key = self.derive_key(chain, index)?;
+        Ok(address_from_public_key(
+            &key.public_key(),
+            self.network_config.address_prefix(),
+            self.scheme,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bip32::ExtendedPublicKey;
+    use crate::mnemonic::{parse_mnemonic, secret_key_from_mnemonic};
+    use crate::wallet::KaspaWallet;
+
+    const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon \
+                                 abandon abandon abandon abandon abandon about";
+
+    fn test_account() -> HdAccount {
+        let mnemonic = parse_mnemonic(ABANDON_ABOUT).unwrap();
+        HdAccount::from_mnemonic(&mnemonic, "", 0, NetworkConfig::mainnet()).unwrap()
+    }
+
+    #[test]
+    fn test_first_receive_matches_mnemonic_wallet() {
+        let mnemonic = parse_mnemonic(ABANDON_ABOUT).unwrap();
+        let secret_key = secret_key_from_mnemonic(&mnemonic, "").unwrap();
+        let wallet = KaspaWallet::new(secret_key, NetworkConfig::mainnet());
+
+        assert_eq!(
+            test_account()
+                .derive_address(AddressChain::Receive, 0)
+                .unwrap()
+                .to_string(),
+            wallet.get_address()
+        );
+    }
+
+    #[test]
+    fn test_chains_and_accounts_are_distinct() {
+        let account = test_account();
+        let receive = account.derive_address(AddressChain::Receive, 0).unwrap();
+        let change = account.derive_address(AddressChain::Change, 0).unwrap();
+        assert_ne!(receive, change);
+        assert_ne!(
+            receive,
+            account.derive_address(AddressChain::Receive, 1).unwrap()
+        );
+
+        let mnemonic = parse_mnemonic(ABANDON_ABOUT).unwrap();
+        let other = HdAccount::from_mnemonic(&mnemonic, "", 1, NetworkConfig::mainnet()).unwrap();
+        assert_ne!(
+            receive,
+            other.derive_address(AddressChain::Receive, 0).unwrap()
+        );
+        assert_eq!(
+            other.derivation_path(AddressChain::Change, 3),
+            "m/44'/111111'/1'/1/3"
+        );
+    }
+
+    #[test]
+    fn test_account_xpub_derives_addresses() {
+        let account = test_account();
+        let xpub = ExtendedPublicKey::parse(&account.account_xpub()).unwrap();
+        assert!(account.account_xpub().starts_with("kpub"));
+
+        let key = xpub
+            .derive_child(RECEIVE_CHAIN)
+            .unwrap()
+            .derive_child(2)
+            .unwrap();
+        assert_eq!(
+            address_from_public_key(&key.public_key(), Prefix::Mainnet, SignatureScheme::Schnorr),
+            account.derive_address(AddressChain::Receive, 2).unwrap()
+        );
+    }
+}
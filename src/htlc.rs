@@ -0,0 +1,359 @@
+use crate::address::{address_from_redeem_script, SignatureScheme};
+use crate::error::{WalletError, WalletResult};
+use crate::p2sh::{ScriptSpend, UnlockItem};
+use crate::script::{self, Instruction};
+use crate::transaction::LockTime;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_txscript::opcodes::codes::{
+    OpCheckLockTimeVerify, OpElse, OpEndIf, OpEqualVerify, OpFalse, OpIf, OpSHA256, OpSize, OpTrue,
+};
+use kaspa_txscript::script_builder::{ScriptBuilder, ScriptBuilderResult};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Length of an HTLC secret. The script checks it so a secret accepted
+/// here is also accepted by contracts on chains that fix the length.
+pub const SECRET_LEN: usize = 32;
+
+/// A hash time-locked contract: the recipient can spend it by revealing
+/// the preimage of `secret_hash`, and once `lock_time` has passed the
+/// refund address can take it back.
+///
+/// Both parties are P2PK addresses whose keys go into the script, each
+/// checked with the opcode for its signature scheme:
+///
+/// ```text
+/// OP_IF
+///     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <secret hash> OP_EQUALVERIFY
+///     <recipient key> OP_CHECKSIG
+/// OP_ELSE
+///     <lock time> OP_CHECKLOCKTIMEVERIFY <refund key> OP_CHECKSIG
+/// OP_ENDIF
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Htlc {
+    secret_hash: [u8; 32],
+    recipient: Address,
+    refund: Address,
+    lock_time: LockTime,
+}
+
+impl Htlc {
+    pub fn new(
+        secret_hash: [u8; 32],
+        recipient: Address,
+        refund: Address,
+        lock_time: LockTime,
+    ) -> WalletResult<Self> {
+        for (role, address) in [("recipient", &recipient), ("refund", &refund)] {
+            if SignatureScheme::from_address_version(address.version).is_none() {
+                return Err(WalletError::InvalidParameters(format!(
+                    "The {} address {} must pay to a public key",
+                    role, address
+                )));
+            }
+        }
+        if lock_time.value() == 0 {
+            return Err(WalletError::InvalidParameters(
+                "An HTLC needs a lock time".to_string(),
+            ));
+        }
+        Ok(Self {
+            secret_hash,
+            recipient,
+            refund,
+            lock_time,
+        })
+    }
+
+    /// Reads a contract built by [`Htlc::redeem_script`]; `None` for any
+    /// other script. `prefix` is the network of the parties' addresses.
+    pub fn parse(redeem_script: &[u8], prefix: Prefix) -> Option<Self> {
+        use Instruction::{Op, Push};
+
+        // Picks out the parameters, then checks that they rebuild exactly
+        // this script.
+        let instructions = script::instructions(redeem_script).ok()?;
+        if instructions.len() != 15 {
+            return None;
+        }
+        let (Push(hash), Push(recipient), Op(recipient_check), Push(refund), Op(refund_check)) = (
+            instructions[5],
+            instructions[7],
+            instructions[8],
+            instructions[12],
+            instructions[13],
+        ) else {
+            return None;
+        };
+        let lock_time = script::pushed_lock_time(instructions[10])?;
+
+        let htlc = Self::new(
+            hash.try_into().ok()?,
+            script::key_address(recipient, recipient_check, prefix)?,
+            script::key_address(refund, refund_check, prefix)?,
+            LockTime::from_value(lock_time),
+        )
+        .ok()?;
+        (htlc.redeem_script().ok()? == redeem_script).then_some(htlc)
+    }
+
+    pub fn secret_hash(&self) -> &[u8; 32] {
+        &self.secret_hash
+    }
+
+    pub fn recipient(&self) -> &Address {
+        &self.recipient
+    }
+
+    pub fn refund(&self) -> &Address {
+        &self.refund
+    }
+
+    pub fn lock_time(&self) -> LockTime {
+        self.lock_time
+    }
+
+    pub fn redeem_script(&self) -> WalletResult<Vec<u8>> {
+        self.build_script()
+            .map_err(|e| WalletError::Transaction(format!("HTLC script error: {}", e)))
+    }
+
+    fn build_script(&self) -> ScriptBuilderResult<Vec<u8>> {
+        Ok(ScriptBuilder::new()
+            .add_op(OpIf)?
+            .add_op(OpSize)?
+            .add_i64(SECRET_LEN as i64)?
+            .add_op(OpEqualVerify)?
+            .add_op(OpSHA256)?
+            .add_data(&self.secret_hash)?
+            .add_op(OpEqualVerify)?
+            .add_data(&self.recipient.payload)?
+            .add_op(script::check_sig_op(&self.recipient))?
+            .add_op(OpElse)?
+            .add_lock_time(self.lock_time.value())?
+            .add_op(OpCheckLockTimeVerify)?
+            .add_data(&self.refund.payload)?
+            .add_op(script::check_sig_op(&self.refund))?
+            .add_op(OpEndIf)?
+            .drain())
+    }
+
+    pub fn address(&self, prefix: Prefix) -> WalletResult<Address> {
+        Ok(address_from_redeem_script(&self.redeem_script()?, prefix))
+    }
+
+    /// The recipient's spend, which reveals `secret` on chain.
+    pub fn redeem(&self, secret: &[u8]) -> WalletResult<ScriptSpend> {
+        if secret.len() != SECRET_LEN || secret_hash(secret) != self.secret_hash {
+            return Err(WalletError::InvalidParameters(
+                "The secret does not match the contract's hash".to_string(),
+            ));
+        }
+        let push = ScriptBuilder::new()
+            .add_data(secret)
+            .map_err(|e| WalletError::Transaction(format!("HTLC script error: {}", e)))?
+            .drain();
+        Ok(ScriptSpend::new(
+            self.redeem_script()?,
+            vec![
+                UnlockItem::Signature,
+                UnlockItem::Script(push),
+                UnlockItem::Script(vec![OpTrue]),
+            ],
+        ))
+    }
+
+    /// The refund address's spend, valid once the lock time has passed.
+    pub fn refund_spend(&self) -> WalletResult<ScriptSpend> {
+        Ok(ScriptSpend::new(
+            self.redeem_script()?,
+            vec![UnlockItem::Signature, UnlockItem::Script(vec![OpFalse])],
+        )
+        .with_lock_time(self.lock_time))
+    }
+}
+
+/// A fresh random secret.
+pub fn generate_secret() -> Zeroizing<[u8; SECRET_LEN]> {
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    rand::rngs::OsRng.fill_bytes(&mut secret[..]);
+    secret
+}
+
+pub fn secret_hash(secret: &[u8]) -> [u8; 32] {
+    Sha256::digest(secret).into()
+}
+
+/// Finds the secret for `hash` among the pushes of a signature
+/// script, such as the one that redeemed the contract.
+pub fn extract_secret(
+    hash: &[u8; 32],
+    signature_script: &[u8],
+) -> Option<Zeroizing<[u8; SECRET_LEN]>> {
+    script::instructions(signature_script)
+        .ok()?
+        .into_iter()
+        .find_map(|instruction| match instruction {
+            Instruction::Push(data) if data.len() == SECRET_LEN && secret_hash(data) == *hash => {
+                Some(Zeroizing::new(data.try_into().expect("checked length")))
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::mock_node::{execute_scripts, MockNode};
+    use crate::node::NodeApi;
+    use crate::p2sh;
+    use crate::test_util::test_wallet;
+    use crate::wallet::KaspaWallet;
+    use kaspa_addresses::Version;
+
+    const LOCK_SCORE: u64 = 1_000_000;
+
+    fn contract(secret: &[u8], recipient: &KaspaWallet, refund: &KaspaWallet) -> Htlc {
+        Htlc::new(
+            secret_hash(secret),
+            recipient.address(),
+            refund.address(),
+            LockTime::DaaScore(LOCK_SCORE),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_contract() {
+        let secret = generate_secret();
+        for scheme in SignatureScheme::ALL {
+            let htlc = contract(
+                &secret[..],
+                &test_wallet(0x61).with_signature_scheme(scheme),
+                &test_wallet(0x62),
+            );
+            let script = htlc.redeem_script().unwrap();
+            assert_eq!(Htlc::parse(&script, Prefix::Testnet), Some(htlc.clone()));
+            assert_eq!(script::sig_op_count(&script).unwrap(), 2);
+            assert_eq!(
+                htlc.address(Prefix::Testnet).unwrap().version,
+                Version::ScriptHash
+            );
+            assert!(Htlc::parse(&script[1..], Prefix::Testnet).is_none());
+        }
+
+        let p2sh = Address::new(Prefix::Testnet, Version::ScriptHash, &[0; 32]);
+        let refund = test_wallet(0x62).address();
+        let lock_time = LockTime::DaaScore(LOCK_SCORE);
+        assert!(Htlc::new([0; 32], p2sh, refund.clone(), lock_time).is_err());
+        assert!(Htlc::new([0; 32], refund.clone(), refund, LockTime::DaaScore(0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redeem_reveals_secret() {
+        let secret = generate_secret();
+        for scheme in SignatureScheme::ALL {
+            let recipient = test_wallet(0x61).with_signature_scheme(scheme);
+            let refund = test_wallet(0x62);
+            let htlc = contract(&secret[..], &recipient, &refund);
+            let node = MockNode::new()
+                .with_utxo(&htlc.address(Prefix::Testnet).unwrap(), 5 * SOMPI_PER_KAS);
+
+            assert!(htlc.redeem(&[0; SECRET_LEN]).is_err());
+            let spend = htlc.redeem(&secret[..]).unwrap();
+            let tx = p2sh::build_script_sweep(
+                &node,
+                &spend,
+                Prefix::Testnet,
+                &recipient.address(),
+                Some(&recipient),
+                1,
+            )
+            .await
+            .unwrap();
+            assert_eq!(tx.tx.inputs[0].sig_op_count, 2);
+            assert_eq!(tx.tx.outputs.len(), 1);
+            execute_scripts(&tx).unwrap();
+
+            // The refund key cannot take the redeem branch.
+            let stolen = p2sh::build_script_sweep(
+                &node,
+                &spend,
+                Prefix::Testnet,
+                &refund.address(),
+                Some(&refund),
+                1,
+            )
+            .await
+            .unwrap();
+            assert!(execute_scripts(&stolen).is_err());
+
+            let revealed =
+                extract_secret(htlc.secret_hash(), &tx.tx.inputs[0].signature_script).unwrap();
+            assert_eq!(*revealed, *secret);
+            assert!(extract_secret(&[0; 32], &tx.tx.inputs[0].signature_script).is_none());
+
+            node.submit(&tx.tx).await.unwrap();
+            assert_eq!(
+                node.balance(&recipient.address()).await.unwrap(),
+                tx.tx.outputs[0].value
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refund_after_lock_time() {
+        let secret = generate_secret();
+        let recipient = test_wallet(0x61);
+        let refund = test_wallet(0x62).with_signature_scheme(SignatureScheme::Ecdsa);
+        let htlc = contract(&secret[..], &recipient, &refund);
+        let node =
+            MockNode::new().with_utxo(&htlc.address(Prefix::Testnet).unwrap(), 5 * SOMPI_PER_KAS);
+
+        let spend = htlc.refund_spend().unwrap();
+        let tx = p2sh::build_script_sweep(
+            &node,
+            &spend,
+            Prefix::Testnet,
+            &refund.address(),
+            Some(&refund),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.tx.lock_time, LOCK_SCORE);
+        execute_scripts(&tx).unwrap();
+
+        // A transaction that could be mined before the lock time fails
+        // OP_CHECKLOCKTIMEVERIFY.
+        let early = p2sh::build_script_sweep(
+            &node,
+            &spend
+                .clone()
+                .with_lock_time(LockTime::DaaScore(LOCK_SCORE - 1)),
+            Prefix::Testnet,
+            &refund.address(),
+            Some(&refund),
+            1,
+        )
+        .await
+        .unwrap();
+        assert!(execute_scripts(&early).is_err());
+
+        // Neither can the recipient use the refund branch.
+        let stolen = p2sh::build_script_sweep(
+            &node,
+            &spend,
+            Prefix::Testnet,
+            &recipient.address(),
+            Some(&recipient),
+            1,
+        )
+        .await
+        .unwrap();
+        assert!(execute_scripts(&stolen).is_err());
+    }
+}
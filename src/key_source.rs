@@ -0,0 +1,218 @@
+use crate::address::SignatureScheme;
+use crate::error::{WalletError, WalletResult};
+use crate::network::NetworkConfig;
+use crate::secret::SecretString;
+use crate::wallet::KaspaWallet;
+use secp256k1::SecretKey;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+pub const DEFAULT_NETWORK: &str = "testnet-10";
+
+/// Where a command reads its private key from. Everything except
+/// `Argument` keeps the key out of shell history and process listings.
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    Keystore(PathBuf),
+    Prompt,
+    Stdin,
+    Env(String),
+    Argument(SecretString),
+}
+
+impl KeySource {
+    /// Parses a key source given as a single argument: `prompt`, `stdin`,
+    /// `env:VAR` or `keystore:PATH`. A bare path to an existing file is
+    /// taken as a keystore.
+    pub fn from_spec(spec: &str) -> WalletResult<Self> {
+        let spec = spec.trim();
+        match spec {
+            "prompt" => return Ok(KeySource::Prompt),
+            "stdin" => return Ok(KeySource::Stdin),
+            _ => {}
+        }
+        if let Some(name) = spec.strip_prefix("env:") {
+            return Ok(KeySource::Env(name.to_string()));
+        }
+        if let Some(path) = spec.strip_prefix("keystore:") {
+            return Ok(KeySource::Keystore(path.into()));
+        }
+        if std::path::Path::new(spec).is_file() {
+            return Ok(KeySource::Keystore(spec.into()));
+        }
+        Err(WalletError::InvalidParameters(format!(
+            "Unknown key source: {} (expected prompt, stdin, env:VAR or keystore:PATH)",
+            spec
+        )))
+    }
+
+    /// Loads the wallet for this source. Keystores carry their own network
+    /// and scheme; explicit `network`/`scheme` values must agree with them.
+    pub fn load_wallet(
+        &self,
+        network: Option<&str>,
+        scheme: Option<SignatureScheme>,
+    ) -> WalletResult<KaspaWallet> {
+        if let KeySource::Keystore(path) = self {
+            let password =
+                SecretString::new(rpassword::prompt_password("Enter keystore password: ")?);
+            let wallet = KaspaWallet::open(path, password.expose_secret())?;
+            check_matches_keystore(&wallet, network, scheme)?;
+            return Ok(wallet);
+        }
+
+        let secret_key = self.read_secret_key()?;
+        Ok(
+            KaspaWallet::with_network(secret_key, network.unwrap_or(DEFAULT_NETWORK))?
+                .with_signature_scheme(scheme.unwrap_or_default()),
+        )
+    }
+
+    /// Reads a raw hex key. Not available for keystores, which are opened
+    /// through `load_wallet`.
+    pub fn read_secret_key(&self) -> WalletResult<SecretKey> {
+        match self {
+            KeySource::Keystore(_) => Err(WalletError::InvalidParameters(
+                "Keystores must be opened with a password".to_string(),
+            )),
+            KeySource::Prompt => {
+                let hex =
+                    SecretString::new(rpassword::prompt_password("Enter private key (hex): ")?);
+                parse_private_key(hex.expose_secret())
+            }
+            KeySource::Stdin => read_private_key(io::stdin().lock()),
+            KeySource::Env(name) => {
+                let value = Zeroizing::new(std::env::var(name).map_err(|_| {
+                    WalletError::InvalidParameters(format!(
+                        "Environment variable {} is not set",
+                        name
+                    ))
+                })?);
+                parse_private_key(&value)
+            }
+            KeySource::Argument(hex) => parse_private_key(hex.expose_secret()),
+        }
+    }
+}
+
+pub fn parse_private_key(hex: &str) -> WalletResult<SecretKey> {
+    let bytes = Zeroizing::new(hex::decode(hex.trim())?);
+    Ok(SecretKey::from_slice(&bytes)?)
+}
+
+/// Reads the first line of `reader` as a hex private key.
+pub fn read_private_key(mut reader: impl BufRead) -> WalletResult<SecretKey> {
+    let mut line = Zeroizing::new(String::new());
+    reader.read_line(&mut line)?;
+    if line.trim().is_empty() {
+        return Err(WalletError::InvalidParameters(
+            "No private key provided on stdin".to_string(),
+        ));
+    }
+    parse_private_key(&line)
+}
+
+fn check_matches_keystore(
+    wallet: &KaspaWallet,
+    network: Option<&str>,
+    scheme: Option<SignatureScheme>,
+) -> WalletResult<()> {
+    if let Some(network) = network {
+        if NetworkConfig::from_name(network)?.name != wallet.get_network_name() {
+            return Err(WalletError::InvalidParameters(format!(
+                "Keystore is for {}, not {}",
+                wallet.get_network_name(),
+                network
+            )));
+        }
+    }
+    if let Some(scheme) = scheme {
+        if scheme != wallet.signature_scheme() {
+            return Err(WalletError::InvalidParameters(format!(
+                "Keystore uses {} addresses, not {}",
+                wallet.signature_scheme(),
+                scheme
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+    #[test]
+    fn test_parse_private_key_trims_whitespace() {
+        let key = parse_private_key(&format!("  {}\n", KEY_HEX)).unwrap();
+        assert_eq!(hex::encode(key.secret_bytes()), KEY_HEX);
+        assert!(parse_private_key("zz").is_err());
+        assert!(parse_private_key("00").is_err());
+    }
+
+    #[test]
+    fn test_read_private_key_from_reader() {
+        let input = format!("{}\nignored\n", KEY_HEX);
+        let key = read_private_key(input.as_bytes()).unwrap();
+        assert_eq!(hex::encode(key.secret_bytes()), KEY_HEX);
+        assert!(read_private_key(&b"\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_env_source() {
+        let name = format!("KASPARUSTWALLET_TEST_KEY_{}", std::process::id());
+        std::env::set_var(&name, KEY_HEX);
+        let wallet = KeySource::Env(name.clone())
+            .load_wallet(Some("mainnet"), Some(SignatureScheme::Ecdsa))
+            .unwrap();
+        std::env::remove_var(&name);
+
+        assert_eq!(wallet.get_network_name(), "mainnet");
+        assert_eq!(wallet.signature_scheme(), SignatureScheme::Ecdsa);
+        assert!(KeySource::Env(name).read_secret_key().is_err());
+    }
+
+    #[test]
+    fn test_from_spec() {
+        assert!(matches!(
+            KeySource::from_spec("prompt"),
+            Ok(KeySource::Prompt)
+        ));
+        assert!(matches!(
+            KeySource::from_spec("stdin"),
+            Ok(KeySource::Stdin)
+        ));
+        assert!(matches!(
+            KeySource::from_spec("env:PAPER_KEY"),
+            Ok(KeySource::Env(name)) if name == "PAPER_KEY"
+        ));
+        assert!(matches!(
+            KeySource::from_spec("keystore:old.json"),
+            Ok(KeySource::Keystore(path)) if path == PathBuf::from("old.json")
+        ));
+        // A raw key must not be accepted where it would end up in history.
+        assert!(KeySource::from_spec(KEY_HEX).is_err());
+    }
+
+    #[test]
+    fn test_debug_redacts_argument() {
+        let debug = format!("{:?}", KeySource::Argument(KEY_HEX.to_string().into()));
+        assert!(!debug.contains(KEY_HEX));
+    }
+
+    #[test]
+    fn test_defaults_and_keystore_mismatch() {
+        let wallet = KeySource::Argument(KEY_HEX.to_string().into())
+            .load_wallet(None, None)
+            .unwrap();
+        assert_eq!(wallet.get_network_name(), DEFAULT_NETWORK);
+        assert_eq!(wallet.signature_scheme(), SignatureScheme::Schnorr);
+
+        assert!(check_matches_keystore(&wallet, Some("testnet-10"), None).is_ok());
+        assert!(check_matches_keystore(&wallet, Some("mainnet"), None).is_err());
+        assert!(check_matches_keystore(&wallet, None, Some(SignatureScheme::Ecdsa)).is_err());
+    }
+}
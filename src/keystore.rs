@@ -0,0 +1,440 @@
+use crate::address::SignatureScheme;
+use crate::error::{WalletError, WalletResult};
+use crate::secret::SecretBytes;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+
+const KDF_ALGORITHM: &str = "argon2id";
+const CIPHER_ALGORITHM: &str = "xchacha20poly1305";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Upper bounds on what a keystore may ask Argon2 for. They leave room
+    /// well above the defaults while keeping a tampered file from stalling
+    /// or exhausting the machine before any check can fail.
+    pub const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+    pub const MAX_ITERATIONS: u32 = 16;
+    pub const MAX_PARALLELISM: u32 = 16;
+
+    /// Whether these are parameters `encrypt` would write.
+    pub fn is_supported(&self) -> bool {
+        (1..=Self::MAX_PARALLELISM).contains(&self.parallelism)
+            && (1..=Self::MAX_ITERATIONS).contains(&self.iterations)
+            && (8 * self.parallelism..=Self::MAX_MEMORY_KIB).contains(&self.memory_kib)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfSection {
+    pub algorithm: String,
+    pub salt: String,
+    #[serde(flatten)]
+    pub params: KdfParams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CipherSection {
+    pub algorithm: String,
+    pub nonce: String,
+}
+
+/// Versioned JSON envelope holding an encrypted wallet secret.
+///
+/// The header (everything except the ciphertext and check values) is bound
+/// to the ciphertext as AEAD associated data. `password_check` lets a wrong
+/// password be told apart from a modified file, and `checksum` covers the
+/// whole envelope to catch corruption before any key derivation runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub network: String,
+    pub scheme: String,
+    pub kdf: KdfSection,
+    pub cipher: CipherSection,
+    pub password_check: String,
+    pub ciphertext: String,
+    pub checksum: String,
+}
+
+#[derive(Serialize)]
+struct KeystoreHeader<'a> {
+    version: u32,
+    network: &'a str,
+    scheme: &'a str,
+    kdf: &'a KdfSection,
+    cipher: &'a CipherSection,
+}
+
+impl Keystore {
+    pub fn encrypt(
+        secret: &[u8],
+        password: &str,
+        network: &str,
+        scheme: SignatureScheme,
+    ) -> WalletResult<Self> {
+        Self::encrypt_with_params(secret, password, network, scheme, KdfParams::default())
+    }
+
+    pub fn encrypt_with_params(
+        secret: &[u8],
+        password: &str,
+        network: &str,
+        scheme: SignatureScheme,
+        params: KdfParams,
+    ) -> WalletResult<Self> {
+        if !params.is_supported() {
+            return Err(WalletError::InvalidParameters(format!(
+                "Unsupported KDF parameters: {} KiB, {} iterations, {} lanes",
+                params.memory_kib, params.iterations, params.parallelism
+            )));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let mut keystore = Self {
+            version: KEYSTORE_VERSION,
+            network: network.to_string(),
+            scheme: scheme.to_string(),
+            kdf: KdfSection {
+                algorithm: KDF_ALGORITHM.to_string(),
+                salt: hex::encode(salt),
+                params,
+            },
+            cipher: CipherSection {
+                algorithm: CIPHER_ALGORITHM.to_string(),
+                nonce: hex::encode(nonce),
+            },
+            password_check: String::new(),
+            ciphertext: String::new(),
+            checksum: String::new(),
+        };
+
+        let (key, check) = derive_keys(password, &salt, &params)?;
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key[..]))
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: secret,
+                    aad: &keystore.header_bytes()?,
+                },
+            )
+            .map_err(|_| WalletError::KeyGeneration("Keystore encryption failed".to_string()))?;
+
+        keystore.password_check = hex::encode(check);
+        keystore.ciphertext = hex::encode(ciphertext);
+        keystore.checksum = keystore.compute_checksum()?;
+        Ok(keystore)
+    }
+
+    pub fn decrypt(&self, password: &str) -> WalletResult<SecretBytes> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(WalletError::KeystoreTampered(format!(
+                "unsupported keystore version {}",
+                self.version
+            )));
+        }
+        if self.kdf.algorithm != KDF_ALGORITHM || self.cipher.algorithm != CIPHER_ALGORITHM {
+            return Err(WalletError::KeystoreTampered(format!(
+                "unsupported algorithms {}/{}",
+                self.kdf.algorithm, self.cipher.algorithm
+            )));
+        }
+        if self.compute_checksum()? != self.checksum {
+            return Err(WalletError::KeystoreTampered(
+                "checksum mismatch".to_string(),
+            ));
+        }
+
+        // The checksum is unkeyed, so these fields may still have been
+        // edited; bound them before handing them to Argon2.
+        if !self.kdf.params.is_supported() {
+            return Err(WalletError::KeystoreTampered(
+                "KDF parameters out of range".to_string(),
+            ));
+        }
+        let salt = decode_field("salt", &self.kdf.salt)?;
+        if salt.len() != SALT_LEN {
+            return Err(WalletError::KeystoreTampered(
+                "invalid salt length".to_string(),
+            ));
+        }
+        let nonce = decode_field("nonce", &self.cipher.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(WalletError::KeystoreTampered(
+                "invalid nonce length".to_string(),
+            ));
+        }
+        let password_check = decode_field("password check", &self.password_check)?;
+
+        let (key, check) = derive_keys(password, &salt, &self.kdf.params)?;
+        if !bool::from(check[..].ct_eq(&password_check)) {
+            return Err(WalletError::WrongPassword);
+        }
+
+        let ciphertext = decode_field("ciphertext", &self.ciphertext)?;
+        XChaCha20Poly1305::new(Key::from_slice(&key[..]))
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &self.header_bytes()?,
+                },
+            )
+            .map(SecretBytes::new)
+            .map_err(|_| WalletError::KeystoreTampered("authentication failed".to_string()))
+    }
+
+    pub fn signature_scheme(&self) -> WalletResult<SignatureScheme> {
+        SignatureScheme::from_name(&self.scheme)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> WalletResult<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| WalletError::KeystoreTampered(format!("invalid keystore JSON: {}", e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> WalletResult<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::Io(format!("Failed to serialize keystore: {}", e)))?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        file.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    fn header_bytes(&self) -> WalletResult<Vec<u8>> {
+        serde_json::to_vec(&KeystoreHeader {
+            version: self.version,
+            network: &self.network,
+            scheme: &self.scheme,
+            kdf: &self.kdf,
+            cipher: &self.cipher,
+        })
+        .map_err(|e| WalletError::Io(format!("Failed to serialize keystore header: {}", e)))
+    }
+
+    fn compute_checksum(&self) -> WalletResult<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.header_bytes()?);
+        hasher.update(self.password_check.as_bytes());
+        hasher.update(self.ciphertext.as_bytes());
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// Runs Argon2id once and splits the output into the AEAD key and the
+/// password check value.
+fn derive_keys(
+    password: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> WalletResult<(Zeroizing<[u8; 32]>, [u8; 32])> {
+    let argon_params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(64),
+    )
+    .map_err(|e| WalletError::KeystoreTampered(format!("invalid KDF parameters: {}", e)))?;
+
+    let mut output = Zeroizing::new([0u8; 64]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(password.as_bytes(), salt, &mut output[..])
+        .map_err(|e| WalletError::KeyGeneration(format!("Key derivation failed: {}", e)))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&output[..32]);
+    let check: [u8; 32] = Sha256::digest(&output[32..]).into();
+    Ok((key, check))
+}
+
+fn decode_field(name: &str, value: &str) -> WalletResult<Vec<u8>> {
+    hex::decode(value)
+        .map_err(|_| WalletError::KeystoreTampered(format!("invalid {} encoding", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; 32] = [0x5a; 32];
+
+    fn fast_params() -> KdfParams {
+        KdfParams {
+            memory_kib: 256,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn test_keystore() -> Keystore {
+        Keystore::encrypt_with_params(
+            &SECRET,
+            "correct horse",
+            "testnet-10",
+            SignatureScheme::Schnorr,
+            fast_params(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let keystore = test_keystore();
+        assert_eq!(
+            keystore.decrypt("correct horse").unwrap().expose_secret(),
+            SECRET
+        );
+        assert!(!keystore.ciphertext.contains(&hex::encode(SECRET)));
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let keystore = test_keystore();
+        assert!(matches!(
+            keystore.decrypt("battery staple"),
+            Err(WalletError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext() {
+        let mut keystore = test_keystore();
+        let mut ciphertext = hex::decode(&keystore.ciphertext).unwrap();
+        ciphertext[0] ^= 0x01;
+        keystore.ciphertext = hex::encode(ciphertext);
+
+        assert!(matches!(
+            keystore.decrypt("correct horse"),
+            Err(WalletError::KeystoreTampered(_))
+        ));
+
+        // Even with a recomputed checksum the AEAD tag still catches it.
+        keystore.checksum = keystore.compute_checksum().unwrap();
+        assert!(matches!(
+            keystore.decrypt("correct horse"),
+            Err(WalletError::KeystoreTampered(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_header() {
+        let mut keystore = test_keystore();
+        keystore.network = "mainnet".to_string();
+        assert!(matches!(
+            keystore.decrypt("correct horse"),
+            Err(WalletError::KeystoreTampered(_))
+        ));
+
+        keystore.checksum = keystore.compute_checksum().unwrap();
+        assert!(matches!(
+            keystore.decrypt("correct horse"),
+            Err(WalletError::KeystoreTampered(_))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_kdf_params() {
+        for params in [
+            KdfParams {
+                memory_kib: 64 * 1024 * 1024,
+                ..fast_params()
+            },
+            KdfParams {
+                iterations: u32::MAX,
+                ..fast_params()
+            },
+            KdfParams {
+                parallelism: 0,
+                ..fast_params()
+            },
+        ] {
+            // Rejected with a recomputed checksum too, before Argon2 runs.
+            let mut keystore = test_keystore();
+            keystore.kdf.params = params;
+            keystore.checksum = keystore.compute_checksum().unwrap();
+            assert!(matches!(
+                keystore.decrypt("correct horse"),
+                Err(WalletError::KeystoreTampered(_))
+            ));
+
+            assert!(Keystore::encrypt_with_params(
+                &SECRET,
+                "correct horse",
+                "testnet-10",
+                SignatureScheme::Schnorr,
+                params,
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_tampered_salt_length() {
+        let mut keystore = test_keystore();
+        keystore.kdf.salt = hex::encode([0u8; SALT_LEN + 1]);
+        keystore.checksum = keystore.compute_checksum().unwrap();
+        assert!(matches!(
+            keystore.decrypt("correct horse"),
+            Err(WalletError::KeystoreTampered(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "kasparustwallet-keystore-{}.json",
+            std::process::id()
+        ));
+        let keystore = test_keystore();
+        keystore.save(&path).unwrap();
+
+        let loaded = Keystore::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.network, "testnet-10");
+        assert_eq!(
+            loaded.decrypt("correct horse").unwrap().expose_secret(),
+            SECRET
+        );
+    }
+}
@@ -1,15 +1,59 @@
 mod address;
+mod amount;
+mod bip32;
+mod bundle;
+mod coin_selection;
+mod compound;
 mod error;
+mod grpc;
+mod hd;
+mod htlc;
+mod key_source;
+mod keystore;
+mod mass;
+mod mnemonic;
+#[cfg(test)]
+mod mock_node;
+mod multisig;
+mod network;
+mod node;
+mod p2sh;
+mod pskt;
 mod rpc;
+mod script;
+mod secret;
+mod send;
+mod signer;
+mod sweep;
+#[cfg(test)]
+mod test_util;
+mod transaction;
+mod vault;
+mod wallet;
+mod wrpc;
 
+use crate::address::SignatureScheme;
+use crate::amount::Amount;
+use crate::bundle::SigningBundle;
+use crate::coin_selection::{self, Strategy};
 use crate::error::WalletError;
-use crate::rpc::RpcClient;
-use clap::{Parser, Subcommand};
-use secp256k1::{PublicKey, Secp256k1, SecretKey};
-use std::fs;
+use crate::hd::{AddressChain, HdAccount};
+use crate::htlc::Htlc;
+use crate::key_source::KeySource;
+use crate::multisig::MultisigAccount;
+use crate::network::{NetworkConfig, Transport};
+use crate::node::NodeApi;
+use crate::p2sh::ScriptSpend;
+use crate::pskt::{Pskt, PsktEncoding};
+use crate::secret::SecretString;
+use crate::transaction::LockTime;
+use crate::vault::{Vault, VaultBook};
+use crate::wallet::KaspaWallet;
+use clap::{Args, Parser, Subcommand};
+use kaspa_consensus_core::tx::TransactionId;
 use std::io::{self, Write};
-
-const DEFAULT_RPC_URL: &str = "https://api-tn10.kaspa.org";
+use std::str::FromStr;
+use zeroize::Zeroizing;
 
 #[derive(Parser)]
 #[command(name = "kasparustwallet")]
@@ -27,24 +71,244 @@ enum Commands {
         network: String,
         #[arg(short, long)]
         output: Option<String>,
+        #[arg(long, default_value = "schnorr")]
+        scheme: String,
+        #[arg(long, default_value_t = 12)]
+        words: usize,
+        #[arg(long)]
+        with_passphrase: bool,
     },
-    Info {
+    Restore {
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
         #[arg(short, long)]
-        private_key: String,
+        output: Option<String>,
+        #[arg(long, default_value = "schnorr")]
+        scheme: String,
+        #[arg(long)]
+        with_passphrase: bool,
+    },
+    Derive {
         #[arg(short, long, default_value = "testnet-10")]
         network: String,
+        #[arg(long, default_value = "schnorr")]
+        scheme: String,
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+        #[arg(long)]
+        change: bool,
+        #[arg(long)]
+        with_passphrase: bool,
+    },
+    Info {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
     },
     Address {
+        #[command(flatten)]
+        key: KeySourceArgs,
         #[arg(short, long)]
-        private_key: String,
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+    },
+    Balance {
+        #[arg(short, long)]
+        address: String,
+        #[command(flatten)]
+        node: NodeArgs,
         #[arg(short, long, default_value = "testnet-10")]
         network: String,
     },
-    Balance {
+    /// Transactions of an address, newest first, from the REST API
+    History {
         #[arg(short, long)]
         address: String,
+        /// REST API endpoint; history comes from its indexer, so other
+        /// transports are not supported
         #[arg(long)]
         rpc: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+    },
+    Send {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Recipient address; repeat for several outputs
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Amount for each --to, in the same order: KAS such as `1.5`, or
+        /// with a unit such as `1.5 KAS` or `150000000 sompi`
+        #[arg(long)]
+        amount: Vec<String>,
+        /// Spend the whole balance; the last --to takes what is left and
+        /// needs no --amount
+        #[arg(long)]
+        all: bool,
+        /// Deduct the fee from the outputs in proportion to their amounts
+        #[arg(long)]
+        fee_from_recipients: bool,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// largest-first, smallest-first, branch-and-bound or random
+        #[arg(long, default_value = "largest-first")]
+        coin_selection: String,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    Compound {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Number of UTXOs to leave
+        #[arg(long, default_value_t = 1)]
+        target_utxos: usize,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transactions but do not submit them
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    Sweep {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Key to empty: prompt, stdin, env:VAR or keystore:PATH
+        #[arg(long, value_name = "SOURCE")]
+        from_key: String,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transactions but do not submit them
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Watch-only: build an unsigned payment bundle for an offline signer
+    Prepare {
+        /// Address whose UTXOs are spent; change returns to it
+        #[arg(long)]
+        from: String,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+        /// Recipient address; repeat for several outputs
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Amount for each --to, in the same order
+        #[arg(long)]
+        amount: Vec<String>,
+        /// Spend the whole balance; the last --to takes what is left
+        #[arg(long)]
+        all: bool,
+        /// Deduct the fee from the outputs in proportion to their amounts
+        #[arg(long)]
+        fee_from_recipients: bool,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// largest-first, smallest-first, branch-and-bound or random
+        #[arg(long, default_value = "largest-first")]
+        coin_selection: String,
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Offline: sign a prepared bundle; needs no network access
+    Sign {
+        bundle: String,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Where to write the signed bundle; defaults to overwriting BUNDLE
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Sign without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Submit a signed bundle
+    Broadcast {
+        bundle: String,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Partially signed transactions for cosigners and offline signing
+    Pskt {
+        #[command(subcommand)]
+        command: PsktCommand,
+    },
+    /// M-of-N accounts built from the cosigners' account xpubs
+    Multisig {
+        #[command(subcommand)]
+        command: MultisigCommand,
+    },
+    /// Custom P2SH scripts: addresses, disassembly and spending
+    Script {
+        #[command(subcommand)]
+        command: ScriptCommand,
+    },
+    /// Hash time-locked contracts for atomic swaps
+    Htlc {
+        #[command(subcommand)]
+        command: HtlcCommand,
+    },
+    /// Savings locked until a DAA score or time
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommand,
+    },
+    /// Fee for a P2PK transaction of the given shape, from its compute mass
+    EstimateFee {
+        #[arg(long)]
+        inputs: usize,
+        #[arg(long)]
+        outputs: usize,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+        #[command(flatten)]
+        node: NodeArgs,
     },
     ValidateAddress {
         #[arg(short, long)]
@@ -52,6 +316,522 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum PsktCommand {
+    /// Build an unsigned payment from the wallet's UTXOs
+    Create {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Recipient address; repeat for several outputs
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Amount for each --to, in the same order
+        #[arg(long)]
+        amount: Vec<String>,
+        /// Spend the whole balance; the last --to takes what is left
+        #[arg(long)]
+        all: bool,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// largest-first, smallest-first, branch-and-bound or random
+        #[arg(long, default_value = "largest-first")]
+        coin_selection: String,
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Add signatures for the inputs this key owns
+    Sign {
+        file: String,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Where to write the result; defaults to overwriting FILE
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+    },
+    /// Merge the signatures of several copies of the same PSKT
+    Combine {
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<String>,
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+    },
+    /// Build the signature scripts and extract the signed transaction
+    Finalize {
+        file: String,
+        /// Where to write the finalized PSKT; defaults to overwriting FILE
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+        /// Submit the extracted transaction
+        #[arg(long)]
+        submit: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Show the transaction and its signing progress
+    Inspect { file: String },
+}
+
+#[derive(Subcommand)]
+enum MultisigCommand {
+    /// Show the account's P2SH addresses
+    Address {
+        #[command(flatten)]
+        account: MultisigArgs,
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+    },
+    /// Build an unsigned PSKT spending from one of the account's addresses
+    Create {
+        #[command(flatten)]
+        account: MultisigArgs,
+        /// Address index to spend from; change returns to it
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// Recipient address; repeat for several outputs
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Amount for each --to, in the same order
+        #[arg(long, required = true)]
+        amount: Vec<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Add this cosigner's signatures, deriving the key from the mnemonic
+    Sign {
+        file: String,
+        /// BIP-44 account whose xpub was shared for the multisig
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        /// Address index the inputs were sent to
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        #[arg(long)]
+        with_passphrase: bool,
+        /// Where to write the result; defaults to overwriting FILE
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        encoding: PsktEncodingArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScriptCommand {
+    /// Show the P2SH address of a redeem script
+    Address {
+        #[command(flatten)]
+        script: RedeemScriptArgs,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+    },
+    /// Disassemble a hex script
+    Decode { script: String },
+    /// Spend the UTXOs of a redeem script's address
+    Spend {
+        #[command(flatten)]
+        script: RedeemScriptArgs,
+        /// Unlocking data as assembly; `<sig>` is replaced by a signature
+        /// from the key, which is only loaded when it is used
+        #[arg(long, default_value = "")]
+        unlock: String,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Recipient address; repeat for several outputs. Change returns to
+        /// the script's address
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Amount for each --to, in the same order
+        #[arg(long, required = true)]
+        amount: Vec<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum HtlcCommand {
+    /// Lock funds that the recipient can claim with the secret, or that
+    /// come back to this key after the lock time
+    Initiate {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// The counterparty's P2PK address
+        #[arg(long)]
+        recipient: String,
+        #[arg(long)]
+        amount: String,
+        /// daa:<score>, time:<unix seconds>, or +<n> for n DAA scores from
+        /// the current one
+        #[arg(long)]
+        lock_time: String,
+        /// SHA256 of the counterparty's secret; a new secret is generated
+        /// when omitted
+        #[arg(long)]
+        secret_hash: Option<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Show the terms and address of a contract
+    Inspect {
+        /// Contract redeem script as hex
+        #[arg(long)]
+        contract: String,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+    },
+    /// Claim a contract as its recipient by revealing the secret
+    Redeem {
+        /// Contract redeem script as hex
+        #[arg(long)]
+        contract: String,
+        /// The secret as hex
+        #[arg(long)]
+        secret: String,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Where to send the funds; defaults to the key's address
+        #[arg(long)]
+        to: Option<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Take back an unclaimed contract after its lock time
+    Refund {
+        /// Contract redeem script as hex
+        #[arg(long)]
+        contract: String,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Where to send the funds; defaults to the key's address
+        #[arg(long)]
+        to: Option<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Recover the secret from the transaction that redeemed a contract
+    ExtractSecret {
+        /// Contract redeem script as hex
+        #[arg(long)]
+        contract: String,
+        /// Id of the redeem transaction. kaspad only finds it while it is
+        /// in the mempool; the REST API also finds accepted transactions
+        #[arg(long, required_unless_present = "signature_script")]
+        tx: Option<String>,
+        /// The redeeming input's signature script as hex, instead of --tx
+        #[arg(long, conflicts_with = "tx")]
+        signature_script: Option<String>,
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultCommand {
+    /// Lock funds until a DAA score or time
+    Create {
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        #[arg(long)]
+        amount: String,
+        /// daa:<score>, time:<unix seconds>, or +<n> for n DAA scores from
+        /// the current one
+        #[arg(long)]
+        lock_time: String,
+        /// P2PK address that can withdraw once unlocked; defaults to the
+        /// key's address
+        #[arg(long)]
+        owner: Option<String>,
+        #[command(flatten)]
+        book: VaultBookArgs,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Show what each recorded vault holds and when it unlocks
+    List {
+        #[arg(short, long, default_value = "testnet-10")]
+        network: String,
+        #[command(flatten)]
+        book: VaultBookArgs,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Move everything out of an unlocked vault
+    Withdraw {
+        /// The vault's address, as shown by `vault list`
+        vault: String,
+        #[command(flatten)]
+        book: VaultBookArgs,
+        #[command(flatten)]
+        key: KeySourceArgs,
+        #[arg(short, long)]
+        network: Option<String>,
+        #[arg(long)]
+        scheme: Option<String>,
+        /// Where to send the funds; defaults to the key's address
+        #[arg(long)]
+        to: Option<String>,
+        /// Fee rate in sompi per gram of mass; defaults to the node's estimate
+        #[arg(long)]
+        fee_rate: Option<u64>,
+        /// Build and sign the transaction but do not submit it
+        #[arg(long)]
+        dry_run: bool,
+        /// Submit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+}
+
+/// Where the scripts of created vaults are recorded.
+#[derive(Args)]
+struct VaultBookArgs {
+    /// Vault file; without it a vault's funds cannot be found or spent
+    #[arg(long = "vaults", default_value = "vaults.json")]
+    path: String,
+}
+
+/// A redeem script given as assembly or as hex.
+#[derive(Args)]
+struct RedeemScriptArgs {
+    /// Redeem script, e.g. "OP_SHA256 0x<hash> OP_EQUAL"
+    #[arg(long = "script")]
+    script: String,
+    /// Read --script as hex instead of assembly
+    #[arg(long)]
+    hex: bool,
+}
+
+impl RedeemScriptArgs {
+    fn redeem_script(&self) -> Result<Vec<u8>, WalletError> {
+        if self.hex {
+            Ok(hex::decode(self.script.trim())?)
+        } else {
+            script::assemble(&self.script)
+        }
+    }
+}
+
+/// The cosigners and threshold of a multisig account.
+#[derive(Args)]
+struct MultisigArgs {
+    /// Cosigner account xpub (kpub, ktub, xpub or tpub); repeat for each
+    #[arg(long = "xpub", required = true)]
+    xpubs: Vec<String>,
+    /// Signatures required to spend
+    #[arg(long)]
+    threshold: usize,
+    #[arg(short, long, default_value = "testnet-10")]
+    network: String,
+    #[arg(long, default_value = "schnorr")]
+    scheme: String,
+}
+
+impl MultisigArgs {
+    fn account(&self) -> Result<MultisigAccount, WalletError> {
+        let xpubs = self
+            .xpubs
+            .iter()
+            .map(|xpub| bip32::ExtendedPublicKey::parse(xpub))
+            .collect::<Result<_, _>>()?;
+        Ok(MultisigAccount::new(
+            xpubs,
+            self.threshold,
+            NetworkConfig::from_name(&self.network)?,
+        )?
+        .with_signature_scheme(SignatureScheme::from_name(&self.scheme)?))
+    }
+}
+
+#[derive(Args)]
+struct PsktEncodingArgs {
+    /// Output encoding: binary, base64 or hex
+    #[arg(long = "format", default_value = "base64")]
+    format: String,
+}
+
+impl PsktEncodingArgs {
+    fn encoding(&self) -> Result<PsktEncoding, WalletError> {
+        PsktEncoding::from_name(&self.format)
+    }
+}
+
+/// Where to read the private key from. Without any of these flags the key is
+/// prompted for on the terminal without echo.
+#[derive(Args)]
+struct KeySourceArgs {
+    /// Encrypted keystore file (the password is prompted for)
+    #[arg(long)]
+    keystore: Option<String>,
+    /// Read the hex private key from the first line of stdin
+    #[arg(long)]
+    key_stdin: bool,
+    /// Read the hex private key from the named environment variable
+    #[arg(long, value_name = "VAR")]
+    key_env: Option<String>,
+    /// Deprecated: hex private key on the command line (needs --insecure-key-arg)
+    #[arg(short, long)]
+    private_key: Option<String>,
+    /// Allow --private-key despite leaking the key into shell history
+    #[arg(long)]
+    insecure_key_arg: bool,
+}
+
+impl KeySourceArgs {
+    fn source(self) -> Result<KeySource, WalletError> {
+        let mut sources = Vec::new();
+        if let Some(path) = self.keystore {
+            sources.push(KeySource::Keystore(path.into()));
+        }
+        if self.key_stdin {
+            sources.push(KeySource::Stdin);
+        }
+        if let Some(name) = self.key_env {
+            sources.push(KeySource::Env(name));
+        }
+        if let Some(hex) = self.private_key {
+            if !self.insecure_key_arg {
+                return Err(WalletError::InvalidParameters(
+                    "--private-key exposes the key in shell history and process listings; \
+                     use --keystore, --key-stdin or --key-env, or pass --insecure-key-arg"
+                        .to_string(),
+                ));
+            }
+            eprintln!(
+                "Warning: --private-key is deprecated and insecure; \
+                 use --keystore, --key-stdin or --key-env instead."
+            );
+            sources.push(KeySource::Argument(hex.into()));
+        }
+
+        match sources.len() {
+            0 => Ok(KeySource::Prompt),
+            1 => Ok(sources.remove(0)),
+            _ => Err(WalletError::InvalidParameters(
+                "Use only one of --keystore, --key-stdin, --key-env and --private-key".to_string(),
+            )),
+        }
+    }
+}
+
+/// Which node to talk to and how.
+#[derive(Args)]
+struct NodeArgs {
+    /// Node endpoint; defaults to the public API for rest and to the
+    /// local kaspad port of the network otherwise
+    #[arg(long)]
+    rpc: Option<String>,
+    /// rest, grpc, wrpc-borsh or wrpc-json
+    #[arg(long, default_value = "rest")]
+    transport: String,
+}
+
+impl NodeArgs {
+    async fn connect(&self, network: &NetworkConfig) -> Result<Box<dyn NodeApi>, WalletError> {
+        node::connect(
+            Transport::from_name(&self.transport)?,
+            self.rpc.as_deref(),
+            network,
+        )
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to connect: {}", e)))
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -64,111 +844,1597 @@ async fn main() {
 
 async fn run_cli(cli: Cli) -> Result<(), WalletError> {
     match cli.command {
-        Commands::Create { network, output } => create_new_wallet(&network, output),
+        Commands::Create {
+            network,
+            output,
+            scheme,
+            words,
+            with_passphrase,
+        } => create_new_wallet(
+            &network,
+            output,
+            SignatureScheme::from_name(&scheme)?,
+            words,
+            with_passphrase,
+        ),
+        Commands::Restore {
+            network,
+            output,
+            scheme,
+            with_passphrase,
+        } => restore_wallet(
+            &network,
+            output,
+            SignatureScheme::from_name(&scheme)?,
+            with_passphrase,
+        ),
+        Commands::Derive {
+            network,
+            scheme,
+            account,
+            index,
+            count,
+            change,
+            with_passphrase,
+        } => derive_addresses(
+            &network,
+            SignatureScheme::from_name(&scheme)?,
+            account,
+            index..index.saturating_add(count),
+            if change {
+                AddressChain::Change
+            } else {
+                AddressChain::Receive
+            },
+            with_passphrase,
+        ),
         Commands::Info {
-            private_key,
+            key,
             network,
-        } => show_wallet_info(&private_key, &network),
+            scheme,
+        } => show_wallet_info(&load_wallet(key, network, scheme)?),
         Commands::Address {
-            private_key,
+            key,
+            network,
+            scheme,
+        } => generate_address(&load_wallet(key, network, scheme)?),
+        Commands::Balance {
+            address,
+            node,
+            network,
+        } => get_balance(&address, &node, &NetworkConfig::from_name(&network)?).await,
+        Commands::History {
+            address,
+            rpc,
+            limit,
+            offset,
+        } => {
+            let client = rpc::RpcClient::new(rpc.as_deref());
+            show_history(&client, &address, rpc::Page { limit, offset }).await
+        }
+        Commands::Send {
+            key,
+            network,
+            scheme,
+            to,
+            amount,
+            all,
+            fee_from_recipients,
+            fee_rate,
+            coin_selection,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let request = send::PaymentRequest::new(payment_outputs(&to, &amount, all)?, 0)
+                .with_strategy(Strategy::from_name(&coin_selection)?)
+                .with_send_all(all)
+                .with_fee_from_recipients(fee_from_recipients);
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            send_payment(&wallet, request, &options, &node).await
+        }
+        Commands::Compound {
+            key,
+            network,
+            scheme,
+            target_utxos,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            compound_utxos(&wallet, target_utxos, &options, &node).await
+        }
+        Commands::Sweep {
+            key,
             network,
-        } => generate_address(&private_key, &network),
-        Commands::Balance { address, rpc } => get_balance(&address, rpc.as_deref()).await,
+            scheme,
+            from_key,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let source = KeySource::from_spec(&from_key)?
+                .load_wallet(Some(wallet.get_network_name()), None)?;
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            sweep_key(&wallet, &source, &options, &node).await
+        }
+        Commands::Prepare {
+            from,
+            network,
+            to,
+            amount,
+            all,
+            fee_from_recipients,
+            fee_rate,
+            coin_selection,
+            output,
+            node,
+        } => {
+            let from = address::parse_address(&from)?;
+            let request = send::PaymentRequest::new(payment_outputs(&to, &amount, all)?, 0)
+                .with_strategy(Strategy::from_name(&coin_selection)?)
+                .with_send_all(all)
+                .with_fee_from_recipients(fee_from_recipients);
+            let network = NetworkConfig::from_name(&network)?;
+            let node_client = node.connect(&network).await?;
+            let result =
+                prepare_bundle(node_client.as_ref(), &network, &from, request, fee_rate).await;
+            let _ = node_client.disconnect().await;
+
+            let (bundle, pskt) = result?;
+            bundle.save(&output)?;
+            println!("Unsigned Payment:");
+            println!("==================");
+            println!("{}", pskt);
+            println!(
+                "Saved to {}; sign it offline with `sign {}`",
+                output, output
+            );
+            Ok(())
+        }
+        Commands::Sign {
+            bundle: path,
+            key,
+            scheme,
+            output,
+            yes,
+        } => {
+            let mut bundle = SigningBundle::load(&path)?;
+            let wallet = load_wallet(key, Some(bundle.network.clone()), scheme)?;
+            println!("Payment to Sign:");
+            println!("==================");
+            println!("From:    {}", bundle.address);
+            println!("{}", bundle.pskt()?);
+            if !yes {
+                let answer = prompt_line("Sign this transaction? [y/N] ")?;
+                if !matches!(answer.expose_secret().trim(), "y" | "Y" | "yes") {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+            }
+
+            let signed = bundle.sign(&wallet)?;
+            let output = output.unwrap_or(path);
+            bundle.save(&output)?;
+            println!("Signed {} inputs; saved to {}", signed, output);
+            Ok(())
+        }
+        Commands::Broadcast { bundle, node } => {
+            let bundle = SigningBundle::load(&bundle)?;
+            let tx = bundle.signed_transaction()?;
+            let node = node
+                .connect(&NetworkConfig::from_name(&bundle.network)?)
+                .await?;
+            let result = node.submit(&tx.tx).await;
+            let _ = node.disconnect().await;
+            let transaction_id = result.map_err(|e| {
+                WalletError::Network(format!("Failed to submit transaction: {}", e))
+            })?;
+            println!("Transaction submitted: {}", transaction_id);
+            Ok(())
+        }
+        Commands::Pskt { command } => run_pskt(command).await,
+        Commands::Multisig { command } => run_multisig(command).await,
+        Commands::Script { command } => run_script(command).await,
+        Commands::Htlc { command } => run_htlc(command).await,
+        Commands::Vault { command } => run_vault(command).await,
+        Commands::EstimateFee {
+            inputs,
+            outputs,
+            fee_rate,
+            network,
+            node,
+        } => {
+            let node = node.connect(&NetworkConfig::from_name(&network)?).await?;
+            let result = estimate_fee(node.as_ref(), inputs, outputs, fee_rate).await;
+            let _ = node.disconnect().await;
+            result
+        }
         Commands::ValidateAddress { address } => validate_address(&address),
     }
 }
 
-fn create_new_wallet(network: &str, output: Option<String>) -> Result<(), WalletError> {
-    let mut rng = rand::rngs::OsRng;
-    let mut secret_bytes = [0u8; 32];
-    rand::RngCore::fill_bytes(&mut rng, &mut secret_bytes);
-    let secret_key = SecretKey::from_slice(&secret_bytes).expect("Failed to create secret key");
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+async fn run_pskt(command: PsktCommand) -> Result<(), WalletError> {
+    match command {
+        PsktCommand::Create {
+            key,
+            network,
+            scheme,
+            to,
+            amount,
+            all,
+            fee_rate,
+            coin_selection,
+            output,
+            encoding,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let request = send::PaymentRequest::new(payment_outputs(&to, &amount, all)?, 0)
+                .with_strategy(Strategy::from_name(&coin_selection)?)
+                .with_send_all(all);
+            let encoding = encoding.encoding()?;
+            let node = node
+                .connect(&NetworkConfig::from_name(wallet.get_network_name())?)
+                .await?;
+            let result = create_pskt(node.as_ref(), &wallet, request, fee_rate).await;
+            let _ = node.disconnect().await;
+
+            let pskt = result?;
+            pskt.save(&output, encoding)?;
+            println!("{}", pskt);
+            println!("Saved to {}", output);
+            Ok(())
+        }
+        PsktCommand::Sign {
+            file,
+            key,
+            scheme,
+            output,
+            encoding,
+        } => {
+            let mut pskt = Pskt::load(&file)?;
+            let wallet = load_wallet(key, Some(pskt.network.clone()), scheme)?;
+            let signed = wallet.sign_pskt(&mut pskt)?;
+            if signed == 0 {
+                return Err(WalletError::InvalidParameters(format!(
+                    "No inputs of {} belong to {}",
+                    file,
+                    wallet.get_address()
+                )));
+            }
+            let output = output.unwrap_or(file);
+            pskt.save(&output, encoding.encoding()?)?;
+            println!("Signed {} inputs; saved to {}", signed, output);
+            Ok(())
+        }
+        PsktCommand::Combine {
+            files,
+            output,
+            encoding,
+        } => {
+            let mut pskt = Pskt::load(&files[0])?;
+            for file in &files[1..] {
+                pskt.combine(&Pskt::load(file)?)?;
+            }
+            pskt.save(&output, encoding.encoding()?)?;
+            println!("{}", pskt);
+            println!("Saved to {}", output);
+            Ok(())
+        }
+        PsktCommand::Finalize {
+            file,
+            output,
+            encoding,
+            submit,
+            node,
+        } => {
+            let mut pskt = Pskt::load(&file)?;
+            pskt.finalize()?;
+            let tx = pskt.extract()?;
+            let output = output.unwrap_or(file);
+            pskt.save(&output, encoding.encoding()?)?;
+            println!("Finalized {}; saved to {}", tx.tx.id(), output);
+            println!("Mass: {} grams", mass::transaction_mass(&tx)?);
+            if !submit {
+                return Ok(());
+            }
+
+            let node = node
+                .connect(&NetworkConfig::from_name(&pskt.network)?)
+                .await?;
+            let result = node.submit(&tx.tx).await;
+            let _ = node.disconnect().await;
+            let transaction_id = result.map_err(|e| {
+                WalletError::Network(format!("Failed to submit transaction: {}", e))
+            })?;
+            println!("Transaction submitted: {}", transaction_id);
+            Ok(())
+        }
+        PsktCommand::Inspect { file } => {
+            println!("{}", Pskt::load(&file)?);
+            Ok(())
+        }
+    }
+}
 
-    let address = address::generate_address(&public_key, network)?;
+async fn run_multisig(command: MultisigCommand) -> Result<(), WalletError> {
+    match command {
+        MultisigCommand::Address {
+            account,
+            index,
+            count,
+        } => {
+            let multisig = account.account()?;
+            println!(
+                "{}-of-{} Multisig Addresses:",
+                multisig.threshold(),
+                multisig.cosigners()
+            );
+            println!("==================");
+            for index in index..index.saturating_add(count) {
+                println!("{}  {}", index, multisig.address(index)?);
+            }
+            Ok(())
+        }
+        MultisigCommand::Create {
+            account,
+            index,
+            to,
+            amount,
+            fee_rate,
+            output,
+            encoding,
+            node,
+        } => {
+            let multisig = account.account()?;
+            let outputs: Vec<_> = payment_outputs(&to, &amount, false)?
+                .into_iter()
+                .map(|(address, amount)| (address, amount.sompi()))
+                .collect();
+            let encoding = encoding.encoding()?;
+            let node = node
+                .connect(&NetworkConfig::from_name(&account.network)?)
+                .await?;
+            let result =
+                create_multisig_pskt(node.as_ref(), &multisig, index, &outputs, fee_rate).await;
+            let _ = node.disconnect().await;
 
-    let private_key_hex = hex::encode(secret_key.secret_bytes());
-    let public_key_hex = hex::encode(public_key.serialize());
+            let pskt = result?;
+            pskt.save(&output, encoding)?;
+            println!("{}", pskt);
+            println!("Saved to {}", output);
+            Ok(())
+        }
+        MultisigCommand::Sign {
+            file,
+            account,
+            index,
+            with_passphrase,
+            output,
+            encoding,
+        } => {
+            let mut pskt = Pskt::load(&file)?;
+            let phrase = prompt_line("Enter your 12 or 24 mnemonic words: ")?;
+            let mnemonic = mnemonic::parse_mnemonic(phrase.expose_secret())?;
+            let passphrase = read_passphrase(with_passphrase)?;
+            let account = HdAccount::from_mnemonic(
+                &mnemonic,
+                passphrase.expose_secret(),
+                account,
+                NetworkConfig::from_name(&pskt.network)?,
+            )?;
 
-    let wallet_info = format!(
-        "Network: {}\n\
-         Private Key: {}\n\
-         Public Key: {}\n\
-         Address: {}\n",
-        network,
-        private_key_hex,
-        public_key_hex,
-        address
+            let key = account.derive_key(AddressChain::Receive, index)?;
+            let signed = pskt.sign(key.secret_key())?;
+            if signed == 0 {
+                return Err(WalletError::InvalidParameters(format!(
+                    "No inputs of {} are locked to {}",
+                    file,
+                    account.derivation_path(AddressChain::Receive, index)
+                )));
+            }
+            let output = output.unwrap_or(file);
+            pskt.save(&output, encoding.encoding()?)?;
+            println!("Signed {} inputs; saved to {}", signed, output);
+            Ok(())
+        }
+    }
+}
+
+async fn run_script(command: ScriptCommand) -> Result<(), WalletError> {
+    match command {
+        ScriptCommand::Address { script, network } => {
+            let redeem_script = script.redeem_script()?;
+            let prefix = address::network_prefix(&network)?;
+            println!("Script Address:");
+            println!("==================");
+            println!(
+                "Address: {}",
+                address::address_from_redeem_script(&redeem_script, prefix)
+            );
+            print_script(&redeem_script)
+        }
+        ScriptCommand::Decode { script } => print_script(&hex::decode(script.trim())?),
+        ScriptCommand::Spend {
+            script,
+            unlock,
+            key,
+            network,
+            scheme,
+            to,
+            amount,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let spend =
+                ScriptSpend::new(script.redeem_script()?, ScriptSpend::parse_unlock(&unlock)?);
+            let wallet = if spend.needs_signature() {
+                Some(load_wallet(key, Some(network.clone()), scheme)?)
+            } else {
+                None
+            };
+            let outputs: Vec<_> = payment_outputs(&to, &amount, false)?
+                .into_iter()
+                .map(|(address, amount)| (address, amount.sompi()))
+                .collect();
+            let network = NetworkConfig::from_name(&network)?;
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let node = node.connect(&network).await?;
+            let result = spend_script(
+                node.as_ref(),
+                &network,
+                &spend,
+                &outputs,
+                wallet.as_ref(),
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+    }
+}
+
+async fn run_htlc(command: HtlcCommand) -> Result<(), WalletError> {
+    match command {
+        HtlcCommand::Initiate {
+            key,
+            network,
+            scheme,
+            recipient,
+            amount,
+            lock_time,
+            secret_hash,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let recipient = address::parse_address(&recipient)?;
+            let amount = Amount::parse(&amount)?;
+            let secret_hash = match secret_hash {
+                Some(secret_hash) => hex::decode(secret_hash.trim())?.try_into().map_err(|_| {
+                    WalletError::InvalidParameters(
+                        "The secret hash must be 32 bytes of hex".to_string(),
+                    )
+                })?,
+                None => {
+                    let secret = htlc::generate_secret();
+                    println!("Secret:  {}", hex::encode(secret.as_slice()));
+                    println!(
+                        "Keep the secret private until you redeem the other side of the swap."
+                    );
+                    htlc::secret_hash(secret.as_slice())
+                }
+            };
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let network = NetworkConfig::from_name(wallet.get_network_name())?;
+            let node = node.connect(&network).await?;
+            let result = initiate_htlc(
+                node.as_ref(),
+                &wallet,
+                recipient,
+                amount,
+                &lock_time,
+                secret_hash,
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+        HtlcCommand::Inspect { contract, network } => {
+            let network = NetworkConfig::from_name(&network)?;
+            print_htlc(&parse_htlc(&contract, &network)?, &network)
+        }
+        HtlcCommand::Redeem {
+            contract,
+            secret,
+            key,
+            network,
+            scheme,
+            to,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let network = NetworkConfig::from_name(wallet.get_network_name())?;
+            let htlc = parse_htlc(&contract, &network)?;
+            if wallet.address() != *htlc.recipient() {
+                return Err(WalletError::InvalidParameters(format!(
+                    "Only the recipient {} can redeem this contract",
+                    htlc.recipient()
+                )));
+            }
+            let spend = htlc.redeem(&Zeroizing::new(hex::decode(secret.trim())?))?;
+            let destination = match to {
+                Some(to) => address::parse_address(&to)?,
+                None => wallet.address(),
+            };
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let node = node.connect(&network).await?;
+            let result = sweep_script(
+                node.as_ref(),
+                &network,
+                &spend,
+                &destination,
+                Some(&wallet),
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+        HtlcCommand::Refund {
+            contract,
+            key,
+            network,
+            scheme,
+            to,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let network = NetworkConfig::from_name(wallet.get_network_name())?;
+            let htlc = parse_htlc(&contract, &network)?;
+            if wallet.address() != *htlc.refund() {
+                return Err(WalletError::InvalidParameters(format!(
+                    "Only the refund address {} can refund this contract",
+                    htlc.refund()
+                )));
+            }
+            let destination = match to {
+                Some(to) => address::parse_address(&to)?,
+                None => wallet.address(),
+            };
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let node = node.connect(&network).await?;
+            let result = refund_htlc(
+                node.as_ref(),
+                &network,
+                &htlc,
+                &destination,
+                &wallet,
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+        HtlcCommand::ExtractSecret {
+            contract,
+            tx,
+            signature_script,
+            network,
+            node,
+        } => {
+            let network = NetworkConfig::from_name(&network)?;
+            let htlc = parse_htlc(&contract, &network)?;
+            let signature_scripts = match (tx, signature_script) {
+                (_, Some(signature_script)) => vec![hex::decode(signature_script.trim())?],
+                (Some(tx), None) => {
+                    let id = TransactionId::from_str(tx.trim()).map_err(|e| {
+                        WalletError::InvalidParameters(format!("Invalid transaction id: {}", e))
+                    })?;
+                    let node = node.connect(&network).await?;
+                    let result = node.input_signature_scripts(&id).await;
+                    let _ = node.disconnect().await;
+                    result.map_err(|e| {
+                        WalletError::Network(format!("Failed to get transaction: {}", e))
+                    })?
+                }
+                (None, None) => vec![],
+            };
+            let secret = signature_scripts
+                .iter()
+                .find_map(|script| htlc::extract_secret(htlc.secret_hash(), script))
+                .ok_or_else(|| {
+                    WalletError::InvalidParameters(
+                        "No input reveals the secret of this contract".to_string(),
+                    )
+                })?;
+            println!("Secret: {}", hex::encode(secret.as_slice()));
+            Ok(())
+        }
+    }
+}
+
+fn parse_htlc(contract: &str, network: &NetworkConfig) -> Result<Htlc, WalletError> {
+    Htlc::parse(&hex::decode(contract.trim())?, network.address_prefix()).ok_or_else(|| {
+        WalletError::InvalidParameters("The script is not an HTLC contract".to_string())
+    })
+}
+
+fn print_htlc(htlc: &Htlc, network: &NetworkConfig) -> Result<(), WalletError> {
+    println!("HTLC Contract:");
+    println!("==================");
+    println!("Address:     {}", htlc.address(network.address_prefix())?);
+    println!("Recipient:   {}", htlc.recipient());
+    println!("Refund to:   {}", htlc.refund());
+    println!("Refundable:  after {}", htlc.lock_time());
+    println!("Secret hash: {}", hex::encode(htlc.secret_hash()));
+    println!("Contract:    {}", hex::encode(htlc.redeem_script()?));
+    Ok(())
+}
+
+/// Reads `--lock-time`, resolving `+<n>` against the node's current DAA
+/// score.
+async fn lock_time_arg(text: &str, node: &dyn NodeApi) -> Result<LockTime, WalletError> {
+    let Some(offset) = text.trim().strip_prefix('+') else {
+        return LockTime::parse(text);
+    };
+    let offset: u64 = offset.parse().map_err(|_| {
+        WalletError::InvalidParameters(format!("Invalid lock time offset {}", text))
+    })?;
+    let daa_score = node
+        .virtual_daa_score()
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to get DAA score: {}", e)))?;
+    LockTime::parse(&daa_score.saturating_add(offset).to_string())
+}
+
+async fn initiate_htlc(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    recipient: kaspa_addresses::Address,
+    amount: Amount,
+    lock_time: &str,
+    secret_hash: [u8; 32],
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let lock_time = lock_time_arg(lock_time, node).await?;
+    let htlc = Htlc::new(secret_hash, recipient, wallet.address(), lock_time)?;
+    let network = NetworkConfig::from_name(wallet.get_network_name())?;
+    print_htlc(&htlc, &network)?;
+    println!("Share the contract with the recipient so they can check it.");
+    println!();
+
+    let address = htlc.address(network.address_prefix())?;
+    let request = send::PaymentRequest::new(vec![(address, amount)], 0);
+    send_with_node(node, wallet, request, options).await?;
+    Ok(())
+}
+
+/// Sweeps `htlc` back to `destination`, first checking that the lock time
+/// has passed so the node does not reject the transaction as not final.
+async fn refund_htlc(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    htlc: &Htlc,
+    destination: &kaspa_addresses::Address,
+    wallet: &KaspaWallet,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    check_lock_time_reached(node, htlc.lock_time()).await?;
+    let spend = htlc.refund_spend()?;
+    sweep_script(node, network, &spend, destination, Some(wallet), options).await
+}
+
+async fn check_lock_time_reached(
+    node: &dyn NodeApi,
+    lock_time: LockTime,
+) -> Result<(), WalletError> {
+    let (daa_score, past_median_time) = chain_clock(node).await?;
+    if !lock_time.is_reached(daa_score, past_median_time) {
+        let now = match lock_time {
+            LockTime::DaaScore(_) => LockTime::DaaScore(daa_score),
+            LockTime::Timestamp(_) => LockTime::Timestamp(past_median_time),
+        };
+        return Err(WalletError::InvalidParameters(format!(
+            "Locked until {}; the network is at {}",
+            lock_time, now
+        )));
+    }
+    Ok(())
+}
+
+/// The virtual DAA score and past median time, which lock times are
+/// checked against.
+async fn chain_clock(node: &dyn NodeApi) -> Result<(u64, u64), WalletError> {
+    let daa_score = node
+        .virtual_daa_score()
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to get DAA score: {}", e)))?;
+    let past_median_time = node
+        .past_median_time()
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to get past median time: {}", e)))?;
+    Ok((daa_score, past_median_time))
+}
+
+async fn run_vault(command: VaultCommand) -> Result<(), WalletError> {
+    match command {
+        VaultCommand::Create {
+            key,
+            network,
+            scheme,
+            amount,
+            lock_time,
+            owner,
+            book,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let owner = match owner {
+                Some(owner) => address::parse_address(&owner)?,
+                None => wallet.address(),
+            };
+            let amount = Amount::parse(&amount)?;
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let network = NetworkConfig::from_name(wallet.get_network_name())?;
+            let node = node.connect(&network).await?;
+            let result = create_vault(
+                node.as_ref(),
+                &wallet,
+                owner,
+                amount,
+                &lock_time,
+                &book.path,
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+        VaultCommand::List {
+            network,
+            book,
+            node,
+        } => {
+            let network = NetworkConfig::from_name(&network)?;
+            let vaults = VaultBook::load(&book.path)?.vaults(&network)?;
+            let node = node.connect(&network).await?;
+            let result = list_vaults(node.as_ref(), &network, &vaults).await;
+            let _ = node.disconnect().await;
+            result
+        }
+        VaultCommand::Withdraw {
+            vault,
+            book,
+            key,
+            network,
+            scheme,
+            to,
+            fee_rate,
+            dry_run,
+            yes,
+            node,
+        } => {
+            let wallet = load_wallet(key, network, scheme)?;
+            let network = NetworkConfig::from_name(wallet.get_network_name())?;
+            let vault =
+                VaultBook::load(&book.path)?.find(&network, &address::parse_address(&vault)?)?;
+            if wallet.address() != *vault.owner() {
+                return Err(WalletError::InvalidParameters(format!(
+                    "Only the owner {} can withdraw from this vault",
+                    vault.owner()
+                )));
+            }
+            let destination = match to {
+                Some(to) => address::parse_address(&to)?,
+                None => wallet.address(),
+            };
+            let options = SendOptions {
+                fee_rate,
+                dry_run,
+                yes,
+            };
+            let node = node.connect(&network).await?;
+            let result = withdraw_vault(
+                node.as_ref(),
+                &network,
+                &vault,
+                &destination,
+                &wallet,
+                &options,
+            )
+            .await;
+            let _ = node.disconnect().await;
+            result
+        }
+    }
+}
+
+/// Funds a new vault and records it once the payment is submitted, so a dry
+/// run or a declined payment leaves the book untouched. The book is read
+/// first, so a broken one stops the payment instead of losing the script.
+async fn create_vault(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    owner: kaspa_addresses::Address,
+    amount: Amount,
+    lock_time: &str,
+    book_path: &str,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let vault = Vault::new(owner, lock_time_arg(lock_time, node).await?)?;
+    let network = NetworkConfig::from_name(wallet.get_network_name())?;
+    let address = vault.address(network.address_prefix())?;
+    let mut book = VaultBook::load(book_path)?;
+    book.add(&network, &vault)?;
+
+    println!("Vault:");
+    println!("==================");
+    println!("Address: {}", address);
+    println!("Owner:   {}", vault.owner());
+    println!("Unlocks: after {}", vault.lock_time());
+    println!("Script:  {}", hex::encode(vault.redeem_script()?));
+    println!();
+
+    let request = send::PaymentRequest::new(vec![(address, amount)], 0);
+    let submitted = send_with_node(node, wallet, request, options).await?;
+    if submitted.is_some() {
+        book.save(book_path)?;
+        println!("Vault recorded in {}", book_path);
+    }
+    Ok(())
+}
+
+async fn list_vaults(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    vaults: &[Vault],
+) -> Result<(), WalletError> {
+    let balances = vault::vault_balances(node, vaults, network.address_prefix()).await?;
+    let (daa_score, past_median_time) = chain_clock(node).await?;
+
+    println!(
+        "Vaults (DAA score {}, {}):",
+        daa_score,
+        LockTime::Timestamp(past_median_time)
     );
+    println!("==================");
+    let (mut locked, mut unlocked) = (0u64, 0u64);
+    for balance in &balances {
+        let lock_time = balance.vault.lock_time();
+        let status = if lock_time.is_reached(daa_score, past_median_time) {
+            unlocked += balance.amount;
+            format!("unlocked since {}", lock_time)
+        } else {
+            locked += balance.amount;
+            format!("locked until {}", lock_time)
+        };
+        println!("{}", balance.address);
+        println!(
+            "  {} in {} UTXOs, {}",
+            Amount::from_sompi(balance.amount),
+            balance.utxo_count,
+            status
+        );
+    }
+    println!("Locked:   {}", Amount::from_sompi(locked));
+    println!("Unlocked: {}", Amount::from_sompi(unlocked));
+    Ok(())
+}
+
+async fn withdraw_vault(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    vault: &Vault,
+    destination: &kaspa_addresses::Address,
+    wallet: &KaspaWallet,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    check_lock_time_reached(node, vault.lock_time()).await?;
+    let spend = vault.spend()?;
+    sweep_script(node, network, &spend, destination, Some(wallet), options).await
+}
+
+fn print_script(redeem_script: &[u8]) -> Result<(), WalletError> {
+    println!("Script:  {}", script::disassemble(redeem_script)?);
+    println!("Hex:     {}", hex::encode(redeem_script));
+    println!("Sig ops: {}", script::sig_op_count(redeem_script)?);
+    Ok(())
+}
+
+fn create_new_wallet(
+    network: &str,
+    output: Option<String>,
+    scheme: SignatureScheme,
+    words: usize,
+    with_passphrase: bool,
+) -> Result<(), WalletError> {
+    let mnemonic = mnemonic::generate_mnemonic(words)?;
+    let passphrase = read_passphrase(with_passphrase)?;
+    let wallet = KaspaWallet::from_mnemonic(
+        &mnemonic,
+        passphrase.expose_secret(),
+        NetworkConfig::from_name(network)?,
+    )?
+    .with_signature_scheme(scheme);
+
+    println!("New Wallet Created:");
+    println!("==================");
+    println!("Mnemonic: {}", mnemonic);
 
     if let Some(output_path) = output {
-        fs::write(&output_path, wallet_info)?;
-        println!("Wallet created and saved to {}", output_path);
+        println!("Address: {}", wallet.get_address());
+        save_keystore(&wallet, &output_path)?;
+        println!("Encrypted keystore saved to {}", output_path);
     } else {
-        println!("New Wallet Created:");
-        println!("==================");
-        print!("{}", wallet_info);
+        print!("{}", wallet_info_text(&wallet).expose_secret());
+    }
 
-        print!("Save this information securely. Press Enter to continue...");
-        io::stdout().flush()?;
-        let _ = io::stdin().read_line(&mut String::new());
+    if with_passphrase {
+        println!("The passphrase is not stored; you need it together with the words to restore.");
     }
+    print!("Write down the mnemonic words and keep them safe. Press Enter to continue...");
+    io::stdout().flush()?;
+    let _ = io::stdin().read_line(&mut String::new());
 
     Ok(())
 }
 
-fn show_wallet_info(private_key: &str, network: &str) -> Result<(), WalletError> {
-    let secret_key_bytes = hex::decode(private_key)?;
-    let secret_key = SecretKey::from_slice(&secret_key_bytes)?;
+fn restore_wallet(
+    network: &str,
+    output: Option<String>,
+    scheme: SignatureScheme,
+    with_passphrase: bool,
+) -> Result<(), WalletError> {
+    let phrase = prompt_line("Enter your 12 or 24 mnemonic words: ")?;
+    let mnemonic = mnemonic::parse_mnemonic(phrase.expose_secret())?;
+    let passphrase = read_passphrase(with_passphrase)?;
+    let wallet = KaspaWallet::from_mnemonic(
+        &mnemonic,
+        passphrase.expose_secret(),
+        NetworkConfig::from_name(network)?,
+    )?
+    .with_signature_scheme(scheme);
 
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+    if let Some(output_path) = output {
+        save_keystore(&wallet, &output_path)?;
+        println!("Wallet restored and saved to {}", output_path);
+    } else {
+        println!("Wallet Restored:");
+        println!("==================");
+        print!("{}", wallet_info_text(&wallet).expose_secret());
+    }
 
-    let address = address::generate_address(&public_key, network)?;
+    Ok(())
+}
 
-    println!("Wallet Information:");
+fn derive_addresses(
+    network: &str,
+    scheme: SignatureScheme,
+    account: u32,
+    indexes: std::ops::Range<u32>,
+    chain: AddressChain,
+    with_passphrase: bool,
+) -> Result<(), WalletError> {
+    let phrase = prompt_line("Enter your 12 or 24 mnemonic words: ")?;
+    let mnemonic = mnemonic::parse_mnemonic(phrase.expose_secret())?;
+    let passphrase = read_passphrase(with_passphrase)?;
+
+    let account = HdAccount::from_mnemonic(
+        &mnemonic,
+        passphrase.expose_secret(),
+        account,
+        NetworkConfig::from_name(network)?,
+    )?
+    .with_signature_scheme(scheme);
+
+    println!("Derived Addresses:");
     println!("==================");
-    println!("Network: {}", network);
-    println!("Private Key: {}", private_key);
-    println!("Public Key: {}", hex::encode(public_key.serialize()));
-    println!("Address: {}", address);
+    for index in indexes {
+        println!(
+            "{}  {}",
+            account.derivation_path(chain, index),
+            account.derive_address(chain, index)?
+        );
+    }
+    println!("Account xpub: {}", account.account_xpub());
 
     Ok(())
 }
 
-fn generate_address(private_key: &str, network: &str) -> Result<(), WalletError> {
-    let secret_key_bytes = hex::decode(private_key)?;
-    let secret_key = SecretKey::from_slice(&secret_key_bytes)?;
+fn wallet_info_text(wallet: &KaspaWallet) -> SecretString {
+    SecretString::new(format!(
+        "Network: {}\n\
+         Scheme: {}\n\
+         Private Key: {}\n\
+         Public Key: {}\n\
+         Address: {}\n",
+        wallet.get_network_name(),
+        wallet.signature_scheme(),
+        wallet.get_private_key().expose_secret(),
+        wallet.get_public_key(),
+        wallet.get_address()
+    ))
+}
+
+fn read_passphrase(with_passphrase: bool) -> Result<SecretString, WalletError> {
+    if with_passphrase {
+        Ok(rpassword::prompt_password("Enter BIP39 passphrase: ")?.into())
+    } else {
+        Ok(SecretString::default())
+    }
+}
+
+/// Asks for a new keystore password twice, without echoing it.
+fn read_new_password() -> Result<SecretString, WalletError> {
+    let password = SecretString::new(rpassword::prompt_password("Enter keystore password: ")?);
+    if password.is_empty() {
+        return Err(WalletError::InvalidParameters(
+            "Keystore password must not be empty".to_string(),
+        ));
+    }
+    let confirmation =
+        SecretString::new(rpassword::prompt_password("Confirm keystore password: ")?);
+    if confirmation.expose_secret() != password.expose_secret() {
+        return Err(WalletError::InvalidParameters(
+            "Passwords do not match".to_string(),
+        ));
+    }
+    Ok(password)
+}
+
+fn save_keystore(wallet: &KaspaWallet, path: &str) -> Result<(), WalletError> {
+    let password = read_new_password()?;
+    wallet.save(path, password.expose_secret())
+}
+
+fn prompt_line(prompt: &str) -> Result<SecretString, WalletError> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut line = Zeroizing::new(String::new());
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string().into())
+}
 
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+fn load_wallet(
+    key: KeySourceArgs,
+    network: Option<String>,
+    scheme: Option<String>,
+) -> Result<KaspaWallet, WalletError> {
+    let scheme = scheme
+        .as_deref()
+        .map(SignatureScheme::from_name)
+        .transpose()?;
+    key.source()?.load_wallet(network.as_deref(), scheme)
+}
 
-    let address = address::generate_address(&public_key, network)?;
+fn show_wallet_info(wallet: &KaspaWallet) -> Result<(), WalletError> {
+    println!("Wallet Information:");
+    println!("==================");
+    println!("Network: {}", wallet.get_network_name());
+    println!("Scheme: {}", wallet.signature_scheme());
+    println!("Private Key: {}", wallet.get_private_key().expose_secret());
+    println!("Public Key: {}", wallet.get_public_key());
+    println!("Address: {}", wallet.get_address());
+
+    Ok(())
+}
 
+fn generate_address(wallet: &KaspaWallet) -> Result<(), WalletError> {
     println!("Generated Address:");
     println!("==================");
-    println!("{}", address);
+    println!("{}", wallet.get_address());
 
     Ok(())
 }
 
-async fn get_balance(address: &str, rpc_url: Option<&str>) -> Result<(), WalletError> {
-    let rpc = rpc_url.unwrap_or(DEFAULT_RPC_URL);
-    let client = RpcClient::new(Some(rpc));
+async fn get_balance(
+    address: &str,
+    node_args: &NodeArgs,
+    network: &NetworkConfig,
+) -> Result<(), WalletError> {
+    let parsed = address::parse_address(address)?;
+    let node = node_args.connect(network).await?;
+    let balance = node.balance(&parsed).await;
+    let pending = match balance {
+        Ok(_) => node.mempool_entries(&parsed).await,
+        Err(_) => Ok(Default::default()),
+    };
+    let _ = node.disconnect().await;
+    let balance =
+        balance.map_err(|e| WalletError::Network(format!("Failed to get balance: {}", e)))?;
 
-    match client.get_balance_by_address(address).await {
-        Ok(response) => {
-            println!("Balance for {}:", address);
-            println!("==================");
-            println!("Balance: {} sompi", response.balance);
-            println!("KAS: {:.8}", response.balance as f64 / 100_000_000.0);
+    println!("Balance for {}:", address);
+    println!("==================");
+    println!("Balance: {} sompi", balance);
+    println!("KAS: {}", Amount::from_sompi(balance).to_kas_string());
+    match pending {
+        Ok(pending) => {
+            let incoming: u64 = pending.receiving.iter().map(|entry| entry.received).sum();
+            println!(
+                "Pending: {} incoming in {} transactions, {} outgoing transactions",
+                Amount::from_sompi(incoming),
+                pending.receiving.len(),
+                pending.sending.len()
+            );
         }
+        Err(rpc::RpcError::Unsupported(_)) => {}
         Err(e) => {
-            return Err(WalletError::Network(format!("Failed to get balance: {}", e)));
+            return Err(WalletError::Network(format!(
+                "Failed to get mempool entries: {}",
+                e
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+async fn show_history(
+    client: &rpc::RpcClient,
+    address: &str,
+    page: rpc::Page,
+) -> Result<(), WalletError> {
+    address::parse_address(address)?;
+    let network_error =
+        |e: rpc::RpcError| WalletError::Network(format!("Failed to get history: {}", e));
+    let total = client
+        .get_transaction_count(address)
+        .await
+        .map_err(network_error)?;
+    let transactions = client
+        .get_transactions_by_address(address, page)
+        .await
+        .map_err(network_error)?;
+    let blue_score = client
+        .get_virtual_blue_score()
+        .await
+        .map_err(network_error)?;
+
+    println!("History for {}:", address);
+    println!("==================");
+    println!("Transactions: {}", total);
+    for tx in &transactions {
+        let received: u64 = tx
+            .outputs
+            .iter()
+            .filter(|output| output.script_public_key_address.as_deref() == Some(address))
+            .map(|output| output.amount)
+            .sum();
+        let spent: u64 = tx
+            .inputs
+            .iter()
+            .filter(|input| input.previous_outpoint_address.as_deref() == Some(address))
+            .filter_map(|input| input.previous_outpoint_amount)
+            .sum();
+        let change = if received >= spent {
+            format!("+{}", Amount::from_sompi(received - spent))
+        } else {
+            format!("-{}", Amount::from_sompi(spent - received))
+        };
+        let confirmations = match tx.accepting_block_blue_score {
+            Some(score) if tx.is_accepted => {
+                format!("{} confirmations", blue_score.saturating_sub(score))
+            }
+            _ => "unconfirmed".to_string(),
+        };
+        println!();
+        println!("{}", tx.transaction_id);
+        println!("  Amount: {}", change);
+        if let Some(time) = tx.block_time {
+            println!("  Time:   Unix time {}", time / 1000);
+        }
+        println!("  Status: {}", confirmations);
+    }
+
+    let shown = page.offset as u64 + transactions.len() as u64;
+    if shown < total {
+        println!();
+        println!(
+            "Showing {} of {}; next page with --offset {}",
+            transactions.len(),
+            total,
+            page.next().offset
+        );
+    }
+    Ok(())
+}
+
+async fn estimate_fee(
+    node: &dyn NodeApi,
+    inputs: usize,
+    outputs: usize,
+    fee_rate: Option<u64>,
+) -> Result<(), WalletError> {
+    if inputs == 0 || outputs == 0 {
+        return Err(WalletError::InvalidParameters(
+            "A transaction needs at least one input and one output".to_string(),
+        ));
+    }
+    let fee_rate = send::resolve_fee_rate(node, fee_rate).await?;
+
+    println!("Fee Estimate:");
+    println!("==================");
+    println!("Inputs:   {}", inputs);
+    println!("Outputs:  {}", outputs);
+    println!(
+        "Mass:     {} grams",
+        transaction::estimate_fee(inputs, outputs, 1)
+    );
+    println!("Fee rate: {} sompi/gram", fee_rate);
+    println!(
+        "Fee:      {}",
+        Amount::from_sompi(transaction::estimate_fee(inputs, outputs, fee_rate))
+    );
+    Ok(())
+}
+
+/// How `send`, `compound` and `sweep` build and confirm transactions.
+struct SendOptions {
+    fee_rate: Option<u64>,
+    dry_run: bool,
+    yes: bool,
+}
+
+/// Pairs each `--to` with its `--amount`. In send-all mode the last
+/// recipient takes the remainder and has no amount of its own.
+fn payment_outputs(
+    to: &[String],
+    amounts: &[String],
+    send_all: bool,
+) -> Result<Vec<(kaspa_addresses::Address, Amount)>, WalletError> {
+    let expected = if send_all {
+        to.len().saturating_sub(1)
+    } else {
+        to.len()
+    };
+    if amounts.len() != expected {
+        return Err(WalletError::InvalidParameters(format!(
+            "Expected {} --amount values for {} --to addresses{}",
+            expected,
+            to.len(),
+            if send_all { " with --all" } else { "" }
+        )));
+    }
+
+    to.iter()
+        .enumerate()
+        .map(|(index, address)| {
+            let amount = match amounts.get(index) {
+                Some(amount) => Amount::parse(amount)?,
+                None => Amount::ZERO,
+            };
+            Ok((address::parse_address(address)?, amount))
+        })
+        .collect()
+}
+
+async fn send_payment(
+    wallet: &KaspaWallet,
+    request: send::PaymentRequest,
+    options: &SendOptions,
+    node_args: &NodeArgs,
+) -> Result<(), WalletError> {
+    let node = node_args
+        .connect(&NetworkConfig::from_name(wallet.get_network_name())?)
+        .await?;
+    let result = send_with_node(node.as_ref(), wallet, request, options).await;
+    let _ = node.disconnect().await;
+    result.map(|_| ())
+}
+
+/// Builds, shows and, once confirmed, submits `request`. Returns the id of
+/// the submitted transaction, or `None` on a dry run or when the user
+/// declines.
+async fn send_with_node(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    mut request: send::PaymentRequest,
+    options: &SendOptions,
+) -> Result<Option<TransactionId>, WalletError> {
+    request.fee_rate = send::resolve_fee_rate(node, options.fee_rate).await?;
+    let payment = send::build_payment(node, wallet, &request).await?;
+
+    println!("Payment Summary:");
+    println!("==================");
+    println!("From:    {}", wallet.get_address());
+    println!("{}", payment);
+
+    if !confirm_submission(options, "Send this transaction? [y/N] ")? {
+        return Ok(None);
+    }
+
+    let transaction_id = send::submit_payment(node, &payment).await?;
+    println!("Transaction submitted: {}", transaction_id);
+    Ok(Some(transaction_id))
+}
+
+/// Selects inputs for `request` as `send` would and wraps the transaction,
+/// without its signatures, in a PSKT.
+async fn create_pskt(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    mut request: send::PaymentRequest,
+    fee_rate: Option<u64>,
+) -> Result<Pskt, WalletError> {
+    request.fee_rate = send::resolve_fee_rate(node, fee_rate).await?;
+    let payment = send::build_payment(node, wallet, &request).await?;
+    if !payment.consolidations.is_empty() {
+        return Err(WalletError::InvalidParameters(
+            "The inputs needed do not fit in one transaction; run `compound` first".to_string(),
+        ));
+    }
+    Pskt::new(wallet.get_network_name(), &payment.tx)
+}
+
+async fn create_multisig_pskt(
+    node: &dyn NodeApi,
+    multisig: &MultisigAccount,
+    index: u32,
+    outputs: &[(kaspa_addresses::Address, u64)],
+    fee_rate: Option<u64>,
+) -> Result<Pskt, WalletError> {
+    let fee_rate = send::resolve_fee_rate(node, fee_rate).await?;
+    multisig.build_spend(node, index, outputs, fee_rate).await
+}
+
+/// Builds the unsigned payment for `from` without any key and wraps it in
+/// a signing bundle.
+async fn prepare_bundle(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    from: &kaspa_addresses::Address,
+    mut request: send::PaymentRequest,
+    fee_rate: Option<u64>,
+) -> Result<(SigningBundle, Pskt), WalletError> {
+    request.fee_rate = send::resolve_fee_rate(node, fee_rate).await?;
+    let tx = send::build_unsigned_payment(node, from, &request).await?;
+    let pskt = Pskt::new(&network.name, &tx)?;
+    Ok((SigningBundle::new(from, &pskt)?, pskt))
+}
+
+async fn spend_script(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    spend: &ScriptSpend,
+    outputs: &[(kaspa_addresses::Address, u64)],
+    wallet: Option<&KaspaWallet>,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let fee_rate = send::resolve_fee_rate(node, options.fee_rate).await?;
+    let prefix = network.address_prefix();
+    let tx = p2sh::build_script_spend(node, spend, prefix, outputs, wallet, fee_rate).await?;
+    submit_script_spend(node, prefix, spend, &tx, options).await
+}
+
+/// Like `spend_script`, but moves everything the script holds to
+/// `destination`.
+async fn sweep_script(
+    node: &dyn NodeApi,
+    network: &NetworkConfig,
+    spend: &ScriptSpend,
+    destination: &kaspa_addresses::Address,
+    wallet: Option<&KaspaWallet>,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let fee_rate = send::resolve_fee_rate(node, options.fee_rate).await?;
+    let prefix = network.address_prefix();
+    let tx = p2sh::build_script_sweep(node, spend, prefix, destination, wallet, fee_rate).await?;
+    submit_script_spend(node, prefix, spend, &tx, options).await
+}
+
+async fn submit_script_spend(
+    node: &dyn NodeApi,
+    prefix: kaspa_addresses::Prefix,
+    spend: &ScriptSpend,
+    tx: &kaspa_consensus_core::tx::SignableTransaction,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let input_total: u64 = tx.entries.iter().flatten().map(|entry| entry.amount).sum();
+    let output_total: u64 = tx.tx.outputs.iter().map(|output| output.value).sum();
+    let fee = input_total.checked_sub(output_total).ok_or_else(|| {
+        WalletError::Transaction(format!(
+            "Outputs total {} sompi but inputs only {} sompi",
+            output_total, input_total
+        ))
+    })?;
+    println!("Script Spend:");
+    println!("==================");
+    println!("From:    {}", spend.address(prefix));
+    for output in &tx.tx.outputs {
+        println!(
+            "To:      {} {}",
+            kaspa_txscript::extract_script_pub_key_address(&output.script_public_key, prefix)
+                .map_err(|e| WalletError::Transaction(e.to_string()))?,
+            Amount::from_sompi(output.value)
+        );
+    }
+    println!("Inputs:  {}", tx.tx.inputs.len());
+    println!("Fee:     {}", Amount::from_sompi(fee));
+    if tx.tx.lock_time != 0 {
+        println!("Lock:    {}", LockTime::from_value(tx.tx.lock_time));
+    }
+    println!("Mass:    {} grams", mass::transaction_mass(tx)?);
+    println!("Tx id:   {}", tx.tx.id());
+
+    if !confirm_submission(options, "Send this transaction? [y/N] ")? {
+        return Ok(());
+    }
+    let transaction_id = node
+        .submit(&tx.tx)
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to submit transaction: {}", e)))?;
+    println!("Transaction submitted: {}", transaction_id);
+    Ok(())
+}
+
+/// Handles `--dry-run` and `--yes`; true when the caller should submit.
+fn confirm_submission(options: &SendOptions, prompt: &str) -> Result<bool, WalletError> {
+    if options.dry_run {
+        println!("Dry run: nothing submitted.");
+        return Ok(false);
+    }
+    if !options.yes {
+        let answer = prompt_line(prompt)?;
+        if !matches!(answer.expose_secret().trim(), "y" | "Y" | "yes") {
+            println!("Aborted.");
+            return Ok(false);
         }
     }
+    Ok(true)
+}
+
+async fn compound_utxos(
+    wallet: &KaspaWallet,
+    target: usize,
+    options: &SendOptions,
+    node_args: &NodeArgs,
+) -> Result<(), WalletError> {
+    let node = node_args
+        .connect(&NetworkConfig::from_name(wallet.get_network_name())?)
+        .await?;
+    let result = compound_with_node(node.as_ref(), wallet, target, options).await;
+    let _ = node.disconnect().await;
+    result
+}
+
+async fn compound_with_node(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    target: usize,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let fee_rate = send::resolve_fee_rate(node, options.fee_rate).await?;
+    let utxos = coin_selection::spendable_utxos(node, &[wallet.address()]).await?;
+    let utxo_count = utxos.len();
+    let merged = compound::compound(wallet, utxos, target, fee_rate)?;
+
+    println!("Compound Summary:");
+    println!("==================");
+    println!("Address:      {}", wallet.get_address());
+    println!("UTXOs:        {} -> {}", utxo_count, merged.utxos.len());
+    println!("Transactions: {}", merged.transactions.len());
+    println!("Fee:          {}", Amount::from_sompi(merged.fee()));
+    if merged.transactions.is_empty() {
+        println!("Nothing to compound.");
+        return Ok(());
+    }
 
+    if !confirm_submission(options, "Submit these transactions? [y/N] ")? {
+        return Ok(());
+    }
+    for tx in &merged.transactions {
+        let transaction_id = node
+            .submit(&tx.tx)
+            .await
+            .map_err(|e| WalletError::Network(format!("Failed to submit transaction: {}", e)))?;
+        println!("Transaction submitted: {}", transaction_id);
+    }
+    Ok(())
+}
+
+async fn sweep_key(
+    wallet: &KaspaWallet,
+    source: &KaspaWallet,
+    options: &SendOptions,
+    node_args: &NodeArgs,
+) -> Result<(), WalletError> {
+    let node = node_args
+        .connect(&NetworkConfig::from_name(wallet.get_network_name())?)
+        .await?;
+    let result = sweep_with_node(node.as_ref(), wallet, source, options).await;
+    let _ = node.disconnect().await;
+    result
+}
+
+async fn sweep_with_node(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    source: &KaspaWallet,
+    options: &SendOptions,
+) -> Result<(), WalletError> {
+    let fee_rate = send::resolve_fee_rate(node, options.fee_rate).await?;
+    let sweep = sweep::build_sweep(node, source, &wallet.address(), fee_rate).await?;
+
+    println!("Sweep Summary:");
+    println!("==================");
+    for address in &sweep.sources {
+        println!("From:         {}", address);
+    }
+    println!("To:           {}", sweep.destination);
+    println!("Inputs:       {}", sweep.input_count());
+    println!("Amount:       {}", Amount::from_sompi(sweep.amount()));
+    println!("Fee:          {}", Amount::from_sompi(sweep.fee()));
+    println!("Transactions: {}", sweep.transactions.len());
+    if sweep.left_behind_count > 0 {
+        println!(
+            "Left behind:  {} in {} UTXOs too small to pay their fee",
+            Amount::from_sompi(sweep.left_behind),
+            sweep.left_behind_count
+        );
+    }
+
+    if !confirm_submission(options, "Sweep these funds? [y/N] ")? {
+        return Ok(());
+    }
+    for transaction_id in sweep::submit_sweep(node, &sweep).await? {
+        println!("Transaction submitted: {}", transaction_id);
+    }
     Ok(())
 }
 
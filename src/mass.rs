@@ -0,0 +1,390 @@
+use crate::error::{WalletError, WalletResult};
+use crate::transaction::{Utxo, DEFAULT_SEQUENCE, DEFAULT_SIG_OP_COUNT, TX_VERSION};
+use kaspa_consensus_core::subnets::SUBNETWORK_ID_NATIVE;
+use kaspa_consensus_core::tx::{
+    ScriptPublicKey, SignableTransaction, Transaction, TransactionInput, TransactionOutput,
+};
+
+// Consensus mass parameters, identical on every network.
+pub const MASS_PER_TX_BYTE: u64 = 1;
+pub const MASS_PER_SCRIPT_PUB_KEY_BYTE: u64 = 10;
+pub const MASS_PER_SIG_OP: u64 = 1000;
+/// KIP-9 storage mass parameter `C`.
+pub const STORAGE_MASS_PARAMETER: u64 = 100_000_000 * 10_000;
+
+/// Mass above which kaspad refuses to relay a transaction.
+pub const MAXIMUM_STANDARD_TRANSACTION_MASS: u64 = 100_000;
+
+/// Length of a P2PK signature script: OP_DATA_65, a 64-byte signature and
+/// the sighash type. Schnorr and ECDSA inputs are the same size.
+pub const SIGNATURE_SCRIPT_LEN: usize = 66;
+
+const HASH_SIZE: u64 = 32;
+const SUBNETWORK_ID_SIZE: u64 = 20;
+
+// Bytes a UTXO entry occupies besides its script, and the unit by which
+// larger entries count as several UTXOs in the storage mass formula.
+const UTXO_CONST_STORAGE: u64 = 63;
+const UTXO_UNIT_SIZE: u64 = 100;
+
+/// Size of `tx` as consensus counts it for mass: version, input and output
+/// counts, lock time, subnetwork id, gas, payload hash and payload length.
+pub fn transaction_serialized_size(tx: &Transaction) -> u64 {
+    let inputs: u64 = tx
+        .inputs
+        .iter()
+        .map(|input| HASH_SIZE + 4 + 8 + input.signature_script.len() as u64 + 8)
+        .sum();
+    let outputs: u64 = tx
+        .outputs
+        .iter()
+        .map(|output| 8 + 2 + 8 + output.script_public_key.script().len() as u64)
+        .sum();
+
+    let header = 2 + 8 + 8 + 8 + SUBNETWORK_ID_SIZE + 8 + HASH_SIZE + 8;
+    header + inputs + outputs + tx.payload.len() as u64
+}
+
+/// Compute mass: serialized size, script public key bytes and signature
+/// operations, each weighted by its consensus parameter.
+pub fn compute_mass(tx: &Transaction) -> u64 {
+    if tx.is_coinbase() {
+        return 0;
+    }
+    let script_public_key_size: u64 = tx
+        .outputs
+        .iter()
+        .map(|output| 2 + output.script_public_key.script().len() as u64)
+        .sum();
+    let sig_ops: u64 = tx
+        .inputs
+        .iter()
+        .map(|input| input.sig_op_count as u64)
+        .sum();
+
+    transaction_serialized_size(tx) * MASS_PER_TX_BYTE
+        + script_public_key_size * MASS_PER_SCRIPT_PUB_KEY_BYTE
+        + sig_ops * MASS_PER_SIG_OP
+}
+
+struct UtxoCell {
+    plurality: u64,
+    amount: u64,
+}
+
+impl UtxoCell {
+    fn new(script: &ScriptPublicKey, amount: u64) -> Self {
+        let plurality =
+            (UTXO_CONST_STORAGE + script.script().len() as u64).div_ceil(UTXO_UNIT_SIZE);
+        Self { plurality, amount }
+    }
+
+    /// `plurality * C / (amount / plurality)`, or `None` for an empty output.
+    fn harmonic(&self) -> Option<u64> {
+        let per_unit = self.amount / self.plurality;
+        if per_unit == 0 {
+            return None;
+        }
+        self.plurality
+            .checked_mul(STORAGE_MASS_PARAMETER / per_unit)
+    }
+}
+
+/// KIP-9 storage mass. Returns `None` when the mass cannot be computed,
+/// which consensus treats as invalid (an output too small to divide by).
+fn calc_storage_mass(inputs: &[UtxoCell], outputs: &[UtxoCell]) -> Option<u64> {
+    let outs_plurality: u64 = outputs.iter().map(|cell| cell.plurality).sum();
+    let ins_plurality: u64 = inputs.iter().map(|cell| cell.plurality).sum();
+
+    let mut harmonic_outs = 0u64;
+    for output in outputs {
+        harmonic_outs = harmonic_outs.checked_add(output.harmonic()?)?;
+    }
+
+    // The relaxed formula applies when |O| = 1 or |O| <= |I| <= 2.
+    if outs_plurality == 1 || ins_plurality == 1 || (outs_plurality == 2 && ins_plurality == 2) {
+        let harmonic_ins = inputs
+            .iter()
+            .map(|input| input.harmonic().unwrap_or(0))
+            .fold(0u64, |total, mass| total.saturating_add(mass));
+        return Some(harmonic_outs.saturating_sub(harmonic_ins));
+    }
+
+    let sum_ins: u64 = inputs.iter().map(|input| input.amount).sum();
+    let mean_ins = sum_ins / ins_plurality.max(1);
+    if mean_ins == 0 {
+        return Some(harmonic_outs);
+    }
+    let arithmetic_ins = ins_plurality.saturating_mul(STORAGE_MASS_PARAMETER / mean_ins);
+    Some(harmonic_outs.saturating_sub(arithmetic_ins))
+}
+
+/// Storage mass of `tx`, which needs every input's UTXO entry.
+pub fn storage_mass(tx: &SignableTransaction) -> WalletResult<u64> {
+    if tx.tx.is_coinbase() {
+        return Ok(0);
+    }
+    let mut inputs = Vec::with_capacity(tx.entries.len());
+    for (index, entry) in tx.entries.iter().enumerate() {
+        let entry = entry.as_ref().ok_or_else(|| {
+            WalletError::Transaction(format!("Input {} has no UTXO entry", index))
+        })?;
+        inputs.push(UtxoCell::new(&entry.script_public_key, entry.amount));
+    }
+    calc_storage_mass(&inputs, &output_cells(&tx.tx.outputs)).ok_or_else(|| {
+        WalletError::Transaction("Transaction storage mass cannot be computed".to_string())
+    })
+}
+
+/// The mass consensus charges `tx`: the larger of compute and storage mass.
+pub fn transaction_mass(tx: &SignableTransaction) -> WalletResult<u64> {
+    Ok(compute_mass(&tx.tx).max(storage_mass(tx)?))
+}
+
+pub fn check_standard_mass(mass: u64) -> WalletResult<()> {
+    if mass > MAXIMUM_STANDARD_TRANSACTION_MASS {
+        return Err(WalletError::MassLimitExceeded {
+            mass,
+            limit: MAXIMUM_STANDARD_TRANSACTION_MASS,
+        });
+    }
+    Ok(())
+}
+
+/// Fee in sompi for `mass` grams at `fee_rate` sompi per gram.
+pub fn fee_for_mass(mass: u64, fee_rate: u64) -> u64 {
+    mass.saturating_mul(fee_rate)
+}
+
+/// Mass of a transaction spending `inputs` into `outputs` once every input
+/// carries a P2PK signature. An incomputable storage mass counts as
+/// `u64::MAX` so such transactions are never considered affordable.
+pub fn estimate_mass(inputs: &[Utxo], outputs: &[TransactionOutput]) -> u64 {
+    let input_cells: Vec<_> = inputs
+        .iter()
+        .map(|utxo| UtxoCell::new(&utxo.entry.script_public_key, utxo.entry.amount))
+        .collect();
+    let storage = calc_storage_mass(&input_cells, &output_cells(outputs)).unwrap_or(u64::MAX);
+    estimate_compute_mass(inputs, outputs).max(storage)
+}
+
+/// Compute mass alone, which grows with the number of inputs and outputs
+/// but not with their values.
+pub fn estimate_compute_mass(inputs: &[Utxo], outputs: &[TransactionOutput]) -> u64 {
+    let tx = Transaction::new(
+        TX_VERSION,
+        inputs
+            .iter()
+            .map(|utxo| {
+                TransactionInput::new(
+                    utxo.outpoint,
+                    vec![0; SIGNATURE_SCRIPT_LEN],
+                    DEFAULT_SEQUENCE,
+                    DEFAULT_SIG_OP_COUNT,
+                )
+            })
+            .collect(),
+        outputs.to_vec(),
+        0,
+        SUBNETWORK_ID_NATIVE,
+        0,
+        vec![],
+    );
+    compute_mass(&tx)
+}
+
+/// Fee for spending `inputs` into `outputs`, plus a change output to
+/// `change_script` receiving whatever is left over when one is given.
+///
+/// The change value sets the storage mass, which sets the fee, which sets
+/// the change, so this iterates until the fee stops growing; paying the
+/// result leaves a change output whose mass it covers. Change too small to
+/// pay for itself or to keep the transaction standard costs `u64::MAX`, so
+/// coin selection leaves it to the fee instead.
+pub fn estimate_fee_with_change(
+    inputs: &[Utxo],
+    outputs: &[TransactionOutput],
+    change_script: Option<&ScriptPublicKey>,
+    fee_rate: u64,
+) -> u64 {
+    let Some(change_script) = change_script else {
+        return fee_for_mass(estimate_mass(inputs, outputs), fee_rate);
+    };
+
+    let input_total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+    let spent: u64 = outputs.iter().map(|output| output.value).sum();
+    let mut with_change = outputs.to_vec();
+    with_change.push(TransactionOutput::new(0, change_script.clone()));
+
+    let mut fee = 0;
+    for _ in 0..32 {
+        let change = input_total.saturating_sub(spent.saturating_add(fee));
+        if change == 0 {
+            return u64::MAX;
+        }
+        with_change.last_mut().unwrap().value = change;
+        let mass = estimate_mass(inputs, &with_change);
+        if mass > MAXIMUM_STANDARD_TRANSACTION_MASS {
+            return u64::MAX;
+        }
+        let next = fee_for_mass(mass, fee_rate);
+        if next <= fee {
+            return fee;
+        }
+        fee = next;
+    }
+    u64::MAX
+}
+
+fn output_cells(outputs: &[TransactionOutput]) -> Vec<UtxoCell> {
+    outputs
+        .iter()
+        .map(|output| UtxoCell::new(&output.script_public_key, output.value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::test_util::{test_wallet, utxo};
+    use crate::wallet::KaspaWallet;
+
+    fn utxos(wallet: &KaspaWallet, amounts: &[u64]) -> Vec<Utxo> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| utxo(index as u32, *amount, wallet.script_public_key()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_mass_of_signed_p2pk() {
+        let wallet = test_wallet(0x42);
+        let inputs = utxos(&wallet, &[10 * SOMPI_PER_KAS, 10 * SOMPI_PER_KAS]);
+        let tx = wallet
+            .create_transaction(
+                inputs.clone(),
+                vec![
+                    (wallet.get_address(), 15 * SOMPI_PER_KAS),
+                    (wallet.get_address(), 4 * SOMPI_PER_KAS),
+                ],
+                0,
+            )
+            .unwrap();
+
+        // 94 header bytes, 118 per input and 52 per 34-byte output, plus
+        // 36 script bytes per output at 10 and one sig op per input.
+        let size = 94 + 2 * 118 + 2 * 52;
+        assert_eq!(transaction_serialized_size(&tx.tx), size);
+        assert_eq!(compute_mass(&tx.tx), size + 2 * 36 * 10 + 2 * 1000);
+        assert_eq!(
+            estimate_mass(&inputs, &tx.tx.outputs),
+            transaction_mass(&tx).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_storage_mass() {
+        let wallet = test_wallet(0x42);
+        let script = wallet.script_public_key();
+        let output = |value| UtxoCell::new(&script, value);
+
+        // One input, one output: C/out - C/in.
+        assert_eq!(
+            calc_storage_mass(&[output(100 * SOMPI_PER_KAS)], &[output(SOMPI_PER_KAS)]),
+            Some(10_000 - 100)
+        );
+        // Splitting into small outputs is what storage mass prices.
+        assert_eq!(
+            calc_storage_mass(
+                &[output(SOMPI_PER_KAS)],
+                &[output(SOMPI_PER_KAS / 100), output(SOMPI_PER_KAS / 2)]
+            ),
+            Some(1_000_000 + 20_000 - 10_000)
+        );
+        // Three inputs and outputs use the arithmetic mean of the inputs.
+        assert_eq!(
+            calc_storage_mass(
+                &[
+                    output(SOMPI_PER_KAS),
+                    output(2 * SOMPI_PER_KAS),
+                    output(3 * SOMPI_PER_KAS)
+                ],
+                &[
+                    output(SOMPI_PER_KAS),
+                    output(2 * SOMPI_PER_KAS),
+                    output(2 * SOMPI_PER_KAS)
+                ]
+            ),
+            Some(10_000 + 5_000 + 5_000 - 3 * 5_000)
+        );
+        // Consolidation never costs storage mass.
+        assert_eq!(
+            calc_storage_mass(
+                &[output(SOMPI_PER_KAS), output(SOMPI_PER_KAS)],
+                &[output(2 * SOMPI_PER_KAS)]
+            ),
+            Some(0)
+        );
+        assert_eq!(
+            calc_storage_mass(&[output(SOMPI_PER_KAS)], &[output(0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_standard_mass_limit() {
+        assert!(check_standard_mass(MAXIMUM_STANDARD_TRANSACTION_MASS).is_ok());
+        assert!(matches!(
+            check_standard_mass(MAXIMUM_STANDARD_TRANSACTION_MASS + 1),
+            Err(WalletError::MassLimitExceeded { .. })
+        ));
+
+        // A dust-sized output alone pushes storage mass past the limit.
+        let wallet = test_wallet(0x42);
+        let inputs = utxos(&wallet, &[SOMPI_PER_KAS]);
+        let outputs = [TransactionOutput::new(1_000, wallet.script_public_key())];
+        assert!(estimate_mass(&inputs, &outputs) > MAXIMUM_STANDARD_TRANSACTION_MASS);
+    }
+
+    #[test]
+    fn test_fee_with_change_covers_final_mass() {
+        let wallet = test_wallet(0x42);
+        let inputs = utxos(&wallet, &[2 * SOMPI_PER_KAS]);
+        let outputs = [TransactionOutput::new(
+            SOMPI_PER_KAS,
+            wallet.script_public_key(),
+        )];
+        let fee_rate = 3;
+
+        let fee = estimate_fee_with_change(
+            &inputs,
+            &outputs,
+            Some(&wallet.script_public_key()),
+            fee_rate,
+        );
+        let change = SOMPI_PER_KAS - fee;
+        let final_outputs = [
+            outputs[0].clone(),
+            TransactionOutput::new(change, wallet.script_public_key()),
+        ];
+        assert!(fee >= fee_for_mass(estimate_mass(&inputs, &final_outputs), fee_rate));
+        assert_eq!(
+            estimate_fee_with_change(&inputs, &outputs, None, fee_rate),
+            fee_for_mass(estimate_mass(&inputs, &outputs), fee_rate)
+        );
+
+        // 5_000 sompi of change would carry a storage mass of 2 * 10^8.
+        let inputs = utxos(&wallet, &[SOMPI_PER_KAS + 5_000]);
+        assert_eq!(
+            estimate_fee_with_change(
+                &inputs,
+                &outputs,
+                Some(&wallet.script_public_key()),
+                fee_rate
+            ),
+            u64::MAX
+        );
+    }
+}
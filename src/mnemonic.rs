@@ -0,0 +1,107 @@
+use crate::bip32::{kaspa_account_path, ExtendedPrivateKey};
+use crate::error::{WalletError, WalletResult};
+use bip39::Mnemonic;
+use rand::RngCore;
+use secp256k1::SecretKey;
+use zeroize::Zeroizing;
+
+pub fn generate_mnemonic(word_count: usize) -> WalletResult<Mnemonic> {
+    let entropy_len = match word_count {
+        12 => 16,
+        24 => 32,
+        _ => {
+            return Err(WalletError::InvalidParameters(format!(
+                "Unsupported mnemonic length: {} (expected 12 or 24 words)",
+                word_count
+            )))
+        }
+    };
+
+    let mut entropy = Zeroizing::new(vec![0u8; entropy_len]);
+    rand::rngs::OsRng.fill_bytes(&mut entropy);
+    Ok(Mnemonic::from_entropy(&entropy)?)
+}
+
+pub fn parse_mnemonic(phrase: &str) -> WalletResult<Mnemonic> {
+    let normalized = Zeroizing::new(phrase.split_whitespace().collect::<Vec<_>>().join(" "));
+    let mnemonic = Mnemonic::parse(Zeroizing::new(normalized.to_lowercase()).as_str())?;
+
+    match mnemonic.word_count() {
+        12 | 24 => Ok(mnemonic),
+        count => Err(WalletError::InvalidMnemonic(format!(
+            "Unsupported mnemonic length: {} words (expected 12 or 24)",
+            count
+        ))),
+    }
+}
+
+/// Derives the master key for `mnemonic` as used by other Kaspa wallets:
+/// the BIP-39 seed feeds BIP-32 on the `m/44'/111111'` coin type.
+pub fn master_key_from_mnemonic(
+    mnemonic: &Mnemonic,
+    passphrase: &str,
+) -> WalletResult<ExtendedPrivateKey> {
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+    ExtendedPrivateKey::new_master(&seed[..])
+}
+
+/// Returns the key of the first receive address of account 0.
+pub fn secret_key_from_mnemonic(mnemonic: &Mnemonic, passphrase: &str) -> WalletResult<SecretKey> {
+    let master = master_key_from_mnemonic(mnemonic, passphrase)?;
+    let path = format!("{}/0/0", kaspa_account_path(0));
+    Ok(*master.derive_path(&path)?.secret_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon \
+                                 abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_generate_word_counts() {
+        assert_eq!(generate_mnemonic(12).unwrap().word_count(), 12);
+        assert_eq!(generate_mnemonic(24).unwrap().word_count(), 24);
+        assert!(generate_mnemonic(15).is_err());
+    }
+
+    #[test]
+    fn test_seed_vector() {
+        // BIP-39 reference vector for all-zero entropy with passphrase "TREZOR".
+        let mnemonic = parse_mnemonic(ABANDON_ABOUT).unwrap();
+        assert_eq!(
+            hex::encode(mnemonic.to_seed("TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn test_parse_normalizes_whitespace_and_case() {
+        let messy = "  Abandon abandon\tabandon abandon abandon abandon\n\
+                     abandon abandon abandon abandon abandon ABOUT ";
+        assert_eq!(
+            parse_mnemonic(messy).unwrap().to_string(),
+            parse_mnemonic(ABANDON_ABOUT).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_invalid_checksum_and_word() {
+        let bad_checksum = ABANDON_ABOUT.replace("about", "abandon");
+        assert!(parse_mnemonic(&bad_checksum).is_err());
+
+        let bad_word = ABANDON_ABOUT.replace("about", "kaspa");
+        assert!(parse_mnemonic(&bad_word).is_err());
+    }
+
+    #[test]
+    fn test_restore_is_deterministic() {
+        let mnemonic = generate_mnemonic(24).unwrap();
+        let restored = parse_mnemonic(&mnemonic.to_string()).unwrap();
+
+        let key = secret_key_from_mnemonic(&mnemonic, "").unwrap();
+        assert_eq!(key, secret_key_from_mnemonic(&restored, "").unwrap());
+        assert_ne!(key, secret_key_from_mnemonic(&restored, "extra").unwrap());
+    }
+}
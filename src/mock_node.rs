@@ -0,0 +1,405 @@
+use crate::node::NodeApi;
+use crate::rpc::{FeeBucket, FeeEstimateResponse, MempoolEntriesResponse, RpcError};
+use crate::transaction::{LockTime, Utxo};
+use async_trait::async_trait;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::constants::MAX_TX_IN_SEQUENCE_NUM;
+use kaspa_consensus_core::hashing::sighash::SigHashReusedValuesUnsync;
+use kaspa_consensus_core::tx::{
+    SignableTransaction, Transaction, TransactionId, TransactionOutpoint, UtxoEntry,
+    VerifiableTransaction,
+};
+use kaspa_txscript::caches::Cache;
+use kaspa_txscript::{pay_to_address_script, TxScriptEngine};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeCall {
+    Balance,
+    Utxos,
+    MempoolEntries,
+    Submit,
+    SignatureScripts,
+    FeeEstimate,
+    VirtualDaaScore,
+    PastMedianTime,
+}
+
+/// In-memory node for tests. It keeps a UTXO set that submitted
+/// transactions spend from and add to, and can be told to fail particular
+/// calls or to behave as if it were unreachable.
+pub struct MockNode {
+    state: Mutex<MockState>,
+}
+
+struct MockState {
+    utxos: Vec<Utxo>,
+    virtual_daa_score: u64,
+    past_median_time: u64,
+    feerate: f64,
+    next_funding_id: u64,
+    failures: HashMap<NodeCall, Vec<RpcError>>,
+    offline: bool,
+    submitted: Vec<Transaction>,
+}
+
+impl Default for MockNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockNode {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState {
+                utxos: Vec::new(),
+                virtual_daa_score: 1_000,
+                past_median_time: 1_700_000_000_000,
+                feerate: 1.0,
+                next_funding_id: 1,
+                failures: HashMap::new(),
+                offline: false,
+                submitted: Vec::new(),
+            }),
+        }
+    }
+
+    /// Adds a UTXO of `amount` sompi paying to `address`.
+    pub fn with_utxo(self, address: &Address, amount: u64) -> Self {
+        self.add_utxo(address, amount);
+        self
+    }
+
+    pub fn with_utxos(self, utxos: Vec<Utxo>) -> Self {
+        self.lock().utxos.extend(utxos);
+        self
+    }
+
+    pub fn with_virtual_daa_score(self, score: u64) -> Self {
+        self.lock().virtual_daa_score = score;
+        self
+    }
+
+    pub fn with_past_median_time(self, time_ms: u64) -> Self {
+        self.lock().past_median_time = time_ms;
+        self
+    }
+
+    /// Fee rate in sompi per gram reported by every fee estimate bucket.
+    pub fn with_feerate(self, feerate: f64) -> Self {
+        self.lock().feerate = feerate;
+        self
+    }
+
+    /// Adds a UTXO funded by a synthetic transaction and returns it.
+    pub fn add_utxo(&self, address: &Address, amount: u64) -> Utxo {
+        self.add_funding(address, amount, false)
+    }
+
+    /// Adds a coinbase output created at the current virtual DAA score, as
+    /// a block reward paid to `address` would be.
+    pub fn add_coinbase_utxo(&self, address: &Address, amount: u64) -> Utxo {
+        self.add_funding(address, amount, true)
+    }
+
+    fn add_funding(&self, address: &Address, amount: u64, is_coinbase: bool) -> Utxo {
+        let mut state = self.lock();
+        let funding_id = TransactionId::from_u64_word(state.next_funding_id);
+        state.next_funding_id += 1;
+
+        let utxo = Utxo::new(
+            TransactionOutpoint::new(funding_id, 0),
+            UtxoEntry::new(
+                amount,
+                pay_to_address_script(address),
+                state.virtual_daa_score,
+                is_coinbase,
+            ),
+        );
+        state.utxos.push(utxo.clone());
+        utxo
+    }
+
+    /// Makes the next `call` fail with `error`. Queued failures for the
+    /// same call are returned in order.
+    pub fn fail_next(&self, call: NodeCall, error: RpcError) {
+        self.lock().failures.entry(call).or_default().push(error);
+    }
+
+    /// While offline every call fails with a connection error.
+    pub fn set_offline(&self, offline: bool) {
+        self.lock().offline = offline;
+    }
+
+    pub fn set_virtual_daa_score(&self, score: u64) {
+        self.lock().virtual_daa_score = score;
+    }
+
+    pub fn set_past_median_time(&self, time_ms: u64) {
+        self.lock().past_median_time = time_ms;
+    }
+
+    /// Transactions accepted by `submit`, oldest first.
+    pub fn submitted(&self) -> Vec<Transaction> {
+        self.lock().submitted.clone()
+    }
+
+    pub fn utxo_set(&self) -> Vec<Utxo> {
+        self.lock().utxos.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock node state poisoned")
+    }
+
+    fn begin(&self, call: NodeCall) -> Result<std::sync::MutexGuard<'_, MockState>, RpcError> {
+        let mut state = self.lock();
+        if state.offline {
+            return Err(RpcError::Connection("mock node is offline".to_string()));
+        }
+        if let Some(queue) = state.failures.get_mut(&call) {
+            if !queue.is_empty() {
+                return Err(queue.remove(0));
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl MockState {
+    fn utxos_for(&self, address: &Address) -> impl Iterator<Item = &Utxo> {
+        let script = pay_to_address_script(address);
+        self.utxos
+            .iter()
+            .filter(move |utxo| utxo.entry.script_public_key == script)
+    }
+}
+
+#[async_trait]
+impl NodeApi for MockNode {
+    async fn balance(&self, address: &Address) -> Result<u64, RpcError> {
+        let state = self.begin(NodeCall::Balance)?;
+        Ok(state.utxos_for(address).map(|utxo| utxo.entry.amount).sum())
+    }
+
+    async fn utxos(&self, addresses: &[Address]) -> Result<Vec<Utxo>, RpcError> {
+        let state = self.begin(NodeCall::Utxos)?;
+        Ok(addresses
+            .iter()
+            .flat_map(|address| state.utxos_for(address).cloned())
+            .collect())
+    }
+
+    /// Submitted transactions are confirmed at once, so the mempool is
+    /// always empty.
+    async fn mempool_entries(
+        &self,
+        _address: &Address,
+    ) -> Result<MempoolEntriesResponse, RpcError> {
+        self.begin(NodeCall::MempoolEntries)?;
+        Ok(MempoolEntriesResponse::default())
+    }
+
+    /// Accepts `tx` if every input spends a known UTXO and its lock time
+    /// has passed, then replaces the spent entries with the transaction's
+    /// outputs. Scripts are not run; see `execute_scripts`.
+    async fn submit(&self, tx: &Transaction) -> Result<TransactionId, RpcError> {
+        let mut state = self.begin(NodeCall::Submit)?;
+        let finalized = tx
+            .inputs
+            .iter()
+            .all(|input| input.sequence == MAX_TX_IN_SEQUENCE_NUM);
+        let lock_time = LockTime::from_value(tx.lock_time);
+        if tx.lock_time != 0
+            && !finalized
+            && !lock_time.is_reached(state.virtual_daa_score, state.past_median_time)
+        {
+            return Err(RpcError::Rpc(format!(
+                "transaction {} is not finalized until {}",
+                tx.id(),
+                lock_time
+            )));
+        }
+        for input in &tx.inputs {
+            if !state
+                .utxos
+                .iter()
+                .any(|utxo| utxo.outpoint == input.previous_outpoint)
+            {
+                return Err(RpcError::Rpc(format!(
+                    "transaction {} spends unknown or already spent outpoint {}",
+                    tx.id(),
+                    input.previous_outpoint
+                )));
+            }
+        }
+
+        state.utxos.retain(|utxo| {
+            !tx.inputs
+                .iter()
+                .any(|input| input.previous_outpoint == utxo.outpoint)
+        });
+        let daa_score = state.virtual_daa_score;
+        for (index, output) in tx.outputs.iter().enumerate() {
+            state.utxos.push(Utxo::new(
+                TransactionOutpoint::new(tx.id(), index as u32),
+                UtxoEntry::new(
+                    output.value,
+                    output.script_public_key.clone(),
+                    daa_score,
+                    false,
+                ),
+            ));
+        }
+        state.submitted.push(tx.clone());
+        Ok(tx.id())
+    }
+
+    /// Finds `id` among the submitted transactions.
+    async fn input_signature_scripts(&self, id: &TransactionId) -> Result<Vec<Vec<u8>>, RpcError> {
+        let state = self.begin(NodeCall::SignatureScripts)?;
+        let tx = state
+            .submitted
+            .iter()
+            .find(|tx| tx.id() == *id)
+            .ok_or_else(|| RpcError::Rpc(format!("transaction {} not found", id)))?;
+        Ok(tx
+            .inputs
+            .iter()
+            .map(|input| input.signature_script.clone())
+            .collect())
+    }
+
+    async fn fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        let state = self.begin(NodeCall::FeeEstimate)?;
+        let bucket = |estimated_seconds| FeeBucket {
+            feerate: state.feerate,
+            estimated_seconds,
+        };
+        Ok(FeeEstimateResponse {
+            priority_bucket: bucket(1.0),
+            normal_buckets: vec![bucket(10.0)],
+            low_buckets: vec![bucket(60.0)],
+        })
+    }
+
+    async fn virtual_daa_score(&self) -> Result<u64, RpcError> {
+        Ok(self.begin(NodeCall::VirtualDaaScore)?.virtual_daa_score)
+    }
+
+    async fn past_median_time(&self) -> Result<u64, RpcError> {
+        Ok(self.begin(NodeCall::PastMedianTime)?.past_median_time)
+    }
+}
+
+/// Runs every input's scripts through the consensus script engine, as a
+/// node would before accepting `tx`.
+pub fn execute_scripts(tx: &SignableTransaction) -> Result<(), String> {
+    let cache = Cache::new(10_000);
+    let reused_values = SigHashReusedValuesUnsync::new();
+    let verifiable = tx.as_verifiable();
+    for (index, (input, entry)) in verifiable.populated_inputs().enumerate() {
+        TxScriptEngine::from_transaction_input(
+            &verifiable,
+            input,
+            index,
+            entry,
+            &reused_values,
+            &cache,
+            true,
+        )
+        .execute()
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_wallet;
+    use crate::transaction::build_transaction;
+
+    #[tokio::test]
+    async fn test_balance_and_utxos_by_address() {
+        let alice = test_wallet(0x42).address();
+        let bob = test_wallet(0x43).address();
+        let node = MockNode::new()
+            .with_utxo(&alice, 1_000)
+            .with_utxo(&alice, 2_500)
+            .with_utxo(&bob, 7);
+
+        assert_eq!(node.balance(&alice).await.unwrap(), 3_500);
+        assert_eq!(node.balance(&bob).await.unwrap(), 7);
+        assert_eq!(node.utxos(&[alice.clone()]).await.unwrap().len(), 2);
+        assert_eq!(node.utxos(&[alice, bob]).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_submit_spends_and_creates_utxos() {
+        let alice = test_wallet(0x42).address();
+        let bob = test_wallet(0x43).address();
+        let node = MockNode::new().with_utxo(&alice, 10_000);
+        let funding = node.utxos(&[alice.clone()]).await.unwrap().remove(0);
+
+        let tx = build_transaction(
+            vec![funding.outpoint],
+            &[(bob.clone(), 6_000), (alice.clone(), 3_000)],
+            0,
+        )
+        .unwrap();
+        assert_eq!(node.submit(&tx).await.unwrap(), tx.id());
+        assert_eq!(node.balance(&alice).await.unwrap(), 3_000);
+        assert_eq!(node.balance(&bob).await.unwrap(), 6_000);
+        assert_eq!(node.submitted().len(), 1);
+
+        // The funding output is gone, so a replay is a double spend.
+        assert!(matches!(node.submit(&tx).await, Err(RpcError::Rpc(_))));
+    }
+
+    #[tokio::test]
+    async fn test_input_signature_scripts_of_submitted() {
+        let alice = test_wallet(0x42).address();
+        let node = MockNode::new().with_utxo(&alice, 10_000);
+        let funding = node.utxos(&[alice.clone()]).await.unwrap().remove(0);
+        let mut tx = build_transaction(vec![funding.outpoint], &[(alice, 9_000)], 0).unwrap();
+        tx.inputs[0].signature_script = vec![0x01, 0x02];
+
+        assert!(matches!(
+            node.input_signature_scripts(&tx.id()).await,
+            Err(RpcError::Rpc(_))
+        ));
+        node.submit(&tx).await.unwrap();
+        assert_eq!(
+            node.input_signature_scripts(&tx.id()).await.unwrap(),
+            vec![vec![0x01, 0x02]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scripted_failures() {
+        let alice = test_wallet(0x42).address();
+        let node = MockNode::new()
+            .with_utxo(&alice, 1)
+            .with_virtual_daa_score(5)
+            .with_feerate(2.0);
+
+        node.fail_next(NodeCall::Balance, RpcError::Rpc("boom".to_string()));
+        assert!(matches!(node.balance(&alice).await, Err(RpcError::Rpc(_))));
+        assert_eq!(node.balance(&alice).await.unwrap(), 1);
+
+        node.set_offline(true);
+        assert!(matches!(
+            node.virtual_daa_score().await,
+            Err(RpcError::Connection(_))
+        ));
+        node.set_offline(false);
+        assert_eq!(node.virtual_daa_score().await.unwrap(), 5);
+        assert_eq!(
+            node.fee_estimate().await.unwrap().priority_bucket.feerate,
+            2.0
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use crate::address::{address_from_redeem_script, SignatureScheme};
+use crate::bip32::ExtendedPublicKey;
+use crate::coin_selection;
+use crate::error::{WalletError, WalletResult};
+use crate::hd::RECEIVE_CHAIN;
+use crate::network::NetworkConfig;
+use crate::node::NodeApi;
+use crate::p2sh::{self, ScriptSpend, UnlockItem};
+use crate::pskt::Pskt;
+use kaspa_addresses::Address;
+use kaspa_txscript::opcodes::codes::{
+    Op1, Op16, OpCheckMultiSig, OpCheckMultiSigECDSA, OpData1, OpData32, OpData33,
+};
+use kaspa_txscript::{multisig_redeem_script, multisig_redeem_script_ecdsa};
+use secp256k1::PublicKey;
+
+/// Most keys OP_CHECKMULTISIG accepts.
+pub const MAX_COSIGNERS: usize = 20;
+
+/// An M-of-N account shared by cosigners who each hold one BIP-44 account
+/// and share its extended public key. Address `i` is the P2SH of a
+/// multisig redeem script over every cosigner's `/0/i` key.
+pub struct MultisigAccount {
+    xpubs: Vec<ExtendedPublicKey>,
+    threshold: usize,
+    network_config: NetworkConfig,
+    scheme: SignatureScheme,
+}
+
+impl MultisigAccount {
+    pub fn new(
+        xpubs: Vec<ExtendedPublicKey>,
+        threshold: usize,
+        network_config: NetworkConfig,
+    ) -> WalletResult<Self> {
+        if xpubs.is_empty() || xpubs.len() > MAX_COSIGNERS {
+            return Err(WalletError::InvalidParameters(format!(
+                "A multisig account needs between 1 and {} cosigners, got {}",
+                MAX_COSIGNERS,
+                xpubs.len()
+            )));
+        }
+        if threshold == 0 || threshold > xpubs.len() {
+            return Err(WalletError::InvalidParameters(format!(
+                "Threshold must be between 1 and {}, got {}",
+                xpubs.len(),
+                threshold
+            )));
+        }
+        if (1..xpubs.len()).any(|i| xpubs[..i].contains(&xpubs[i])) {
+            return Err(WalletError::InvalidParameters(
+                "Every cosigner must have a distinct key".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            xpubs,
+            threshold,
+            network_config,
+            scheme: SignatureScheme::default(),
+        })
+    }
+
+    pub fn with_signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn cosigners(&self) -> usize {
+        self.xpubs.len()
+    }
+
+    /// The cosigners' keys for address `index`, sorted by their script
+    /// encoding so every cosigner builds the same script whatever order
+    /// the xpubs were given in.
+    pub fn public_keys(&self, index: u32) -> WalletResult<Vec<PublicKey>> {
+        let mut keys = self
+            .xpubs
+            .iter()
+            .map(|xpub| {
+                Ok(xpub
+                    .derive_child(RECEIVE_CHAIN)?
+                    .derive_child(index)?
+                    .public_key())
+            })
+            .collect::<WalletResult<Vec<_>>>()?;
+        keys.sort_by_key(|key| script_key(key, self.scheme));
+        Ok(keys)
+    }
+
+    pub fn redeem_script(&self, index: u32) -> WalletResult<Vec<u8>> {
+        let keys = self.public_keys(index)?;
+        let script = match self.scheme {
+            SignatureScheme::Schnorr => multisig_redeem_script(
+                keys.iter().map(|key| key.x_only_public_key().0.serialize()),
+                self.threshold,
+            ),
+            SignatureScheme::Ecdsa => {
+                multisig_redeem_script_ecdsa(keys.iter().map(|key| key.serialize()), self.threshold)
+            }
+        };
+        script.map_err(|e| WalletError::Transaction(format!("Multisig script error: {}", e)))
+    }
+
+    pub fn address(&self, index: u32) -> WalletResult<Address> {
+        Ok(address_from_redeem_script(
+            &self.redeem_script(index)?,
+            self.network_config.address_prefix(),
+        ))
+    }
+
+    /// Builds a PSKT paying `outputs` from address `index`, with change
+    /// back to it, for the cosigners to sign. Each input carries the
+    /// redeem script so signers can tell which key is theirs.
+    pub async fn build_spend(
+        &self,
+        node: &dyn NodeApi,
+        index: u32,
+        outputs: &[(Address, u64)],
+        fee_rate: u64,
+    ) -> WalletResult<Pskt> {
+        let address = self.address(index)?;
+        let redeem_script = self.redeem_script(index)?;
+        let utxos = coin_selection::spendable_utxos(node, std::slice::from_ref(&address)).await?;
+
+        // Priced as the spend it becomes once enough cosigners sign.
+        let shape = ScriptSpend::new(
+            redeem_script.clone(),
+            vec![UnlockItem::Signature; self.threshold],
+        )
+        .shape()?;
+        let tx = p2sh::build_spend(utxos, outputs, &address, shape, fee_rate)?;
+
+        let mut pskt = Pskt::new(&self.network_config.name, &tx)?;
+        for input in &mut pskt.inputs {
+            input.redeem_script = Some(redeem_script.clone());
+        }
+        Ok(pskt)
+    }
+}
+
+/// A key as it appears in a multisig script: x-only for Schnorr,
+/// compressed for ECDSA.
+fn script_key(key: &PublicKey, scheme: SignatureScheme) -> Vec<u8> {
+    match scheme {
+        SignatureScheme::Schnorr => key.x_only_public_key().0.serialize().to_vec(),
+        SignatureScheme::Ecdsa => key.serialize().to_vec(),
+    }
+}
+
+/// The parts of an M-of-N redeem script as `multisig_redeem_script` and
+/// `multisig_redeem_script_ecdsa` build it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigScript {
+    pub required: usize,
+    /// Keys in script order, which is the order signatures must follow.
+    pub keys: Vec<Vec<u8>>,
+    pub scheme: SignatureScheme,
+}
+
+impl MultisigScript {
+    /// Returns `None` for anything but a multisig script.
+    pub fn parse(script: &[u8]) -> Option<Self> {
+        let (required, rest) = small_int(script)?;
+        let (&check, mut rest) = rest.split_last()?;
+        let (scheme, push) = match check {
+            OpCheckMultiSig => (SignatureScheme::Schnorr, OpData32),
+            OpCheckMultiSigECDSA => (SignatureScheme::Ecdsa, OpData33),
+            _ => return None,
+        };
+
+        let key_len = push as usize;
+        let mut keys = Vec::new();
+        while rest.first() == Some(&push) && rest.len() > key_len {
+            keys.push(rest[1..=key_len].to_vec());
+            rest = &rest[key_len + 1..];
+        }
+        let (count, rest) = small_int(rest)?;
+        if !rest.is_empty() || count != keys.len() || required == 0 || required > count {
+            return None;
+        }
+        Some(Self {
+            required,
+            keys,
+            scheme,
+        })
+    }
+
+    /// Position of `public_key` among the script's keys.
+    pub fn key_index(&self, public_key: &PublicKey) -> Option<usize> {
+        let key = script_key(public_key, self.scheme);
+        self.keys.iter().position(|candidate| *candidate == key)
+    }
+}
+
+/// Reads a number pushed by `ScriptBuilder::add_i64`: OP_1 to OP_16, or a
+/// one-byte push for 17 to 20 keys.
+fn small_int(script: &[u8]) -> Option<(usize, &[u8])> {
+    let (&op, rest) = script.split_first()?;
+    match op {
+        Op1..=Op16 => Some(((op - Op1 + 1) as usize, rest)),
+        OpData1 => {
+            let (&value, rest) = rest.split_first()?;
+            Some((value as usize, rest))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::bip32::ExtendedPrivateKey;
+    use crate::mock_node::{execute_scripts, MockNode};
+    use crate::signer;
+    use crate::test_util::secret_key;
+    use crate::wallet::KaspaWallet;
+    use secp256k1::SecretKey;
+
+    fn cosigner(byte: u8) -> ExtendedPrivateKey {
+        ExtendedPrivateKey::new_master(&[byte; 32])
+            .unwrap()
+            .derive_path("m/44'/111111'/0'")
+            .unwrap()
+    }
+
+    fn account(scheme: SignatureScheme) -> MultisigAccount {
+        let xpubs = [0x41, 0x42, 0x43]
+            .into_iter()
+            .map(|byte| cosigner(byte).extended_public_key())
+            .collect();
+        MultisigAccount::new(xpubs, 2, NetworkConfig::testnet10())
+            .unwrap()
+            .with_signature_scheme(scheme)
+    }
+
+    fn cosigner_key(byte: u8, index: u32) -> SecretKey {
+        *cosigner(byte)
+            .derive_child(RECEIVE_CHAIN)
+            .unwrap()
+            .derive_child(index)
+            .unwrap()
+            .secret_key()
+    }
+
+    #[test]
+    fn test_account_validation() {
+        let xpub = cosigner(0x41).extended_public_key();
+        let network = NetworkConfig::testnet10;
+        assert!(MultisigAccount::new(vec![], 1, network()).is_err());
+        assert!(MultisigAccount::new(vec![xpub.clone()], 0, network()).is_err());
+        assert!(MultisigAccount::new(vec![xpub.clone()], 2, network()).is_err());
+        assert!(MultisigAccount::new(vec![xpub.clone(), xpub.clone()], 1, network()).is_err());
+        assert!(MultisigAccount::new(vec![xpub; MAX_COSIGNERS + 1], 1, network()).is_err());
+    }
+
+    #[test]
+    fn test_address_does_not_depend_on_cosigner_order() {
+        let schnorr = account(SignatureScheme::Schnorr);
+        let address = schnorr.address(0).unwrap();
+        assert_eq!(address.version, kaspa_addresses::Version::ScriptHash);
+        assert_ne!(address, schnorr.address(1).unwrap());
+
+        let reversed = [0x43, 0x42, 0x41]
+            .into_iter()
+            .map(|byte| cosigner(byte).extended_public_key())
+            .collect();
+        let reversed = MultisigAccount::new(reversed, 2, NetworkConfig::testnet10()).unwrap();
+        assert_eq!(reversed.address(0).unwrap(), address);
+
+        let ecdsa = account(SignatureScheme::Ecdsa);
+        assert_ne!(ecdsa.address(0).unwrap(), address);
+    }
+
+    #[test]
+    fn test_parse_redeem_script() {
+        for scheme in SignatureScheme::ALL {
+            let account = account(scheme);
+            let parsed = MultisigScript::parse(&account.redeem_script(0).unwrap()).unwrap();
+            assert_eq!(parsed.required, 2);
+            assert_eq!(parsed.scheme, scheme);
+            for (position, key) in account.public_keys(0).unwrap().iter().enumerate() {
+                assert_eq!(parsed.key_index(key), Some(position));
+            }
+        }
+
+        let wide: Vec<[u8; 32]> = (1..=MAX_COSIGNERS as u8)
+            .map(|byte| {
+                let public_key =
+                    PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &secret_key(byte));
+                public_key.x_only_public_key().0.serialize()
+            })
+            .collect();
+        let script = multisig_redeem_script(wide.iter(), 17).unwrap();
+        let parsed = MultisigScript::parse(&script).unwrap();
+        assert_eq!((parsed.required, parsed.keys.len()), (17, MAX_COSIGNERS));
+
+        assert!(MultisigScript::parse(&[]).is_none());
+        assert!(MultisigScript::parse(&script[..script.len() - 1]).is_none());
+        let wallet = KaspaWallet::new(cosigner_key(0x41, 0), NetworkConfig::testnet10());
+        assert!(MultisigScript::parse(wallet.script_public_key().script()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_two_of_three_spend() {
+        for scheme in SignatureScheme::ALL {
+            let account = account(scheme);
+            let address = account.address(0).unwrap();
+            let node = MockNode::new()
+                .with_utxo(&address, 3 * SOMPI_PER_KAS)
+                .with_utxo(&address, SOMPI_PER_KAS);
+            let recipient =
+                KaspaWallet::new(cosigner_key(0x44, 0), NetworkConfig::testnet10()).address();
+
+            let unsigned = account
+                .build_spend(&node, 0, &[(recipient.clone(), 2 * SOMPI_PER_KAS)], 1)
+                .await
+                .unwrap();
+            assert_eq!(unsigned.inputs.len(), 1);
+            assert_eq!(unsigned.tx.inputs[0].sig_op_count, 3);
+
+            // Each cosigner signs their own copy.
+            let mut first = unsigned.clone();
+            assert_eq!(first.sign(&cosigner_key(0x41, 0)).unwrap(), 1);
+            assert!(first.clone().finalize().is_err());
+            let mut third = unsigned.clone();
+            assert_eq!(third.sign(&cosigner_key(0x43, 0)).unwrap(), 1);
+            assert_eq!(third.sign(&cosigner_key(0x41, 1)).unwrap(), 0);
+
+            first.combine(&third).unwrap();
+            first.finalize().unwrap();
+            let tx = first.extract().unwrap();
+
+            // Two signatures in key order, then the redeem script.
+            let script = &tx.tx.inputs[0].signature_script;
+            let redeem_script = account.redeem_script(0).unwrap();
+            assert!(script.ends_with(&redeem_script));
+            assert_eq!(script[0], 65);
+            assert_eq!(script[66], 65);
+            let multisig = MultisigScript::parse(&redeem_script).unwrap();
+            let mut signers: Vec<_> = [0x41, 0x43]
+                .into_iter()
+                .map(|byte| {
+                    let key = cosigner_key(byte, 0);
+                    PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &key)
+                })
+                .collect();
+            signers.sort_by_key(|key| multisig.key_index(key));
+            for (slot, key) in signers.iter().enumerate() {
+                let signature: [u8; 64] = script[slot * 66 + 1..slot * 66 + 65].try_into().unwrap();
+                signer::verify_signature(&tx, 0, key, scheme, first.inputs[0].sighash, &signature)
+                    .unwrap();
+            }
+            execute_scripts(&tx).unwrap();
+
+            // OP_CHECKMULTISIG matches signatures to keys in order.
+            let mut swapped = tx.clone();
+            let script = &mut swapped.tx.inputs[0].signature_script;
+            let first_push = script[..66].to_vec();
+            script.copy_within(66..132, 0);
+            script[66..132].copy_from_slice(&first_push);
+            assert!(execute_scripts(&swapped).is_err());
+
+            node.submit(&tx.tx).await.unwrap();
+            assert_eq!(node.balance(&recipient).await.unwrap(), 2 * SOMPI_PER_KAS);
+            assert_eq!(
+                node.balance(&address).await.unwrap(),
+                4 * SOMPI_PER_KAS - 2 * SOMPI_PER_KAS - first.fee()
+            );
+        }
+    }
+}
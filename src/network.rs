@@ -1,10 +1,13 @@
 use crate::error::WalletResult;
+use kaspa_addresses::Prefix;
 
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
     pub name: String,
     pub prefix: String,
     pub rpc_url: String,
+    pub wrpc_borsh_url: String,
+    pub wrpc_json_url: String,
 }
 
 impl NetworkConfig {
@@ -13,30 +16,38 @@ impl NetworkConfig {
             name: "mainnet".to_string(),
             prefix: "kaspa".to_string(),
             rpc_url: "127.0.0.1:16110".to_string(),
+            wrpc_borsh_url: "ws://127.0.0.1:17110".to_string(),
+            wrpc_json_url: "ws://127.0.0.1:18110".to_string(),
         }
     }
 
     pub fn testnet10() -> Self {
         Self {
             name: "testnet-10".to_string(),
-            prefix: "kaspa".to_string(),
+            prefix: "kaspatest".to_string(),
             rpc_url: "127.0.0.1:16210".to_string(),
+            wrpc_borsh_url: "ws://127.0.0.1:17210".to_string(),
+            wrpc_json_url: "ws://127.0.0.1:18210".to_string(),
         }
     }
 
     pub fn testnet11() -> Self {
         Self {
             name: "testnet-11".to_string(),
-            prefix: "kaspa".to_string(),
+            prefix: "kaspatest".to_string(),
             rpc_url: "127.0.0.1:16310".to_string(),
+            wrpc_borsh_url: "ws://127.0.0.1:17310".to_string(),
+            wrpc_json_url: "ws://127.0.0.1:18310".to_string(),
         }
     }
 
     pub fn simnet() -> Self {
         Self {
             name: "simnet".to_string(),
-            prefix: "kaspa".to_string(),
+            prefix: "kaspasim".to_string(),
             rpc_url: "127.0.0.1:16410".to_string(),
+            wrpc_borsh_url: "ws://127.0.0.1:17410".to_string(),
+            wrpc_json_url: "ws://127.0.0.1:18410".to_string(),
         }
     }
 
@@ -54,7 +65,54 @@ impl NetworkConfig {
         }
     }
 
-    pub fn get_prefix(&self) -> &str {
-        &self.prefix
+    pub fn address_prefix(&self) -> Prefix {
+        match self.prefix.as_str() {
+            "kaspa" => Prefix::Mainnet,
+            "kaspatest" => Prefix::Testnet,
+            "kaspasim" => Prefix::Simnet,
+            _ => Prefix::Devnet,
+        }
+    }
+}
+
+/// How the wallet reaches a node: the public REST API, or a kaspad over
+/// gRPC or wRPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Rest,
+    Grpc,
+    WrpcBorsh,
+    WrpcJson,
+}
+
+impl Transport {
+    pub fn from_name(name: &str) -> WalletResult<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "rest" => Ok(Self::Rest),
+            "grpc" => Ok(Self::Grpc),
+            "wrpc-borsh" | "borsh" => Ok(Self::WrpcBorsh),
+            "wrpc-json" | "json" => Ok(Self::WrpcJson),
+            _ => Err(crate::error::WalletError::InvalidParameters(format!(
+                "Unknown transport: {} (expected rest, grpc, wrpc-borsh or wrpc-json)",
+                name
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_from_name() {
+        assert_eq!(Transport::from_name("REST").unwrap(), Transport::Rest);
+        assert_eq!(Transport::from_name("grpc").unwrap(), Transport::Grpc);
+        assert_eq!(
+            Transport::from_name("wrpc-borsh").unwrap(),
+            Transport::WrpcBorsh
+        );
+        assert_eq!(Transport::from_name("json").unwrap(), Transport::WrpcJson);
+        assert!(Transport::from_name("p2p").is_err());
     }
 }
@@ -0,0 +1,237 @@
+use crate::grpc::GrpcClient;
+use crate::network::{NetworkConfig, Transport};
+use crate::rpc::{FeeEstimateResponse, MempoolEntriesResponse, RpcClient, RpcError};
+use crate::transaction::Utxo;
+use crate::wrpc::{WrpcClient, WrpcEncoding};
+use async_trait::async_trait;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{Transaction, TransactionId};
+use std::str::FromStr;
+
+/// The node operations the wallet relies on, independent of how the node is
+/// reached. Everything that reads chain state or broadcasts should go
+/// through this trait so it can run against `MockNode` in tests.
+#[async_trait]
+pub trait NodeApi: Send + Sync {
+    async fn balance(&self, address: &Address) -> Result<u64, RpcError>;
+
+    /// Spendable outputs locked to any of `addresses`.
+    async fn utxos(&self, addresses: &[Address]) -> Result<Vec<Utxo>, RpcError>;
+
+    /// Unconfirmed transactions spending from or paying to `address`. The
+    /// REST API has no mempool view and reports `RpcError::Unsupported`.
+    async fn mempool_entries(&self, address: &Address) -> Result<MempoolEntriesResponse, RpcError>;
+
+    async fn submit(&self, tx: &Transaction) -> Result<TransactionId, RpcError>;
+
+    /// Signature scripts of the inputs of transaction `id`. kaspad keeps no
+    /// transaction index, so over gRPC and wRPC only transactions still in
+    /// the mempool are found; the REST API's indexer also finds accepted
+    /// ones.
+    async fn input_signature_scripts(&self, id: &TransactionId) -> Result<Vec<Vec<u8>>, RpcError>;
+
+    async fn fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError>;
+
+    async fn virtual_daa_score(&self) -> Result<u64, RpcError>;
+
+    /// Median timestamp of the blocks before the virtual, in milliseconds.
+    /// Consensus checks timestamp lock times against this rather than the
+    /// wall clock, which it trails.
+    async fn past_median_time(&self) -> Result<u64, RpcError>;
+
+    /// Closes any persistent connection. A no-op for stateless transports.
+    async fn disconnect(&self) -> Result<(), RpcError> {
+        Ok(())
+    }
+}
+
+/// Opens a node over `transport`. `url` overrides the endpoint; without it
+/// REST uses the public API and the other transports the network's local
+/// kaspad port.
+pub async fn connect(
+    transport: Transport,
+    url: Option<&str>,
+    network: &NetworkConfig,
+) -> Result<Box<dyn NodeApi>, RpcError> {
+    match transport {
+        Transport::Rest => Ok(Box::new(RpcClient::new(url))),
+        Transport::Grpc => {
+            let client = match url {
+                Some(url) => GrpcClient::connect(url).await?,
+                None => GrpcClient::for_network(network).await?,
+            };
+            Ok(Box::new(client))
+        }
+        Transport::WrpcBorsh | Transport::WrpcJson => {
+            let encoding = if transport == Transport::WrpcBorsh {
+                WrpcEncoding::Borsh
+            } else {
+                WrpcEncoding::SerdeJson
+            };
+            let client = match url {
+                Some(url) => WrpcClient::new(url, encoding)?,
+                None => WrpcClient::for_network(network, encoding)?,
+            };
+            client.connect().await?;
+            Ok(Box::new(client))
+        }
+    }
+}
+
+fn parse_transaction_id(id: &str) -> Result<TransactionId, RpcError> {
+    TransactionId::from_str(id)
+        .map_err(|e| RpcError::InvalidResponse(format!("transaction id {}: {}", id, e)))
+}
+
+#[async_trait]
+impl NodeApi for RpcClient {
+    async fn balance(&self, address: &Address) -> Result<u64, RpcError> {
+        Ok(self
+            .get_balance_by_address(&address.to_string())
+            .await?
+            .balance)
+    }
+
+    async fn utxos(&self, addresses: &[Address]) -> Result<Vec<Utxo>, RpcError> {
+        let mut utxos = Vec::new();
+        for address in addresses {
+            for response in self.get_utxos_by_address(&address.to_string()).await? {
+                utxos.push(response.to_utxo()?);
+            }
+        }
+        Ok(utxos)
+    }
+
+    async fn mempool_entries(
+        &self,
+        _address: &Address,
+    ) -> Result<MempoolEntriesResponse, RpcError> {
+        Err(RpcError::Unsupported(
+            "the REST API has no mempool view; use --transport grpc or wrpc".to_string(),
+        ))
+    }
+
+    async fn submit(&self, tx: &Transaction) -> Result<TransactionId, RpcError> {
+        parse_transaction_id(&self.submit_transaction(tx).await?)
+    }
+
+    async fn input_signature_scripts(&self, id: &TransactionId) -> Result<Vec<Vec<u8>>, RpcError> {
+        self.get_transaction(&id.to_string())
+            .await?
+            .inputs
+            .iter()
+            .filter_map(|input| input.signature_script.as_deref())
+            .map(|script| {
+                hex::decode(script)
+                    .map_err(|e| RpcError::InvalidResponse(format!("signature script: {}", e)))
+            })
+            .collect()
+    }
+
+    async fn fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        self.get_fee_estimate().await
+    }
+
+    async fn virtual_daa_score(&self) -> Result<u64, RpcError> {
+        self.get_virtual_daa_score().await
+    }
+
+    async fn past_median_time(&self) -> Result<u64, RpcError> {
+        self.get_past_median_time().await
+    }
+}
+
+#[async_trait]
+impl NodeApi for GrpcClient {
+    async fn balance(&self, address: &Address) -> Result<u64, RpcError> {
+        Ok(self
+            .get_balance_by_address(&address.to_string())
+            .await?
+            .balance)
+    }
+
+    async fn utxos(&self, addresses: &[Address]) -> Result<Vec<Utxo>, RpcError> {
+        let mut utxos = Vec::new();
+        for address in addresses {
+            for response in self.get_utxos_by_address(&address.to_string()).await? {
+                utxos.push(response.to_utxo()?);
+            }
+        }
+        Ok(utxos)
+    }
+
+    async fn mempool_entries(&self, address: &Address) -> Result<MempoolEntriesResponse, RpcError> {
+        self.get_mempool_entries_by_address(&address.to_string())
+            .await
+    }
+
+    async fn submit(&self, tx: &Transaction) -> Result<TransactionId, RpcError> {
+        parse_transaction_id(&self.submit_transaction(tx).await?)
+    }
+
+    async fn input_signature_scripts(&self, id: &TransactionId) -> Result<Vec<Vec<u8>>, RpcError> {
+        self.get_mempool_signature_scripts(*id).await
+    }
+
+    async fn fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        self.get_fee_estimate().await
+    }
+
+    async fn virtual_daa_score(&self) -> Result<u64, RpcError> {
+        self.get_virtual_daa_score().await
+    }
+
+    async fn past_median_time(&self) -> Result<u64, RpcError> {
+        self.get_past_median_time().await
+    }
+
+    async fn disconnect(&self) -> Result<(), RpcError> {
+        GrpcClient::disconnect(self).await
+    }
+}
+
+#[async_trait]
+impl NodeApi for WrpcClient {
+    async fn balance(&self, address: &Address) -> Result<u64, RpcError> {
+        self.get_balance_by_address(address).await
+    }
+
+    async fn utxos(&self, addresses: &[Address]) -> Result<Vec<Utxo>, RpcError> {
+        self.get_utxos_by_addresses(addresses.to_vec()).await
+    }
+
+    async fn mempool_entries(&self, address: &Address) -> Result<MempoolEntriesResponse, RpcError> {
+        let entries = self.get_mempool_entries_by_address(address).await?;
+        MempoolEntriesResponse::from_entries(address, entries)
+    }
+
+    async fn submit(&self, tx: &Transaction) -> Result<TransactionId, RpcError> {
+        self.submit_transaction(tx).await
+    }
+
+    async fn input_signature_scripts(&self, id: &TransactionId) -> Result<Vec<Vec<u8>>, RpcError> {
+        let entry = self.get_mempool_entry(*id).await?;
+        Ok(entry
+            .transaction
+            .inputs
+            .into_iter()
+            .map(|input| input.signature_script)
+            .collect())
+    }
+
+    async fn fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        Ok(self.get_fee_estimate().await?.into())
+    }
+
+    async fn virtual_daa_score(&self) -> Result<u64, RpcError> {
+        self.get_virtual_daa_score().await
+    }
+
+    async fn past_median_time(&self) -> Result<u64, RpcError> {
+        self.get_past_median_time().await
+    }
+
+    async fn disconnect(&self) -> Result<(), RpcError> {
+        WrpcClient::disconnect(self).await
+    }
+}
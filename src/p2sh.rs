@@ -0,0 +1,437 @@
+use crate::address::address_from_redeem_script;
+use crate::coin_selection;
+use crate::error::{WalletError, WalletResult};
+use crate::mass;
+use crate::node::NodeApi;
+use crate::script;
+use crate::signer::SigHashMode;
+use crate::transaction::{build_signable_transaction, LockTime, Utxo, LOCK_TIME_SEQUENCE};
+use crate::wallet::KaspaWallet;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_consensus_core::tx::SignableTransaction;
+use kaspa_txscript::pay_to_script_hash_signature_script;
+use kaspa_txscript::script_builder::ScriptBuilder;
+
+/// Size and signature operations of the script that will unlock a P2SH
+/// input, so the fee can be priced before any signature exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnlockShape {
+    pub signature_script_len: usize,
+    pub sig_op_count: u8,
+}
+
+/// One part of the data that unlocks a P2SH input, which the signature
+/// script pushes before the redeem script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnlockItem {
+    /// Script bytes, usually data pushes, used as they are.
+    Script(Vec<u8>),
+    /// A signature over the input by the spending key, pushed with its
+    /// sighash type like a P2PK signature script.
+    Signature,
+}
+
+/// Spends outputs locked to the hash of `redeem_script` by revealing it
+/// after the unlocking data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptSpend {
+    pub redeem_script: Vec<u8>,
+    pub unlock: Vec<UnlockItem>,
+    /// The spending transaction's `lock_time`, which scripts using
+    /// OP_CHECKLOCKTIMEVERIFY compare against. Zero for no lock.
+    pub lock_time: u64,
+}
+
+impl ScriptSpend {
+    pub fn new(redeem_script: Vec<u8>, unlock: Vec<UnlockItem>) -> Self {
+        Self {
+            redeem_script,
+            unlock,
+            lock_time: 0,
+        }
+    }
+
+    pub fn with_lock_time(mut self, lock_time: LockTime) -> Self {
+        self.lock_time = lock_time.value();
+        self
+    }
+
+    /// Reads unlocking data written as script assembly, where the token
+    /// `<sig>` stands for a signature by the spending key.
+    pub fn parse_unlock(text: &str) -> WalletResult<Vec<UnlockItem>> {
+        let mut items = Vec::new();
+        for (index, part) in text.split("<sig>").enumerate() {
+            if index > 0 {
+                items.push(UnlockItem::Signature);
+            }
+            let script = script::assemble(part)?;
+            if !script.is_empty() {
+                items.push(UnlockItem::Script(script));
+            }
+        }
+        Ok(items)
+    }
+
+    pub fn address(&self, prefix: Prefix) -> Address {
+        address_from_redeem_script(&self.redeem_script, prefix)
+    }
+
+    pub fn needs_signature(&self) -> bool {
+        self.unlock.contains(&UnlockItem::Signature)
+    }
+
+    /// The size of the signature scripts this spend produces and the
+    /// signature operations its redeem script performs.
+    pub fn shape(&self) -> WalletResult<UnlockShape> {
+        let unlock_len: usize = self
+            .unlock
+            .iter()
+            .map(|item| match item {
+                UnlockItem::Script(script) => script.len(),
+                UnlockItem::Signature => mass::SIGNATURE_SCRIPT_LEN,
+            })
+            .sum();
+        Ok(UnlockShape {
+            signature_script_len: unlock_len + redeem_script_push(&self.redeem_script)?.len(),
+            sig_op_count: script::sig_op_count(&self.redeem_script)?,
+        })
+    }
+
+    /// The signature script unlocking input `input_index` of `tx`; `wallet`
+    /// provides the signatures, if the unlocking data has any.
+    pub fn signature_script(
+        &self,
+        tx: &SignableTransaction,
+        input_index: usize,
+        wallet: Option<&KaspaWallet>,
+    ) -> WalletResult<Vec<u8>> {
+        let mut unlock = Vec::new();
+        for item in &self.unlock {
+            match item {
+                UnlockItem::Script(script) => unlock.extend_from_slice(script),
+                UnlockItem::Signature => {
+                    let wallet = wallet.ok_or_else(|| {
+                        WalletError::InvalidParameters(
+                            "The unlocking data needs a signature but no key was given".to_string(),
+                        )
+                    })?;
+                    unlock.extend(wallet.sign_script_input(tx, input_index, SigHashMode::All)?);
+                }
+            }
+        }
+        pay_to_script_hash_signature_script(self.redeem_script.clone(), unlock)
+            .map_err(|e| WalletError::Transaction(format!("Redeem script error: {}", e)))
+    }
+
+    /// Sets the spend's lock time on `tx`, then unlocks every input, all
+    /// of which must spend this script.
+    pub fn unlock_inputs(
+        &self,
+        tx: &mut SignableTransaction,
+        wallet: Option<&KaspaWallet>,
+    ) -> WalletResult<()> {
+        // Signatures commit to the lock time and sequences, so they are
+        // set first.
+        tx.tx.lock_time = self.lock_time;
+        if self.lock_time != 0 {
+            for input in &mut tx.tx.inputs {
+                input.sequence = LOCK_TIME_SEQUENCE;
+            }
+        }
+        tx.tx.finalize();
+        for input_index in 0..tx.tx.inputs.len() {
+            let signature_script = self.signature_script(tx, input_index, wallet)?;
+            tx.tx.inputs[input_index].signature_script = signature_script;
+        }
+        Ok(())
+    }
+}
+
+/// The push of `redeem_script` that ends every signature script spending
+/// it.
+fn redeem_script_push(redeem_script: &[u8]) -> WalletResult<Vec<u8>> {
+    Ok(ScriptBuilder::new()
+        .add_data(redeem_script)
+        .map_err(|e| WalletError::Transaction(format!("Redeem script error: {}", e)))?
+        .drain())
+}
+
+/// Builds and unlocks a transaction paying `outputs` from the UTXOs of
+/// `spend`'s address, with change back to that address.
+pub async fn build_script_spend(
+    node: &dyn NodeApi,
+    spend: &ScriptSpend,
+    prefix: Prefix,
+    outputs: &[(Address, u64)],
+    wallet: Option<&KaspaWallet>,
+    fee_rate: u64,
+) -> WalletResult<SignableTransaction> {
+    let address = spend.address(prefix);
+    let utxos = script_utxos(node, spend, &address, wallet).await?;
+    let mut tx = build_spend(utxos, outputs, &address, spend.shape()?, fee_rate)?;
+    spend.unlock_inputs(&mut tx, wallet)?;
+    Ok(tx)
+}
+
+/// Builds and unlocks a transaction moving every UTXO of `spend`'s
+/// address, less the fee, to `destination`.
+pub async fn build_script_sweep(
+    node: &dyn NodeApi,
+    spend: &ScriptSpend,
+    prefix: Prefix,
+    destination: &Address,
+    wallet: Option<&KaspaWallet>,
+    fee_rate: u64,
+) -> WalletResult<SignableTransaction> {
+    let address = spend.address(prefix);
+    let utxos = script_utxos(node, spend, &address, wallet).await?;
+    let mut tx = build_sweep(utxos, destination, spend.shape()?, fee_rate)?;
+    spend.unlock_inputs(&mut tx, wallet)?;
+    Ok(tx)
+}
+
+/// The UTXOs at `address`, after checking that `spend` has the key it
+/// needs so nothing is fetched for a spend that cannot be unlocked.
+async fn script_utxos(
+    node: &dyn NodeApi,
+    spend: &ScriptSpend,
+    address: &Address,
+    wallet: Option<&KaspaWallet>,
+) -> WalletResult<Vec<Utxo>> {
+    if spend.needs_signature() && wallet.is_none() {
+        return Err(WalletError::InvalidParameters(
+            "The unlocking data needs a signature but no key was given".to_string(),
+        ));
+    }
+    coin_selection::spendable_utxos(node, std::slice::from_ref(address)).await
+}
+
+/// Builds an unsigned transaction sending everything in the P2SH `utxos`,
+/// less the fee for unlocking them all with a script of `shape`, to
+/// `destination`.
+pub fn build_sweep(
+    utxos: Vec<Utxo>,
+    destination: &Address,
+    shape: UnlockShape,
+    fee_rate: u64,
+) -> WalletResult<SignableTransaction> {
+    let total: u64 = utxos.iter().map(|utxo| utxo.entry.amount).sum();
+    if total == 0 {
+        return Err(WalletError::InsufficientFunds {
+            required: 1,
+            available: 0,
+        });
+    }
+
+    // A smaller output raises the storage mass and so the fee; iterate
+    // until the fee stops growing.
+    let mut fee = 0;
+    for _ in 0..32 {
+        let Some(amount) = total.checked_sub(fee).filter(|amount| *amount > 0) else {
+            break;
+        };
+        let tx = unsigned_spend(utxos.clone(), &[(destination.clone(), amount)], shape)?;
+        let tx_mass = unlocked_mass(&tx, shape);
+        let next = mass::fee_for_mass(tx_mass, fee_rate);
+        if next <= fee {
+            mass::check_standard_mass(tx_mass)?;
+            return Ok(tx);
+        }
+        fee = next;
+    }
+    Err(WalletError::InsufficientFunds {
+        required: fee,
+        available: total,
+    })
+}
+
+/// Builds an unsigned transaction paying `outputs` from P2SH `utxos` that
+/// all unlock with a script of `shape`, with change back to `change`.
+///
+/// The largest UTXOs are spent first, and the fee is the mass of the
+/// transaction once unlocked times `fee_rate`. Change too small to pay for
+/// itself is left to the fee.
+pub fn build_spend(
+    mut utxos: Vec<Utxo>,
+    outputs: &[(Address, u64)],
+    change: &Address,
+    shape: UnlockShape,
+    fee_rate: u64,
+) -> WalletResult<SignableTransaction> {
+    if outputs.is_empty() {
+        return Err(WalletError::InvalidParameters(
+            "Payment has no recipients".to_string(),
+        ));
+    }
+    let spent = outputs
+        .iter()
+        .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+        .ok_or_else(|| WalletError::InvalidParameters("Payment total overflows".to_string()))?;
+    let available: u64 = utxos.iter().map(|utxo| utxo.entry.amount).sum();
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.entry.amount));
+
+    let mut required = spent;
+    for count in 1..=utxos.len() {
+        let inputs = utxos[..count].to_vec();
+        let total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+        if total < spent {
+            continue;
+        }
+
+        // The change value sets the storage mass, which sets the fee, which
+        // sets the change; iterate until the fee stops growing.
+        let mut fee = 0;
+        for _ in 0..32 {
+            let Some(change_value) = total
+                .checked_sub(spent.saturating_add(fee))
+                .filter(|value| *value > 0)
+            else {
+                break;
+            };
+            let mut recipients = outputs.to_vec();
+            recipients.push((change.clone(), change_value));
+            let tx = unsigned_spend(inputs.clone(), &recipients, shape)?;
+            let tx_mass = unlocked_mass(&tx, shape);
+            if tx_mass > mass::MAXIMUM_STANDARD_TRANSACTION_MASS {
+                break;
+            }
+            let next = mass::fee_for_mass(tx_mass, fee_rate);
+            if next <= fee {
+                return Ok(tx);
+            }
+            fee = next;
+        }
+
+        let tx = unsigned_spend(inputs, outputs, shape)?;
+        let tx_mass = unlocked_mass(&tx, shape);
+        required = spent.saturating_add(mass::fee_for_mass(tx_mass, fee_rate));
+        if total >= required {
+            mass::check_standard_mass(tx_mass)?;
+            return Ok(tx);
+        }
+    }
+    Err(WalletError::InsufficientFunds {
+        required,
+        available,
+    })
+}
+
+fn unsigned_spend(
+    inputs: Vec<Utxo>,
+    outputs: &[(Address, u64)],
+    shape: UnlockShape,
+) -> WalletResult<SignableTransaction> {
+    let mut tx = build_signable_transaction(inputs, outputs, 0)?;
+    for input in &mut tx.tx.inputs {
+        input.sig_op_count = shape.sig_op_count;
+    }
+    tx.tx.finalize();
+    Ok(tx)
+}
+
+/// Mass of `tx` once every input carries an unlocking script of `shape`.
+fn unlocked_mass(tx: &SignableTransaction, shape: UnlockShape) -> u64 {
+    let mut unlocked = tx.clone();
+    for input in &mut unlocked.tx.inputs {
+        input.signature_script = vec![0; shape.signature_script_len];
+    }
+    mass::transaction_mass(&unlocked).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::mock_node::MockNode;
+    use crate::signer;
+    use crate::test_util::test_wallet;
+    use kaspa_txscript::pay_to_script_hash_script;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_parse_unlock() {
+        assert_eq!(
+            ScriptSpend::parse_unlock("<sig> 0x0102 OP_TRUE").unwrap(),
+            [
+                UnlockItem::Signature,
+                UnlockItem::Script(vec![0x02, 0x01, 0x02, 0x51])
+            ]
+        );
+        assert!(ScriptSpend::parse_unlock("").unwrap().is_empty());
+        assert!(ScriptSpend::parse_unlock("<sig> OP_NOPE").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spend_hashlock() {
+        let preimage = b"open sesame";
+        let redeem_script = script::assemble(&format!(
+            "OP_SHA256 0x{} OP_EQUAL",
+            hex::encode(Sha256::digest(preimage))
+        ))
+        .unwrap();
+        let unlock = ScriptSpend::parse_unlock(&format!("0x{}", hex::encode(preimage))).unwrap();
+        let spend = ScriptSpend::new(redeem_script.clone(), unlock);
+        let address = spend.address(Prefix::Testnet);
+        let node = MockNode::new().with_utxo(&address, 5 * SOMPI_PER_KAS);
+        let recipient = test_wallet(0x43).address();
+
+        let tx = build_script_spend(
+            &node,
+            &spend,
+            Prefix::Testnet,
+            &[(recipient.clone(), 2 * SOMPI_PER_KAS)],
+            None,
+            1,
+        )
+        .await
+        .unwrap();
+        let input = &tx.tx.inputs[0];
+        assert_eq!(input.sig_op_count, 0);
+        assert_eq!(
+            input.signature_script.len(),
+            spend.shape().unwrap().signature_script_len
+        );
+        assert!(input.signature_script.ends_with(&redeem_script));
+        assert_eq!(
+            tx.tx.outputs[1].script_public_key,
+            pay_to_script_hash_script(&redeem_script)
+        );
+
+        node.submit(&tx.tx).await.unwrap();
+        assert_eq!(node.balance(&recipient).await.unwrap(), 2 * SOMPI_PER_KAS);
+    }
+
+    #[tokio::test]
+    async fn test_spend_with_signature() {
+        let wallet = test_wallet(0x42);
+        let redeem_script = script::assemble(&format!(
+            "0x{} OP_CHECKSIG",
+            hex::encode(wallet.public_key().x_only_public_key().0.serialize())
+        ))
+        .unwrap();
+        let spend = ScriptSpend::new(redeem_script, ScriptSpend::parse_unlock("<sig>").unwrap());
+        let node = MockNode::new().with_utxo(&spend.address(Prefix::Testnet), 5 * SOMPI_PER_KAS);
+        let outputs = [(test_wallet(0x43).address(), 2 * SOMPI_PER_KAS)];
+
+        assert!(
+            build_script_spend(&node, &spend, Prefix::Testnet, &outputs, None, 1)
+                .await
+                .is_err()
+        );
+        let tx = build_script_spend(&node, &spend, Prefix::Testnet, &outputs, Some(&wallet), 1)
+            .await
+            .unwrap();
+        let script = &tx.tx.inputs[0].signature_script;
+        assert_eq!(tx.tx.inputs[0].sig_op_count, 1);
+        let signature: [u8; 64] = script[1..65].try_into().unwrap();
+        signer::verify_signature(
+            &tx,
+            0,
+            wallet.public_key(),
+            wallet.signature_scheme(),
+            SigHashMode::All,
+            &signature,
+        )
+        .unwrap();
+    }
+}
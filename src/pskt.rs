@@ -0,0 +1,757 @@
+use crate::address::{network_prefix, SignatureScheme};
+use crate::amount::Amount;
+use crate::error::{WalletError, WalletResult};
+use crate::multisig::MultisigScript;
+use crate::signer::{self, input_scheme, SigHashMode};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use kaspa_consensus_core::subnets::SubnetworkId;
+use kaspa_consensus_core::tx::{
+    ScriptPublicKey, SignableTransaction, Transaction, TransactionId, TransactionInput,
+    TransactionOutpoint, TransactionOutput, UtxoEntry,
+};
+use kaspa_txscript::{
+    extract_script_pub_key_address, pay_to_script_hash_script, pay_to_script_hash_signature_script,
+};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Version 2 added redeem scripts; version 1 files are still read.
+pub const PSKT_VERSION: u8 = 2;
+
+/// First bytes of every binary PSKT, so files can be told apart from their
+/// base64 and hex forms. Deliberately not rusty-kaspa's `PSKT` prefix, as
+/// the format is this wallet's own.
+const MAGIC: &[u8; 4] = b"KRWP";
+
+/// Text prefixes of rusty-kaspa's `kaspa-wallet-pskt` PSKT and PSKB
+/// serializations, recognised only to explain that they cannot be read.
+const FOREIGN_PREFIXES: [&str; 2] = ["PSKT", "PSKB"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PsktEncoding {
+    Binary,
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl PsktEncoding {
+    pub fn from_name(name: &str) -> WalletResult<Self> {
+        match name.to_lowercase().as_str() {
+            "binary" | "bin" => Ok(PsktEncoding::Binary),
+            "base64" => Ok(PsktEncoding::Base64),
+            "hex" => Ok(PsktEncoding::Hex),
+            _ => Err(WalletError::InvalidParameters(format!(
+                "Unknown PSKT encoding: {}. Use binary, base64 or hex",
+                name
+            ))),
+        }
+    }
+}
+
+/// Per-input signing state of a PSKT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsktInput {
+    /// The output being spent, needed by signers to compute the sighash.
+    pub entry: UtxoEntry,
+    pub sighash: SigHashMode,
+    /// Multisig redeem script of a P2SH input, which the signature script
+    /// must reveal.
+    pub redeem_script: Option<Vec<u8>>,
+    /// Bare 64-byte signatures collected so far, by signing key.
+    pub partial_sigs: BTreeMap<PublicKey, [u8; 64]>,
+    /// Signature script, set once the input is finalized.
+    pub final_script: Option<Vec<u8>>,
+}
+
+impl PsktInput {
+    /// The scheme `public_key` signs this input with, or `None` when the
+    /// input is not locked to it.
+    fn signing_scheme(&self, public_key: &PublicKey) -> Option<SignatureScheme> {
+        match self.multisig() {
+            Some(multisig) => multisig.key_index(public_key).map(|_| multisig.scheme),
+            None => input_scheme(&self.entry.script_public_key, public_key),
+        }
+    }
+
+    /// The redeem script, when it is a multisig script the input is
+    /// actually locked to.
+    fn multisig(&self) -> Option<MultisigScript> {
+        let redeem_script = self.redeem_script.as_ref()?;
+        if pay_to_script_hash_script(redeem_script) != self.entry.script_public_key {
+            return None;
+        }
+        MultisigScript::parse(redeem_script)
+    }
+
+    /// Builds the signature script from the collected signatures,
+    /// verifying each one used.
+    fn signature_script(&self, tx: &SignableTransaction, index: usize) -> WalletResult<Vec<u8>> {
+        let verify = |public_key: &PublicKey, scheme, signature: &[u8; 64]| {
+            signer::verify_signature(tx, index, public_key, scheme, self.sighash, signature)
+                .map_err(|e| {
+                    WalletError::Pskt(format!("Invalid signature for input {}: {}", index, e))
+                })
+        };
+
+        if self.redeem_script.is_none() {
+            let (public_key, signature, scheme) = self
+                .partial_sigs
+                .iter()
+                .find_map(|(public_key, signature)| {
+                    input_scheme(&self.entry.script_public_key, public_key)
+                        .map(|scheme| (public_key, signature, scheme))
+                })
+                .ok_or_else(|| {
+                    WalletError::Pskt(format!("Input {} is missing its signature", index))
+                })?;
+            verify(public_key, scheme, signature)?;
+            return Ok(signer::signature_script(signature, self.sighash));
+        }
+
+        let multisig = self.multisig().ok_or_else(|| {
+            WalletError::Pskt(format!(
+                "Input {} has a redeem script that is not a multisig script it is locked to",
+                index
+            ))
+        })?;
+        // OP_CHECKMULTISIG expects the signatures in the order of the keys.
+        let mut signers: Vec<_> = self
+            .partial_sigs
+            .iter()
+            .filter_map(|(public_key, signature)| {
+                multisig
+                    .key_index(public_key)
+                    .map(|position| (position, public_key, signature))
+            })
+            .collect();
+        signers.sort_by_key(|(position, _, _)| *position);
+        if signers.len() < multisig.required {
+            return Err(WalletError::Pskt(format!(
+                "Input {} has {} of the {} signatures it needs",
+                index,
+                signers.len(),
+                multisig.required
+            )));
+        }
+
+        let mut signatures = Vec::new();
+        for (_, public_key, signature) in signers.into_iter().take(multisig.required) {
+            verify(public_key, multisig.scheme, signature)?;
+            signatures.extend(signer::signature_script(signature, self.sighash));
+        }
+        let redeem_script = self.redeem_script.clone().expect("checked above");
+        pay_to_script_hash_signature_script(redeem_script, signatures)
+            .map_err(|e| WalletError::Pskt(format!("Input {}: {}", index, e)))
+    }
+}
+
+/// A partially signed Kaspa transaction: an unsigned transaction plus
+/// everything cosigners need to sign their inputs offline, and the
+/// signatures gathered so far. It moves between machines as a file in the
+/// binary, base64 or hex encoding. The format is specific to this wallet
+/// and does not interoperate with rusty-kaspa's `kaspa-wallet-pskt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pskt {
+    pub network: String,
+    /// The transaction, always held with empty signature scripts.
+    pub tx: Transaction,
+    pub inputs: Vec<PsktInput>,
+}
+
+impl Pskt {
+    /// Wraps `tx`, dropping any signatures it already carries. Every input
+    /// must have its UTXO entry.
+    pub fn new(network: &str, tx: &SignableTransaction) -> WalletResult<Self> {
+        network_prefix(network)?;
+        if !tx.is_fully_populated() {
+            return Err(WalletError::Pskt(
+                "Missing UTXO entries for the inputs".to_string(),
+            ));
+        }
+
+        let mut unsigned = tx.tx.clone();
+        for input in &mut unsigned.inputs {
+            input.signature_script.clear();
+        }
+        let inputs = tx
+            .entries
+            .iter()
+            .flatten()
+            .map(|entry| PsktInput {
+                entry: entry.clone(),
+                sighash: SigHashMode::All,
+                redeem_script: None,
+                partial_sigs: BTreeMap::new(),
+                final_script: None,
+            })
+            .collect();
+        Ok(Self {
+            network: network.to_string(),
+            tx: unsigned,
+            inputs,
+        })
+    }
+
+    /// The transaction id, which does not depend on signatures.
+    pub fn id(&self) -> TransactionId {
+        self.tx.id()
+    }
+
+    pub fn fee(&self) -> u64 {
+        let input_total: u64 = self.inputs.iter().map(|input| input.entry.amount).sum();
+        let output_total: u64 = self.tx.outputs.iter().map(|output| output.value).sum();
+        input_total.saturating_sub(output_total)
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(|input| input.final_script.is_some())
+    }
+
+    /// The transaction with its entries and whatever inputs are finalized
+    /// so far, ready for sighash computation.
+    pub fn signable(&self) -> SignableTransaction {
+        let mut tx = self.tx.clone();
+        for (tx_input, input) in tx.inputs.iter_mut().zip(&self.inputs) {
+            tx_input.signature_script = input.final_script.clone().unwrap_or_default();
+        }
+        let entries = self
+            .inputs
+            .iter()
+            .map(|input| input.entry.clone())
+            .collect();
+        SignableTransaction::with_entries(tx, entries)
+    }
+
+    /// Adds a signature from `secret_key` to every input locked to its key,
+    /// alone or as one of a multisig's keys, that is not finalized yet.
+    /// Returns the number of inputs signed.
+    pub fn sign(&mut self, secret_key: &SecretKey) -> WalletResult<usize> {
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), secret_key);
+        let tx = self.signable();
+        let mut signed = 0;
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            if input.final_script.is_some() {
+                continue;
+            }
+            let Some(scheme) = input.signing_scheme(&public_key) else {
+                continue;
+            };
+            let signature = signer::input_signature(&tx, index, secret_key, scheme, input.sighash)?;
+            input.partial_sigs.insert(public_key, signature);
+            signed += 1;
+        }
+        Ok(signed)
+    }
+
+    /// Merges the signatures of `other`, which must be the same transaction.
+    pub fn combine(&mut self, other: &Pskt) -> WalletResult<()> {
+        let same_entries = self.inputs.iter().zip(&other.inputs).all(|(ours, theirs)| {
+            ours.entry == theirs.entry
+                && ours.sighash == theirs.sighash
+                && ours.redeem_script == theirs.redeem_script
+        });
+        if self.network != other.network
+            || self.id() != other.id()
+            || self.inputs.len() != other.inputs.len()
+            || !same_entries
+        {
+            return Err(WalletError::Pskt(format!(
+                "Cannot combine PSKTs of different transactions ({} and {})",
+                self.id(),
+                other.id()
+            )));
+        }
+
+        for (index, (ours, theirs)) in self.inputs.iter_mut().zip(&other.inputs).enumerate() {
+            for (public_key, signature) in &theirs.partial_sigs {
+                if ours
+                    .partial_sigs
+                    .get(public_key)
+                    .is_some_and(|sig| sig != signature)
+                {
+                    return Err(WalletError::Pskt(format!(
+                        "Conflicting signatures for input {}",
+                        index
+                    )));
+                }
+                ours.partial_sigs.insert(*public_key, *signature);
+            }
+            match (&ours.final_script, &theirs.final_script) {
+                (Some(script), Some(other_script)) if script != other_script => {
+                    return Err(WalletError::Pskt(format!(
+                        "Conflicting signature scripts for input {}",
+                        index
+                    )));
+                }
+                (None, Some(script)) => ours.final_script = Some(script.clone()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Turns the collected signatures into signature scripts, checking each
+    /// one against its input. Fails if any input is still short of
+    /// signatures, leaving every input as it was.
+    pub fn finalize(&mut self) -> WalletResult<()> {
+        let tx = self.signable();
+        let mut finalized = Vec::new();
+        for (index, input) in self.inputs.iter().enumerate() {
+            if input.final_script.is_none() {
+                finalized.push((index, input.signature_script(&tx, index)?));
+            }
+        }
+        for (index, script) in finalized {
+            self.inputs[index].final_script = Some(script);
+        }
+        Ok(())
+    }
+
+    /// The network-ready signed transaction. Only finalized PSKTs can be
+    /// extracted.
+    pub fn extract(&self) -> WalletResult<SignableTransaction> {
+        if let Some(index) = self
+            .inputs
+            .iter()
+            .position(|input| input.final_script.is_none())
+        {
+            return Err(WalletError::Pskt(format!(
+                "Input {} is not finalized",
+                index
+            )));
+        }
+        Ok(self.signable())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer(MAGIC.to_vec());
+        writer.u8(PSKT_VERSION);
+        writer.bytes(self.network.as_bytes());
+
+        writer.u16(self.tx.version);
+        writer.u64(self.tx.lock_time);
+        writer.0.extend_from_slice(self.tx.subnetwork_id.as_ref());
+        writer.u64(self.tx.gas);
+        writer.bytes(&self.tx.payload);
+
+        writer.u32(self.inputs.len() as u32);
+        for (tx_input, input) in self.tx.inputs.iter().zip(&self.inputs) {
+            writer
+                .0
+                .extend_from_slice(&tx_input.previous_outpoint.transaction_id.as_bytes());
+            writer.u32(tx_input.previous_outpoint.index);
+            writer.u64(tx_input.sequence);
+            writer.u8(tx_input.sig_op_count);
+
+            writer.u64(input.entry.amount);
+            writer.script(&input.entry.script_public_key);
+            writer.u64(input.entry.block_daa_score);
+            writer.u8(input.entry.is_coinbase as u8);
+            writer.u8(input.sighash.to_u8());
+            writer.optional_bytes(input.redeem_script.as_deref());
+            writer.u32(input.partial_sigs.len() as u32);
+            for (public_key, signature) in &input.partial_sigs {
+                writer.0.extend_from_slice(&public_key.serialize());
+                writer.0.extend_from_slice(signature);
+            }
+            writer.optional_bytes(input.final_script.as_deref());
+        }
+
+        writer.u32(self.tx.outputs.len() as u32);
+        for output in &self.tx.outputs {
+            writer.u64(output.value);
+            writer.script(&output.script_public_key);
+        }
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> WalletResult<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(malformed("not a PSKT"));
+        }
+        let version = reader.u8()?;
+        if version == 0 || version > PSKT_VERSION {
+            return Err(WalletError::Pskt(format!(
+                "Unsupported PSKT version {}",
+                version
+            )));
+        }
+        let network = String::from_utf8(reader.bytes()?.to_vec())
+            .map_err(|_| malformed("network name is not UTF-8"))?;
+        network_prefix(&network)?;
+
+        let tx_version = reader.u16()?;
+        let lock_time = reader.u64()?;
+        let subnetwork_id = SubnetworkId::from_bytes(reader.array()?);
+        let gas = reader.u64()?;
+        let payload = reader.bytes()?.to_vec();
+
+        let input_count = reader.u32()?;
+        let mut tx_inputs = Vec::new();
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let outpoint =
+                TransactionOutpoint::new(TransactionId::from_bytes(reader.array()?), reader.u32()?);
+            let sequence = reader.u64()?;
+            let sig_op_count = reader.u8()?;
+            tx_inputs.push(TransactionInput::new(
+                outpoint,
+                vec![],
+                sequence,
+                sig_op_count,
+            ));
+
+            let entry = UtxoEntry::new(
+                reader.u64()?,
+                reader.script()?,
+                reader.u64()?,
+                reader.u8()? != 0,
+            );
+            let sighash =
+                SigHashMode::from_u8(reader.u8()?).ok_or_else(|| malformed("bad sighash type"))?;
+            let redeem_script = if version < 2 {
+                None
+            } else {
+                reader.optional_bytes()?
+            };
+            let mut partial_sigs = BTreeMap::new();
+            for _ in 0..reader.u32()? {
+                let public_key = PublicKey::from_slice(reader.take(33)?)
+                    .map_err(|_| malformed("bad public key"))?;
+                partial_sigs.insert(public_key, reader.array()?);
+            }
+            let final_script = reader.optional_bytes()?;
+            inputs.push(PsktInput {
+                entry,
+                sighash,
+                redeem_script,
+                partial_sigs,
+                final_script,
+            });
+        }
+
+        let output_count = reader.u32()?;
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            outputs.push(TransactionOutput::new(reader.u64()?, reader.script()?));
+        }
+        if !reader.0.is_empty() {
+            return Err(malformed("trailing data"));
+        }
+
+        Ok(Self {
+            network,
+            tx: Transaction::new(
+                tx_version,
+                tx_inputs,
+                outputs,
+                lock_time,
+                subnetwork_id,
+                gas,
+                payload,
+            ),
+            inputs,
+        })
+    }
+
+    pub fn encode(&self, encoding: PsktEncoding) -> Vec<u8> {
+        let bytes = self.to_bytes();
+        match encoding {
+            PsktEncoding::Binary => bytes,
+            PsktEncoding::Base64 => BASE64.encode(bytes).into_bytes(),
+            PsktEncoding::Hex => hex::encode(bytes).into_bytes(),
+        }
+    }
+
+    /// Reads any of the three encodings.
+    pub fn decode(data: &[u8]) -> WalletResult<Self> {
+        if data.starts_with(MAGIC) {
+            return Self::from_bytes(data);
+        }
+        let text = std::str::from_utf8(data)
+            .map_err(|_| malformed("neither binary nor text"))?
+            .trim();
+        if let Some(prefix) = FOREIGN_PREFIXES
+            .iter()
+            .find(|prefix| text.starts_with(**prefix))
+        {
+            return Err(WalletError::Pskt(format!(
+                "This is a rusty-kaspa {}, which this wallet cannot read; it only \
+                 reads its own partially signed format",
+                prefix
+            )));
+        }
+        let bytes = if text.chars().all(|c| c.is_ascii_hexdigit()) {
+            hex::decode(text)?
+        } else {
+            BASE64
+                .decode(text)
+                .map_err(|e| malformed(&format!("invalid base64: {}", e)))?
+        };
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> WalletResult<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, encoding: PsktEncoding) -> WalletResult<()> {
+        fs::write(path, self.encode(encoding))?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pskt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let render = |script: &ScriptPublicKey| {
+            network_prefix(&self.network)
+                .ok()
+                .and_then(|prefix| extract_script_pub_key_address(script, prefix).ok())
+                .map(|address| address.to_string())
+                .unwrap_or_else(|| format!("script {}", hex::encode(script.script())))
+        };
+
+        writeln!(f, "Network: {}", self.network)?;
+        writeln!(f, "Tx id:   {}", self.id())?;
+        writeln!(f, "Inputs:  {}", self.inputs.len())?;
+        for (index, (tx_input, input)) in self.tx.inputs.iter().zip(&self.inputs).enumerate() {
+            let status = if input.final_script.is_some() {
+                "finalized".to_string()
+            } else {
+                format!("{} signatures", input.partial_sigs.len())
+            };
+            writeln!(
+                f,
+                "  {}: {} {} from {} ({})",
+                index,
+                tx_input.previous_outpoint,
+                Amount::from_sompi(input.entry.amount),
+                render(&input.entry.script_public_key),
+                status
+            )?;
+        }
+        writeln!(f, "Outputs: {}", self.tx.outputs.len())?;
+        for (index, output) in self.tx.outputs.iter().enumerate() {
+            writeln!(
+                f,
+                "  {}: {} to {}",
+                index,
+                Amount::from_sompi(output.value),
+                render(&output.script_public_key)
+            )?;
+        }
+        writeln!(f, "Fee:     {}", Amount::from_sompi(self.fee()))?;
+        write!(
+            f,
+            "Status:  {}",
+            if self.is_finalized() {
+                "finalized, ready to submit"
+            } else {
+                "awaiting signatures"
+            }
+        )
+    }
+}
+
+fn malformed(reason: &str) -> WalletError {
+    WalletError::Pskt(format!("Malformed PSKT: {}", reason))
+}
+
+/// Little-endian writer for the binary encoding. Variable-length fields
+/// are prefixed with their length as a u32.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.0.extend_from_slice(bytes);
+    }
+
+    /// A flag byte, then the bytes when there are any.
+    fn optional_bytes(&mut self, bytes: Option<&[u8]>) {
+        match bytes {
+            Some(bytes) => {
+                self.u8(1);
+                self.bytes(bytes);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn script(&mut self, script: &ScriptPublicKey) {
+        self.u16(script.version());
+        self.bytes(script.script());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> WalletResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(malformed("truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> WalletResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> WalletResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> WalletResult<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> WalletResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> WalletResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn bytes(&mut self) -> WalletResult<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// A flag byte, then the bytes when it is set.
+    fn optional_bytes(&mut self) -> WalletResult<Option<Vec<u8>>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.bytes()?.to_vec())),
+            _ => Err(malformed("bad optional field flag")),
+        }
+    }
+
+    fn script(&mut self) -> WalletResult<ScriptPublicKey> {
+        let version = self.u16()?;
+        Ok(ScriptPublicKey::from_vec(version, self.bytes()?.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::test_util::{secret_key, test_wallet, utxo};
+    use crate::transaction::build_signable_transaction;
+
+    /// One input owned by each of the 0x41 and 0x42 keys, paying 0x43.
+    fn shared_pskt() -> Pskt {
+        let utxos = [0x41, 0x42]
+            .into_iter()
+            .enumerate()
+            .map(|(index, byte)| {
+                utxo(
+                    index as u32,
+                    2 * SOMPI_PER_KAS,
+                    test_wallet(byte).script_public_key(),
+                )
+            })
+            .collect();
+        let tx = build_signable_transaction(
+            utxos,
+            &[(test_wallet(0x43).address(), 3 * SOMPI_PER_KAS)],
+            0,
+        )
+        .unwrap();
+        Pskt::new("testnet-10", &tx).unwrap()
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        let mut pskt = shared_pskt();
+        pskt.sign(&secret_key(0x41)).unwrap();
+
+        for encoding in [
+            PsktEncoding::Binary,
+            PsktEncoding::Base64,
+            PsktEncoding::Hex,
+        ] {
+            let decoded = Pskt::decode(&pskt.encode(encoding)).unwrap();
+            assert_eq!(decoded, pskt);
+            assert_eq!(decoded.id(), pskt.id());
+        }
+        assert!(Pskt::decode(b"KRWP").is_err());
+        let foreign = Pskt::decode(b"PSKT7b7d").unwrap_err().to_string();
+        assert!(foreign.contains("rusty-kaspa PSKT"), "{}", foreign);
+        let mut bytes = pskt.to_bytes();
+        bytes.push(0);
+        assert!(Pskt::from_bytes(&bytes).is_err());
+        bytes[4] = PSKT_VERSION + 1;
+        assert!(Pskt::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_sign_combine_finalize_extract() {
+        let unsigned = shared_pskt();
+        assert_eq!(unsigned.fee(), SOMPI_PER_KAS);
+
+        let mut first = unsigned.clone();
+        assert_eq!(first.sign(&secret_key(0x41)).unwrap(), 1);
+        assert_eq!(first.sign(&secret_key(0x44)).unwrap(), 0);
+        assert!(first.clone().finalize().is_err());
+        assert!(first.extract().is_err());
+
+        let mut second = unsigned.clone();
+        assert_eq!(second.sign(&secret_key(0x42)).unwrap(), 1);
+
+        first.combine(&second).unwrap();
+        first.finalize().unwrap();
+        assert!(first.is_finalized());
+        assert!(first.to_string().contains("finalized, ready to submit"));
+
+        let tx = first.extract().unwrap();
+        assert_eq!(tx.tx.id(), unsigned.id());
+        signer::verify_input(&tx, 0, test_wallet(0x41).public_key()).unwrap();
+        signer::verify_input(&tx, 1, test_wallet(0x42).public_key()).unwrap();
+    }
+
+    #[test]
+    fn test_combine_rejects_other_transactions() {
+        let mut pskt = shared_pskt();
+        let mut other = shared_pskt();
+        other.tx.outputs[0].value -= 1;
+        other.tx.finalize();
+        assert!(pskt.combine(&other).is_err());
+
+        let mut tampered = shared_pskt();
+        tampered.sign(&secret_key(0x41)).unwrap();
+        tampered.inputs[0]
+            .partial_sigs
+            .values_mut()
+            .for_each(|signature| signature[0] ^= 1);
+        pskt.combine(&tampered).unwrap();
+        assert!(pskt.finalize().is_err());
+        assert!(pskt.inputs[0].final_script.is_none());
+    }
+}
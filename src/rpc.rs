@@ -1,8 +1,18 @@
+use crate::transaction::Utxo;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{
+    ScriptPublicKey, Transaction, TransactionId, TransactionOutpoint, UtxoEntry,
+};
+use kaspa_rpc_core::{RpcFeeEstimate, RpcFeerateBucket, RpcMempoolEntry, RpcMempoolEntryByAddress};
+use kaspa_txscript::pay_to_address_script;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::time::Duration;
 use thiserror::Error;
 
 const DEFAULT_RPC_URL: &str = "https://api-tn10.kaspa.org";
+const DEFAULT_PAGE_LIMIT: u32 = 50;
 
 #[derive(Error, Debug)]
 pub enum RpcError {
@@ -12,6 +22,10 @@ pub enum RpcError {
     Rpc(String),
     #[error("JSON error: {0}")]
     JsonError(String),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Not supported: {0}")]
+    Unsupported(String),
 }
 
 pub struct RpcClient {
@@ -24,9 +38,357 @@ pub struct GetBalanceByAddressResponse {
     pub balance: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutpointResponse {
+    pub transaction_id: String,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptPublicKeyResponse {
+    #[serde(default)]
+    pub version: u16,
+    pub script_public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UtxoEntryResponse {
+    #[serde(deserialize_with = "flexible::u64")]
+    pub amount: u64,
+    pub script_public_key: ScriptPublicKeyResponse,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub block_daa_score: u64,
+    #[serde(default)]
+    pub is_coinbase: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UtxoResponse {
+    pub address: Option<String>,
+    pub outpoint: OutpointResponse,
+    pub utxo_entry: UtxoEntryResponse,
+}
+
+impl UtxoResponse {
+    /// Converts the indexer's view of the UTXO into the consensus types
+    /// used for building and signing transactions.
+    pub fn to_utxo(&self) -> Result<Utxo, RpcError> {
+        let transaction_id =
+            TransactionId::from_str(&self.outpoint.transaction_id).map_err(|e| {
+                RpcError::InvalidResponse(format!(
+                    "transaction id {}: {}",
+                    self.outpoint.transaction_id, e
+                ))
+            })?;
+        let script = hex::decode(&self.utxo_entry.script_public_key.script_public_key)
+            .map_err(|e| RpcError::InvalidResponse(format!("script public key: {}", e)))?;
+
+        Ok(Utxo::new(
+            TransactionOutpoint::new(transaction_id, self.outpoint.index),
+            UtxoEntry::new(
+                self.utxo_entry.amount,
+                ScriptPublicKey::from_vec(self.utxo_entry.script_public_key.version, script),
+                self.utxo_entry.block_daa_score,
+                self.utxo_entry.is_coinbase,
+            ),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_PAGE_LIMIT,
+            offset: 0,
+        }
+    }
+}
+
+impl Page {
+    pub fn next(self) -> Self {
+        Self {
+            limit: self.limit,
+            offset: self.offset.saturating_add(self.limit),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionInputResponse {
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub index: Option<u64>,
+    pub previous_outpoint_hash: String,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub previous_outpoint_index: u64,
+    pub previous_outpoint_address: Option<String>,
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub previous_outpoint_amount: Option<u64>,
+    pub signature_script: Option<String>,
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub sig_op_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionOutputResponse {
+    #[serde(deserialize_with = "flexible::u64")]
+    pub index: u64,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub amount: u64,
+    pub script_public_key: String,
+    pub script_public_key_address: Option<String>,
+    pub script_public_key_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionResponse {
+    pub transaction_id: String,
+    pub subnetwork_id: Option<String>,
+    pub hash: Option<String>,
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub mass: Option<u64>,
+    pub payload: Option<String>,
+    #[serde(default)]
+    pub block_hash: Vec<String>,
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub block_time: Option<u64>,
+    #[serde(default)]
+    pub is_accepted: bool,
+    pub accepting_block_hash: Option<String>,
+    #[serde(default, deserialize_with = "flexible::option_u64")]
+    pub accepting_block_blue_score: Option<u64>,
+    #[serde(default)]
+    pub inputs: Vec<TransactionInputResponse>,
+    #[serde(default)]
+    pub outputs: Vec<TransactionOutputResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeBucket {
+    /// Fee rate in sompi per gram of mass.
+    pub feerate: f64,
+    pub estimated_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEstimateResponse {
+    pub priority_bucket: FeeBucket,
+    #[serde(default)]
+    pub normal_buckets: Vec<FeeBucket>,
+    #[serde(default)]
+    pub low_buckets: Vec<FeeBucket>,
+}
+
+impl From<RpcFeerateBucket> for FeeBucket {
+    fn from(bucket: RpcFeerateBucket) -> Self {
+        Self {
+            feerate: bucket.feerate,
+            estimated_seconds: bucket.estimated_seconds,
+        }
+    }
+}
+
+impl From<RpcFeeEstimate> for FeeEstimateResponse {
+    fn from(estimate: RpcFeeEstimate) -> Self {
+        Self {
+            priority_bucket: estimate.priority_bucket.into(),
+            normal_buckets: estimate
+                .normal_buckets
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            low_buckets: estimate.low_buckets.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MempoolEntryResponse {
+    pub transaction_id: String,
+    pub fee: u64,
+    pub is_orphan: bool,
+    /// Sompi the transaction pays to the queried address.
+    pub received: u64,
+}
+
+/// Unconfirmed transactions spending from (`sending`) or paying to
+/// (`receiving`) an address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MempoolEntriesResponse {
+    pub sending: Vec<MempoolEntryResponse>,
+    pub receiving: Vec<MempoolEntryResponse>,
+}
+
+impl MempoolEntriesResponse {
+    /// Flattens the node's per-address entries for `address`.
+    pub fn from_entries(
+        address: &Address,
+        entries: Vec<RpcMempoolEntryByAddress>,
+    ) -> Result<Self, RpcError> {
+        let script = pay_to_address_script(address);
+        let mut response = Self::default();
+        for entry in entries {
+            for sending in entry.sending {
+                response.sending.push(mempool_entry(sending, &script)?);
+            }
+            for receiving in entry.receiving {
+                response.receiving.push(mempool_entry(receiving, &script)?);
+            }
+        }
+        Ok(response)
+    }
+}
+
+fn mempool_entry(
+    entry: RpcMempoolEntry,
+    script: &ScriptPublicKey,
+) -> Result<MempoolEntryResponse, RpcError> {
+    let received = entry
+        .transaction
+        .outputs
+        .iter()
+        .filter(|output| output.script_public_key == *script)
+        .map(|output| output.value)
+        .sum();
+    let transaction_id = match &entry.transaction.verbose_data {
+        Some(verbose) => verbose.transaction_id,
+        None => Transaction::try_from(entry.transaction)
+            .map_err(|e| RpcError::InvalidResponse(format!("mempool transaction: {}", e)))?
+            .id(),
+    };
+    Ok(MempoolEntryResponse {
+        transaction_id: transaction_id.to_string(),
+        fee: entry.fee,
+        is_orphan: entry.is_orphan,
+        received,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlueScoreResponse {
+    #[serde(deserialize_with = "flexible::u64")]
+    pub blue_score: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInfoResponse {
+    pub network_name: String,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub block_count: u64,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub header_count: u64,
+    #[serde(default)]
+    pub tip_hashes: Vec<String>,
+    pub difficulty: f64,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub past_median_time: u64,
+    #[serde(default)]
+    pub virtual_parent_hashes: Vec<String>,
+    pub pruning_point_hash: String,
+    #[serde(deserialize_with = "flexible::u64")]
+    pub virtual_daa_score: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitScriptPublicKey {
+    pub version: u16,
+    pub script_public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionInput {
+    pub previous_outpoint: OutpointResponse,
+    pub signature_script: String,
+    pub sequence: u64,
+    pub sig_op_count: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionOutput {
+    pub amount: u64,
+    pub script_public_key: SubmitScriptPublicKey,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransaction {
+    pub version: u16,
+    pub inputs: Vec<SubmitTransactionInput>,
+    pub outputs: Vec<SubmitTransactionOutput>,
+    pub lock_time: u64,
+    pub subnetwork_id: String,
+}
+
+impl From<&Transaction> for SubmitTransaction {
+    fn from(tx: &Transaction) -> Self {
+        Self {
+            version: tx.version,
+            inputs: tx
+                .inputs
+                .iter()
+                .map(|input| SubmitTransactionInput {
+                    previous_outpoint: OutpointResponse {
+                        transaction_id: input.previous_outpoint.transaction_id.to_string(),
+                        index: input.previous_outpoint.index,
+                    },
+                    signature_script: hex::encode(&input.signature_script),
+                    sequence: input.sequence,
+                    sig_op_count: input.sig_op_count,
+                })
+                .collect(),
+            outputs: tx
+                .outputs
+                .iter()
+                .map(|output| SubmitTransactionOutput {
+                    amount: output.value,
+                    script_public_key: SubmitScriptPublicKey {
+                        version: output.script_public_key.version(),
+                        script_public_key: hex::encode(output.script_public_key.script()),
+                    },
+                })
+                .collect(),
+            lock_time: tx.lock_time,
+            subnetwork_id: tx.subnetwork_id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionRequest {
+    pub transaction: SubmitTransaction,
+    pub allow_orphan: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionResponse {
+    pub transaction_id: Option<String>,
+    pub error: Option<String>,
+}
+
 impl RpcClient {
     pub fn new(rpc_url: Option<&str>) -> Self {
-        let url = rpc_url.unwrap_or(DEFAULT_RPC_URL).trim_end_matches('/').to_string();
+        let url = rpc_url
+            .unwrap_or(DEFAULT_RPC_URL)
+            .trim_end_matches('/')
+            .to_string();
         Self {
             url,
             client: reqwest::Client::builder()
@@ -36,29 +398,143 @@ impl RpcClient {
         }
     }
 
-    pub async fn get_balance_by_address(&self, address: &str) -> Result<GetBalanceByAddressResponse, RpcError> {
-        let url = format!("{}/addresses/{}/balance", self.url, address);
+    pub async fn get_balance_by_address(
+        &self,
+        address: &str,
+    ) -> Result<GetBalanceByAddressResponse, RpcError> {
+        let balance_response: RestBalanceResponse = self
+            .get_json(&format!("/addresses/{}/balance", address), &[])
+            .await?;
+
+        Ok(GetBalanceByAddressResponse {
+            balance: balance_response.balance,
+        })
+    }
+
+    pub async fn get_utxos_by_address(&self, address: &str) -> Result<Vec<UtxoResponse>, RpcError> {
+        self.get_json(&format!("/addresses/{}/utxos", address), &[])
+            .await
+    }
+
+    /// Returns one page of the address history, newest first.
+    pub async fn get_transactions_by_address(
+        &self,
+        address: &str,
+        page: Page,
+    ) -> Result<Vec<TransactionResponse>, RpcError> {
+        self.get_json(
+            &format!("/addresses/{}/full-transactions", address),
+            &[
+                ("limit", page.limit.to_string()),
+                ("offset", page.offset.to_string()),
+                ("resolve_previous_outpoints", "light".to_string()),
+            ],
+        )
+        .await
+    }
+
+    pub async fn get_transaction_count(&self, address: &str) -> Result<u64, RpcError> {
+        let count: RestCountResponse = self
+            .get_json(&format!("/addresses/{}/transactions-count", address), &[])
+            .await?;
+        Ok(count.total)
+    }
+
+    pub async fn get_transaction(
+        &self,
+        transaction_id: &str,
+    ) -> Result<TransactionResponse, RpcError> {
+        self.get_json(
+            &format!("/transactions/{}", transaction_id),
+            &[
+                ("inputs", "true".to_string()),
+                ("outputs", "true".to_string()),
+                ("resolve_previous_outpoints", "light".to_string()),
+            ],
+        )
+        .await
+    }
+
+    pub async fn get_fee_estimate(&self) -> Result<FeeEstimateResponse, RpcError> {
+        self.get_json("/info/fee-estimate", &[]).await
+    }
+
+    pub async fn get_virtual_blue_score(&self) -> Result<u64, RpcError> {
+        let response: BlueScoreResponse =
+            self.get_json("/info/virtual-chain-blue-score", &[]).await?;
+        Ok(response.blue_score)
+    }
+
+    pub async fn get_virtual_daa_score(&self) -> Result<u64, RpcError> {
+        Ok(self.get_network_info().await?.virtual_daa_score)
+    }
+
+    pub async fn get_past_median_time(&self) -> Result<u64, RpcError> {
+        Ok(self.get_network_info().await?.past_median_time)
+    }
+
+    pub async fn get_network_info(&self) -> Result<NetworkInfoResponse, RpcError> {
+        self.get_json("/info/blockdag", &[]).await
+    }
+
+    /// Submits a signed transaction and returns the id reported by the node.
+    pub async fn submit_transaction(&self, tx: &Transaction) -> Result<String, RpcError> {
+        let request = SubmitTransactionRequest {
+            transaction: SubmitTransaction::from(tx),
+            allow_orphan: false,
+        };
+        let response: SubmitTransactionResponse = self.post_json("/transactions", &request).await?;
+
+        match (response.transaction_id, response.error) {
+            (_, Some(error)) => Err(RpcError::Rpc(error)),
+            (Some(transaction_id), None) => Ok(transaction_id),
+            (None, None) => Err(RpcError::InvalidResponse(
+                "submission returned no transaction id".to_string(),
+            )),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, RpcError> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.url, path))
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| RpcError::Connection(e.to_string()))?;
+        Self::read_json(response).await
+    }
 
-        let response = self.client
-            .get(&url)
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, RpcError> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.url, path))
+            .json(body)
             .send()
             .await
             .map_err(|e| RpcError::Connection(e.to_string()))?;
+        Self::read_json(response).await
+    }
 
+    async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, RpcError> {
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
             return Err(RpcError::Rpc(format!("HTTP {}: {}", status, text)));
         }
 
-        let balance_response: RestBalanceResponse = response
+        response
             .json()
             .await
-            .map_err(|e| RpcError::JsonError(e.to_string()))?;
-
-        Ok(GetBalanceByAddressResponse {
-            balance: balance_response.balance,
-        })
+            .map_err(|e| RpcError::JsonError(e.to_string()))
     }
 }
 
@@ -66,3 +542,231 @@ impl RpcClient {
 struct RestBalanceResponse {
     balance: u64,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RestCountResponse {
+    total: u64,
+}
+
+/// The REST API encodes some 64-bit values as JSON strings and others as
+/// numbers, depending on the endpoint; accept either.
+mod flexible {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+
+    fn parse<E: serde::de::Error>(value: NumberOrString) -> Result<u64, E> {
+        match value {
+            NumberOrString::Number(number) => Ok(number),
+            NumberOrString::String(text) => text.parse().map_err(E::custom),
+        }
+    }
+
+    pub fn u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        parse(NumberOrString::deserialize(deserializer)?)
+    }
+
+    pub fn option_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        Option::<NumberOrString>::deserialize(deserializer)?
+            .map(parse)
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_wallet, TXID};
+    use crate::transaction::{build_transaction, parse_outpoint};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn test_transaction(index: u32) -> Transaction {
+        build_transaction(
+            vec![parse_outpoint(TXID, index).unwrap()],
+            &[(test_wallet(0x42).address(), 42)],
+            0,
+        )
+        .unwrap()
+    }
+
+    /// Serves one HTTP request with `status` and `body`, returning the base
+    /// URL and a handle resolving to the raw request text.
+    async fn serve_once(
+        status: &'static str,
+        body: String,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            // Read the headers and the full body so the client is never reset.
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map(|value| value.trim().parse::<usize>().unwrap())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_utxo_response_to_utxo() {
+        let wallet = test_wallet(0x42);
+        let json = format!(
+            r#"[{{"address":"{}","outpoint":{{"transactionId":"{}","index":1}},
+                "utxoEntry":{{"amount":"150000000","scriptPublicKey":{{"scriptPublicKey":"{}"}},
+                "blockDaaScore":"92481337","isCoinbase":false}}}}]"#,
+            wallet.get_address(),
+            TXID,
+            hex::encode(wallet.script_public_key().script())
+        );
+        let utxos: Vec<UtxoResponse> = serde_json::from_str(&json).unwrap();
+        let utxo = utxos[0].to_utxo().unwrap();
+
+        assert_eq!(utxo.outpoint, parse_outpoint(TXID, 1).unwrap());
+        assert_eq!(utxo.entry.amount, 150_000_000);
+        assert_eq!(utxo.entry.block_daa_score, 92_481_337);
+        assert_eq!(utxo.entry.script_public_key, wallet.script_public_key());
+    }
+
+    #[test]
+    fn test_transaction_and_info_responses() {
+        let tx: TransactionResponse = serde_json::from_str(&format!(
+            r#"{{"transaction_id":"{0}","hash":"{0}","mass":"2036","block_hash":["{0}"],
+                "block_time":1700000000000,"is_accepted":true,"accepting_block_blue_score":123,
+                "inputs":[{{"transaction_id":"{0}","index":0,"previous_outpoint_hash":"{0}",
+                    "previous_outpoint_index":"3","signature_script":"41ab","sig_op_count":"1"}}],
+                "outputs":[{{"transaction_id":"{0}","index":0,"amount":5000,
+                    "script_public_key":"20ab","script_public_key_address":"{1}"}}]}}"#,
+            TXID,
+            test_wallet(0x42).get_address()
+        ))
+        .unwrap();
+        assert_eq!(tx.mass, Some(2036));
+        assert_eq!(tx.inputs[0].previous_outpoint_index, 3);
+        assert_eq!(tx.outputs[0].amount, 5000);
+
+        let info: NetworkInfoResponse = serde_json::from_str(
+            r#"{"networkName":"kaspa-testnet-10","blockCount":"1000","headerCount":"1000",
+                "tipHashes":[],"difficulty":1.5,"pastMedianTime":"1700000000000",
+                "virtualParentHashes":[],"pruningPointHash":"00","virtualDaaScore":"92481337"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.virtual_daa_score, 92_481_337);
+
+        let fees: FeeEstimateResponse = serde_json::from_str(
+            r#"{"priorityBucket":{"feerate":2.0,"estimatedSeconds":0.5},
+                "normalBuckets":[{"feerate":1.0,"estimatedSeconds":1.0}],"lowBuckets":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(fees.normal_buckets[0].feerate, 1.0);
+    }
+
+    #[test]
+    fn test_mempool_entries_for_address() {
+        let wallet = test_wallet(0x42);
+        let other = test_wallet(0x43).address();
+        let incoming = build_transaction(
+            vec![parse_outpoint(TXID, 0).unwrap()],
+            &[(wallet.address(), 4_000), (other.clone(), 1_000)],
+            0,
+        )
+        .unwrap();
+        let outgoing =
+            build_transaction(vec![parse_outpoint(TXID, 1).unwrap()], &[(other, 2_000)], 0)
+                .unwrap();
+
+        let response = MempoolEntriesResponse::from_entries(
+            &wallet.address(),
+            vec![RpcMempoolEntryByAddress {
+                address: wallet.address(),
+                sending: vec![RpcMempoolEntry::new(500, (&outgoing).into(), false)],
+                receiving: vec![RpcMempoolEntry::new(1_000, (&incoming).into(), true)],
+            }],
+        )
+        .unwrap();
+
+        let entry = &response.receiving[0];
+        assert_eq!(entry.transaction_id, incoming.id().to_string());
+        assert_eq!(entry.fee, 1_000);
+        assert_eq!(entry.received, 4_000);
+        assert!(entry.is_orphan);
+        assert_eq!(response.sending[0].received, 0);
+    }
+
+    #[test]
+    fn test_submit_request_matches_transaction() {
+        let tx = test_transaction(2);
+        let request = serde_json::to_value(SubmitTransactionRequest {
+            transaction: SubmitTransaction::from(&tx),
+            allow_orphan: false,
+        })
+        .unwrap();
+        let transaction = &request["transaction"];
+        assert_eq!(
+            transaction["inputs"][0]["previousOutpoint"]["transactionId"],
+            TXID
+        );
+        assert_eq!(transaction["inputs"][0]["previousOutpoint"]["index"], 2);
+        assert_eq!(transaction["outputs"][0]["amount"], 42);
+        assert_eq!(
+            transaction["subnetworkId"],
+            "0000000000000000000000000000000000000000"
+        );
+        assert_eq!(request["allowOrphan"], false);
+    }
+
+    #[tokio::test]
+    async fn test_submit_maps_errors() {
+        let tx = test_transaction(0);
+
+        let (url, request) =
+            serve_once("200 OK", format!(r#"{{"transactionId":"{}"}}"#, TXID)).await;
+        let client = RpcClient::new(Some(&url));
+        assert_eq!(client.submit_transaction(&tx).await.unwrap(), TXID);
+        assert!(request.await.unwrap().starts_with("POST /transactions "));
+
+        let (url, _) = serve_once("200 OK", r#"{"error":"orphan transaction"}"#.to_string()).await;
+        let client = RpcClient::new(Some(&url));
+        assert!(matches!(
+            client.submit_transaction(&tx).await,
+            Err(RpcError::Rpc(message)) if message == "orphan transaction"
+        ));
+
+        let (url, _) = serve_once("404 Not Found", r#"{"detail":"not found"}"#.to_string()).await;
+        let client = RpcClient::new(Some(&url));
+        assert!(matches!(
+            client.get_transaction(TXID).await,
+            Err(RpcError::Rpc(message)) if message.contains("404")
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+use crate::error::{WalletError, WalletResult};
+use kaspa_addresses::{Address, Prefix, Version};
+use kaspa_txscript::opcodes::codes::{
+    Op1, Op16, OpCheckMultiSig, OpCheckMultiSigECDSA, OpCheckMultiSigVerify, OpCheckSig,
+    OpCheckSigECDSA, OpCheckSigVerify, OpData1, OpData75, OpPushData1, OpPushData2, OpPushData4,
+};
+use kaspa_txscript::script_builder::ScriptBuilder;
+
+/// Most keys a multisig opcode can check, which is what it counts for
+/// when the number of keys is not a literal.
+const MAX_MULTISIG_KEYS: u8 = 20;
+
+/// Names of the opcodes besides data pushes and OP_1 to OP_16.
+const OPCODE_NAMES: &[(u8, &str)] = &[
+    (0x00, "OP_0"),
+    (0x4c, "OP_PUSHDATA1"),
+    (0x4d, "OP_PUSHDATA2"),
+    (0x4e, "OP_PUSHDATA4"),
+    (0x4f, "OP_1NEGATE"),
+    (0x50, "OP_RESERVED"),
+    (0x61, "OP_NOP"),
+    (0x62, "OP_VER"),
+    (0x63, "OP_IF"),
+    (0x64, "OP_NOTIF"),
+    (0x65, "OP_VERIF"),
+    (0x66, "OP_VERNOTIF"),
+    (0x67, "OP_ELSE"),
+    (0x68, "OP_ENDIF"),
+    (0x69, "OP_VERIFY"),
+    (0x6a, "OP_RETURN"),
+    (0x6b, "OP_TOALTSTACK"),
+    (0x6c, "OP_FROMALTSTACK"),
+    (0x6d, "OP_2DROP"),
+    (0x6e, "OP_2DUP"),
+    (0x6f, "OP_3DUP"),
+    (0x70, "OP_2OVER"),
+    (0x71, "OP_2ROT"),
+    (0x72, "OP_2SWAP"),
+    (0x73, "OP_IFDUP"),
+    (0x74, "OP_DEPTH"),
+    (0x75, "OP_DROP"),
+    (0x76, "OP_DUP"),
+    (0x77, "OP_NIP"),
+    (0x78, "OP_OVER"),
+    (0x79, "OP_PICK"),
+    (0x7a, "OP_ROLL"),
+    (0x7b, "OP_ROT"),
+    (0x7c, "OP_SWAP"),
+    (0x7d, "OP_TUCK"),
+    (0x7e, "OP_CAT"),
+    (0x7f, "OP_SUBSTR"),
+    (0x80, "OP_LEFT"),
+    (0x81, "OP_RIGHT"),
+    (0x82, "OP_SIZE"),
+    (0x83, "OP_INVERT"),
+    (0x84, "OP_AND"),
+    (0x85, "OP_OR"),
+    (0x86, "OP_XOR"),
+    (0x87, "OP_EQUAL"),
+    (0x88, "OP_EQUALVERIFY"),
+    (0x89, "OP_RESERVED1"),
+    (0x8a, "OP_RESERVED2"),
+    (0x8b, "OP_1ADD"),
+    (0x8c, "OP_1SUB"),
+    (0x8d, "OP_2MUL"),
+    (0x8e, "OP_2DIV"),
+    (0x8f, "OP_NEGATE"),
+    (0x90, "OP_ABS"),
+    (0x91, "OP_NOT"),
+    (0x92, "OP_0NOTEQUAL"),
+    (0x93, "OP_ADD"),
+    (0x94, "OP_SUB"),
+    (0x95, "OP_MUL"),
+    (0x96, "OP_DIV"),
+    (0x97, "OP_MOD"),
+    (0x98, "OP_LSHIFT"),
+    (0x99, "OP_RSHIFT"),
+    (0x9a, "OP_BOOLAND"),
+    (0x9b, "OP_BOOLOR"),
+    (0x9c, "OP_NUMEQUAL"),
+    (0x9d, "OP_NUMEQUALVERIFY"),
+    (0x9e, "OP_NUMNOTEQUAL"),
+    (0x9f, "OP_LESSTHAN"),
+    (0xa0, "OP_GREATERTHAN"),
+    (0xa1, "OP_LESSTHANOREQUAL"),
+    (0xa2, "OP_GREATERTHANOREQUAL"),
+    (0xa3, "OP_MIN"),
+    (0xa4, "OP_MAX"),
+    (0xa5, "OP_WITHIN"),
+    (0xa8, "OP_SHA256"),
+    (0xa9, "OP_CHECKMULTISIGECDSA"),
+    (0xaa, "OP_BLAKE2B"),
+    (0xab, "OP_CHECKSIGECDSA"),
+    (0xac, "OP_CHECKSIG"),
+    (0xad, "OP_CHECKSIGVERIFY"),
+    (0xae, "OP_CHECKMULTISIG"),
+    (0xaf, "OP_CHECKMULTISIGVERIFY"),
+    (0xb0, "OP_CHECKLOCKTIMEVERIFY"),
+    (0xb1, "OP_CHECKSEQUENCEVERIFY"),
+];
+
+/// Alternative names accepted by [`assemble`].
+const OPCODE_ALIASES: &[(u8, &str)] = &[(0x00, "OP_FALSE"), (0x51, "OP_TRUE")];
+
+/// One step of a script: an opcode, or the data a push opcode pushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    Op(u8),
+    Push(&'a [u8]),
+}
+
+/// Splits `script` into its instructions. Fails on a push that runs past
+/// the end of the script.
+pub fn instructions(script: &[u8]) -> WalletResult<Vec<Instruction<'_>>> {
+    let mut instructions = Vec::new();
+    let mut rest = script;
+    while let Some((&op, tail)) = rest.split_first() {
+        let (len, tail) = match op {
+            OpData1..=OpData75 => (op as usize, tail),
+            OpPushData1 => length_prefix::<1>(tail)?,
+            OpPushData2 => length_prefix::<2>(tail)?,
+            OpPushData4 => length_prefix::<4>(tail)?,
+            _ => {
+                instructions.push(Instruction::Op(op));
+                rest = tail;
+                continue;
+            }
+        };
+        if tail.len() < len {
+            return Err(WalletError::InvalidParameters(format!(
+                "Script pushes {} bytes but only {} remain",
+                len,
+                tail.len()
+            )));
+        }
+        let (data, tail) = tail.split_at(len);
+        instructions.push(Instruction::Push(data));
+        rest = tail;
+    }
+    Ok(instructions)
+}
+
+fn length_prefix<const N: usize>(script: &[u8]) -> WalletResult<(usize, &[u8])> {
+    if script.len() < N {
+        return Err(WalletError::InvalidParameters(
+            "Script ends inside a push length".to_string(),
+        ));
+    }
+    let (prefix, rest) = script.split_at(N);
+    let mut bytes = [0u8; 8];
+    bytes[..N].copy_from_slice(prefix);
+    Ok((u64::from_le_bytes(bytes) as usize, rest))
+}
+
+/// Builds a script from assembly such as `OP_SHA256 0x<hash> OP_EQUAL`.
+///
+/// Opcodes are named with or without their `OP_` prefix, in any case.
+/// `0x`-prefixed hex is pushed as data and decimal numbers as script
+/// numbers, both with the smallest push that holds them, so `0x05` and `5`
+/// both become `OP_5`.
+pub fn assemble(text: &str) -> WalletResult<Vec<u8>> {
+    let mut builder = ScriptBuilder::new();
+    for token in text.split_whitespace() {
+        let result = if let Some(data) = token.strip_prefix("0x") {
+            builder.add_data(&hex::decode(data)?)
+        } else if let Ok(number) = token.parse::<i64>() {
+            builder.add_i64(number)
+        } else {
+            let op = opcode(token).ok_or_else(|| {
+                WalletError::InvalidParameters(format!("Unknown opcode {}", token))
+            })?;
+            if (OpData1..=OpPushData4).contains(&op) {
+                return Err(WalletError::InvalidParameters(format!(
+                    "{} needs data; write the data as 0x<hex> instead",
+                    token
+                )));
+            }
+            builder.add_op(op)
+        };
+        result.map_err(|e| {
+            WalletError::InvalidParameters(format!("Cannot add {} to the script: {}", token, e))
+        })?;
+    }
+    Ok(builder.drain())
+}
+
+/// The assembly for `script`, in the form [`assemble`] reads.
+pub fn disassemble(script: &[u8]) -> WalletResult<String> {
+    Ok(instructions(script)?
+        .into_iter()
+        .map(|instruction| match instruction {
+            Instruction::Op(op) => opcode_name(op),
+            Instruction::Push(data) => format!("0x{}", hex::encode(data)),
+        })
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Signature operations `script` performs, counted the way consensus
+/// counts a P2SH redeem script: a multisig counts its number of keys when
+/// that is a literal OP_1 to OP_16, and 20 otherwise. Inputs must declare
+/// exactly this count.
+pub fn sig_op_count(script: &[u8]) -> WalletResult<u8> {
+    let mut count = 0u8;
+    let mut previous = None;
+    for instruction in instructions(script)? {
+        if let Instruction::Op(op) = instruction {
+            count = count.saturating_add(match op {
+                OpCheckSig | OpCheckSigVerify | OpCheckSigECDSA => 1,
+                OpCheckMultiSig | OpCheckMultiSigVerify | OpCheckMultiSigECDSA => match previous {
+                    Some(Instruction::Op(keys @ Op1..=Op16)) => keys - Op1 + 1,
+                    _ => MAX_MULTISIG_KEYS,
+                },
+                _ => 0,
+            });
+        }
+        previous = Some(instruction);
+    }
+    Ok(count)
+}
+
+/// The opcode that checks a signature by the key of the P2PK `address`.
+pub fn check_sig_op(address: &Address) -> u8 {
+    match address.version {
+        Version::PubKeyECDSA => OpCheckSigECDSA,
+        _ => OpCheckSig,
+    }
+}
+
+/// The P2PK address of a `key` that a script checks with `check`; `None`
+/// when the key does not suit the opcode.
+pub fn key_address(key: &[u8], check: u8, prefix: Prefix) -> Option<Address> {
+    let version = match (check, key.len()) {
+        (OpCheckSig, 32) => Version::PubKey,
+        (OpCheckSigECDSA, 33) => Version::PubKeyECDSA,
+        _ => return None,
+    };
+    Some(Address::new(prefix, version, key))
+}
+
+/// Reads a lock time pushed by `ScriptBuilder::add_lock_time`, which is
+/// little-endian as OP_CHECKLOCKTIMEVERIFY reads it rather than a script
+/// number.
+pub fn pushed_lock_time(instruction: Instruction) -> Option<u64> {
+    match instruction {
+        Instruction::Push(bytes) if bytes.len() <= 8 => {
+            let mut value = [0u8; 8];
+            value[..bytes.len()].copy_from_slice(bytes);
+            Some(u64::from_le_bytes(value))
+        }
+        Instruction::Op(op @ Op1..=Op16) => Some((op - Op1 + 1) as u64),
+        _ => None,
+    }
+}
+
+fn opcode_name(op: u8) -> String {
+    match op {
+        Op1..=Op16 => format!("OP_{}", op - Op1 + 1),
+        _ => OPCODE_NAMES
+            .iter()
+            .find(|(value, _)| *value == op)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| format!("OP_UNKNOWN{}", op)),
+    }
+}
+
+fn opcode(name: &str) -> Option<u8> {
+    let upper = name.to_ascii_uppercase();
+    let name = if upper.starts_with("OP_") {
+        upper
+    } else {
+        format!("OP_{}", upper)
+    };
+    if let Some(number) = name
+        .strip_prefix("OP_")
+        .and_then(|number| number.parse::<u8>().ok())
+        .filter(|number| (1..=16).contains(number))
+    {
+        return Some(Op1 + number - 1);
+    }
+    OPCODE_NAMES
+        .iter()
+        .chain(OPCODE_ALIASES)
+        .find(|(_, candidate)| *candidate == name)
+        .map(|(value, _)| *value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kaspa_txscript::multisig_redeem_script;
+
+    #[test]
+    fn test_assemble_and_disassemble() {
+        let hash = "ab".repeat(32);
+        let script = assemble(&format!("OP_SHA256 0x{} op_equal", hash)).unwrap();
+        assert_eq!(script[0], 0xa8);
+        assert_eq!(script[1], 32);
+        assert_eq!(*script.last().unwrap(), 0x87);
+        assert_eq!(
+            disassemble(&script).unwrap(),
+            format!("OP_SHA256 0x{} OP_EQUAL", hash)
+        );
+
+        // Numbers and short data use the smallest push.
+        assert_eq!(
+            assemble("0 5 0x05 16 TRUE OP_FALSE").unwrap(),
+            [0x00, 0x55, 0x55, 0x60, 0x51, 0x00]
+        );
+        assert_eq!(assemble("1000").unwrap(), [0x02, 0xe8, 0x03]);
+        assert_eq!(disassemble(&[0x02, 0xe8, 0x03]).unwrap(), "0xe803");
+
+        let long = vec![7u8; 100];
+        let script = assemble(&format!("0x{}", hex::encode(&long))).unwrap();
+        assert_eq!(&script[..2], [0x4c, 100]);
+        assert_eq!(instructions(&script).unwrap(), [Instruction::Push(&long)]);
+        assert_eq!(assemble(&disassemble(&script).unwrap()).unwrap(), script);
+    }
+
+    #[test]
+    fn test_rejects_bad_scripts() {
+        assert!(assemble("OP_FOO").is_err());
+        assert!(assemble("OP_PUSHDATA1").is_err());
+        assert!(assemble("0xabc").is_err());
+        assert!(disassemble(&[0x05, 1, 2]).is_err());
+        assert!(disassemble(&[0x4d, 1]).is_err());
+        assert_eq!(disassemble(&[0xfc]).unwrap(), "OP_UNKNOWN252");
+    }
+
+    #[test]
+    fn test_sig_op_count() {
+        assert_eq!(sig_op_count(&assemble("OP_CHECKSIG").unwrap()).unwrap(), 1);
+        assert_eq!(
+            sig_op_count(
+                &assemble("OP_IF OP_CHECKSIG OP_ELSE OP_CHECKSIGVERIFY OP_ENDIF").unwrap()
+            )
+            .unwrap(),
+            2
+        );
+        let keys = [[1u8; 32], [2; 32], [3; 32]];
+        let multisig = multisig_redeem_script(keys.iter(), 2).unwrap();
+        assert_eq!(sig_op_count(&multisig).unwrap(), 3);
+        assert_eq!(
+            sig_op_count(&assemble("OP_CHECKMULTISIG").unwrap()).unwrap(),
+            20
+        );
+        // Data that happens to hold a checksig byte is not an operation.
+        assert_eq!(sig_op_count(&assemble("0xac").unwrap()).unwrap(), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Text that must not outlive its use: passwords, passphrases, mnemonic
+/// phrases and hex-encoded keys. The buffer is wiped on drop and `Debug`
+/// never shows the contents.
+#[derive(Clone, Default)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+/// Raw secret bytes such as seeds and decrypted keys, wiped on drop.
+#[derive(Clone)]
+pub struct SecretBytes(Zeroizing<Vec<u8>>);
+
+impl SecretBytes {
+    pub fn new(value: Vec<u8>) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(value: Vec<u8>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes(<redacted {} bytes>)", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str = "d636a23d4f49fe4e0d59fcf7a6c2ab3846ff2d3a54007b3817a11dff770d06ff";
+
+    #[test]
+    fn test_secret_string_debug_is_redacted() {
+        let secret = SecretString::new(KEY_HEX.to_string());
+        let debug = format!("{:?}", secret);
+        assert!(!debug.contains(KEY_HEX));
+        assert!(!debug.contains("d636"));
+        assert_eq!(secret.expose_secret(), KEY_HEX);
+
+        let nested = format!("{:?}", Some(vec![secret.clone()]));
+        assert!(!nested.contains(KEY_HEX));
+    }
+
+    #[test]
+    fn test_secret_bytes_debug_is_redacted() {
+        let bytes = hex::decode(KEY_HEX).unwrap();
+        let secret = SecretBytes::new(bytes.clone());
+        let debug = format!("{:?} {:#?}", secret, secret);
+        assert!(!debug.contains(KEY_HEX));
+        assert!(!debug.contains(&format!("{:?}", bytes)));
+        assert_eq!(secret.expose_secret(), &bytes[..]);
+    }
+}
@@ -0,0 +1,752 @@
+use crate::amount::Amount;
+use crate::coin_selection::{self, CoinSelector, Selection, Strategy};
+use crate::compound;
+use crate::error::{WalletError, WalletResult};
+use crate::mass;
+use crate::node::NodeApi;
+use crate::transaction::{build_signable_transaction, Utxo};
+use crate::wallet::KaspaWallet;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{SignableTransaction, TransactionId, TransactionOutput};
+use kaspa_txscript::pay_to_address_script;
+use std::collections::HashSet;
+use std::fmt;
+
+/// What to pay and how the fee is covered.
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub outputs: Vec<(Address, Amount)>,
+    pub fee_rate: u64,
+    pub strategy: Strategy,
+    /// Spend every UTXO of the wallet without change. The last output's
+    /// amount is ignored: it receives whatever is left after the other
+    /// outputs and the fee.
+    pub send_all: bool,
+    /// Deduct the fee from the outputs in proportion to their amounts
+    /// instead of adding it on top.
+    pub fee_from_recipients: bool,
+}
+
+impl PaymentRequest {
+    pub fn new(outputs: Vec<(Address, Amount)>, fee_rate: u64) -> Self {
+        Self {
+            outputs,
+            fee_rate,
+            strategy: Strategy::default(),
+            send_all: false,
+            fee_from_recipients: false,
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_send_all(mut self, send_all: bool) -> Self {
+        self.send_all = send_all;
+        self
+    }
+
+    pub fn with_fee_from_recipients(mut self, fee_from_recipients: bool) -> Self {
+        self.fee_from_recipients = fee_from_recipients;
+        self
+    }
+}
+
+/// A signed payment ready for review and submission.
+pub struct Payment {
+    pub tx: SignableTransaction,
+    /// Transactions merging inputs that would not fit in `tx` under the
+    /// mass limit. They must be submitted, in order, before `tx`.
+    pub consolidations: Vec<SignableTransaction>,
+    /// Recipients and the amounts they receive, after any fee deduction.
+    pub outputs: Vec<(Address, u64)>,
+    pub change: u64,
+    pub fee: u64,
+    pub fee_from_recipients: bool,
+}
+
+impl Payment {
+    pub fn id(&self) -> TransactionId {
+        self.tx.tx.id()
+    }
+
+    /// Total received by the recipients.
+    pub fn amount(&self) -> u64 {
+        self.outputs.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// Mass consensus charges this payment, which its fee pays for.
+    pub fn mass(&self) -> u64 {
+        mass::transaction_mass(&self.tx).unwrap_or_default()
+    }
+
+    pub fn consolidation_fee(&self) -> u64 {
+        self.consolidations
+            .iter()
+            .map(compound::transaction_fee)
+            .sum()
+    }
+
+    pub fn input_total(&self) -> u64 {
+        self.tx
+            .entries
+            .iter()
+            .flatten()
+            .map(|entry| entry.amount)
+            .sum()
+    }
+}
+
+impl fmt::Display for Payment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, amount) in &self.outputs {
+            writeln!(f, "To:      {} ({})", address, Amount::from_sompi(amount))?;
+        }
+        writeln!(f, "Amount:  {}", Amount::from_sompi(self.amount()))?;
+        writeln!(
+            f,
+            "Fee:     {} ({} grams{})",
+            Amount::from_sompi(self.fee),
+            self.mass(),
+            if self.fee_from_recipients {
+                ", paid by recipients"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(f, "Change:  {}", Amount::from_sompi(self.change))?;
+        writeln!(
+            f,
+            "Inputs:  {} ({})",
+            self.tx.tx.inputs.len(),
+            Amount::from_sompi(self.input_total())
+        )?;
+        if !self.consolidations.is_empty() {
+            writeln!(
+                f,
+                "Merging: {} consolidation transactions first ({} in fees)",
+                self.consolidations.len(),
+                Amount::from_sompi(self.consolidation_fee())
+            )?;
+        }
+        write!(f, "Tx id:   {}", self.id())
+    }
+}
+
+/// Inputs and outputs of a payment before it is signed.
+struct PaymentPlan {
+    inputs: Vec<Utxo>,
+    /// Recipients and the amounts they receive, after any fee deduction.
+    outputs: Vec<(Address, u64)>,
+    change: u64,
+    fee: u64,
+    /// Transactions merging inputs that would not fit under the mass limit.
+    consolidations: Vec<SignableTransaction>,
+}
+
+impl PaymentPlan {
+    fn recipients(&self, change_address: &Address) -> Vec<(Address, u64)> {
+        let mut recipients = self.outputs.clone();
+        if self.change > 0 {
+            recipients.push((change_address.clone(), self.change));
+        }
+        recipients
+    }
+}
+
+/// Builds and signs the payment described by `request` from the wallet's
+/// UTXOs. The fee is the transaction's mass times `fee_rate` sompi per
+/// gram, and transactions over the standard mass limit are rejected. When
+/// the inputs needed would not fit in one transaction, they are merged by a
+/// chain of consolidation transactions first.
+pub async fn build_payment(
+    node: &dyn NodeApi,
+    wallet: &KaspaWallet,
+    request: &PaymentRequest,
+) -> WalletResult<Payment> {
+    let plan = plan_payment(node, &wallet.address(), request, |inputs, target| {
+        compound::compound(wallet, inputs, target, request.fee_rate)
+    })
+    .await?;
+
+    let recipients = plan
+        .recipients(&wallet.address())
+        .into_iter()
+        .map(|(address, amount)| (address.to_string(), amount))
+        .collect();
+    let tx = wallet.create_transaction(plan.inputs, recipients, request.fee_rate)?;
+    Ok(Payment {
+        tx,
+        consolidations: plan.consolidations,
+        outputs: plan.outputs,
+        change: plan.change,
+        fee: plan.fee,
+        fee_from_recipients: request.fee_from_recipients,
+    })
+}
+
+/// Selects inputs for `request` from the UTXOs of `from` and returns the
+/// transaction unsigned, with change back to `from`, for a key held
+/// elsewhere to sign. No private key is involved, so payments that would
+/// need consolidating first are refused.
+pub async fn build_unsigned_payment(
+    node: &dyn NodeApi,
+    from: &Address,
+    request: &PaymentRequest,
+) -> WalletResult<SignableTransaction> {
+    let plan = plan_payment(node, from, request, |inputs, _| {
+        Err(WalletError::Transaction(format!(
+            "The {} inputs needed do not fit in one transaction; compound them first",
+            inputs.len()
+        )))
+    })
+    .await?;
+    build_signable_transaction(plan.inputs, &plan.recipients(from), 0)
+}
+
+/// Selects inputs and settles the fee for `request`. When the selected
+/// inputs do not fit in one transaction, `consolidate` is asked to merge
+/// them down to a number that does and selection starts over.
+async fn plan_payment(
+    node: &dyn NodeApi,
+    from: &Address,
+    request: &PaymentRequest,
+    mut consolidate: impl FnMut(Vec<Utxo>, usize) -> WalletResult<compound::Compound>,
+) -> WalletResult<PaymentPlan> {
+    if request.outputs.is_empty() {
+        return Err(WalletError::InvalidParameters(
+            "Payment has no recipients".to_string(),
+        ));
+    }
+    let mut utxos = coin_selection::spendable_utxos(node, std::slice::from_ref(from)).await?;
+
+    let fee_rate = request.fee_rate;
+    let requested = request
+        .outputs
+        .iter()
+        .try_fold(Amount::ZERO, |total, (_, amount)| {
+            total.checked_add(*amount)
+        })
+        .ok_or_else(|| WalletError::InvalidParameters("Payment total overflows".to_string()))?
+        .sompi();
+    let request_outputs: Vec<(Address, u64)> = request
+        .outputs
+        .iter()
+        .map(|(address, amount)| (address.clone(), amount.sompi()))
+        .collect();
+    let payment_outputs: Vec<_> = request_outputs
+        .iter()
+        .map(|(address, amount)| TransactionOutput::new(*amount, pay_to_address_script(address)))
+        .collect();
+    let change_script = pay_to_address_script(from);
+    // When the recipients pay the fee, inputs only need to cover the
+    // amounts, and change is kept whenever it leaves the transaction
+    // standard.
+    let selection_rate = if request.fee_from_recipients {
+        0
+    } else {
+        fee_rate
+    };
+    let selector = CoinSelector::new(request.strategy, |inputs: &[Utxo], outputs| {
+        let change = (outputs > payment_outputs.len()).then_some(&change_script);
+        mass::estimate_fee_with_change(inputs, &payment_outputs, change, selection_rate)
+    });
+
+    let mut consolidations = Vec::new();
+    loop {
+        let mut outputs = request_outputs.clone();
+        let selection = if request.send_all {
+            send_all_selection(&utxos, &mut outputs)?
+        } else {
+            selector.select(&utxos, requested, outputs.len())?
+        };
+        let mut tx_outputs: Vec<_> = outputs
+            .iter()
+            .map(|(address, amount)| {
+                TransactionOutput::new(*amount, pay_to_address_script(address))
+            })
+            .collect();
+        if selection.has_change() {
+            tx_outputs.push(TransactionOutput::new(
+                selection.change,
+                change_script.clone(),
+            ));
+        }
+
+        let fitting = compound::fitting_inputs(&selection.inputs, &tx_outputs);
+        if fitting == 0 {
+            // Not even one input fits next to the outputs, and merging
+            // cannot change that.
+            return Err(WalletError::MassLimitExceeded {
+                mass: mass::estimate_compute_mass(&selection.inputs[..1], &tx_outputs),
+                limit: mass::MAXIMUM_STANDARD_TRANSACTION_MASS,
+            });
+        }
+        if fitting < selection.inputs.len() {
+            // Merge the selected inputs down to what one transaction can
+            // spend, then select again from the merged outputs, which are
+            // smaller in number but have paid consolidation fees.
+            let merged = consolidate(selection.inputs.clone(), fitting)?;
+            if merged.transactions.is_empty() {
+                return Err(WalletError::MassLimitExceeded {
+                    mass: mass::estimate_compute_mass(&selection.inputs, &tx_outputs),
+                    limit: mass::MAXIMUM_STANDARD_TRANSACTION_MASS,
+                });
+            }
+            let spent: HashSet<_> = selection.inputs.iter().map(|utxo| utxo.outpoint).collect();
+            utxos.retain(|utxo| !spent.contains(&utxo.outpoint));
+            utxos.extend(merged.utxos);
+            consolidations.extend(merged.transactions);
+            continue;
+        }
+
+        let payers: Vec<usize> = if request.fee_from_recipients {
+            (0..outputs.len()).collect()
+        } else if request.send_all {
+            vec![outputs.len() - 1]
+        } else {
+            vec![]
+        };
+        let fee = if payers.is_empty() {
+            selection.fee
+        } else {
+            let change = tx_outputs[outputs.len()..].to_vec();
+            deduct_fee(
+                &selection.inputs,
+                &mut outputs,
+                &change,
+                &payers,
+                selection.fee,
+                fee_rate,
+            )?
+        };
+
+        return Ok(PaymentPlan {
+            inputs: selection.inputs,
+            outputs,
+            change: selection.change,
+            fee,
+            consolidations,
+        });
+    }
+}
+
+/// Selects every UTXO and gives the last output what the others leave.
+fn send_all_selection(utxos: &[Utxo], outputs: &mut [(Address, u64)]) -> WalletResult<Selection> {
+    let available: u64 = utxos.iter().map(|utxo| utxo.entry.amount).sum();
+    let (last, fixed) = outputs.split_last_mut().expect("payment has outputs");
+    let fixed: u64 = fixed.iter().map(|(_, amount)| amount).sum();
+    last.1 = available
+        .checked_sub(fixed)
+        .filter(|rest| *rest > 0)
+        .ok_or(WalletError::InsufficientFunds {
+            required: fixed.saturating_add(1),
+            available,
+        })?;
+    Ok(Selection {
+        inputs: utxos.to_vec(),
+        fee: 0,
+        change: 0,
+    })
+}
+
+/// Lowers the `payers` outputs, in proportion to their amounts, until that
+/// plus the `covered` sompi already left to the fee pays for the
+/// transaction's mass. Returns the total fee.
+///
+/// Smaller outputs raise the storage mass, so this repeats until the
+/// deduction stops growing.
+fn deduct_fee(
+    inputs: &[Utxo],
+    outputs: &mut [(Address, u64)],
+    change: &[TransactionOutput],
+    payers: &[usize],
+    covered: u64,
+    fee_rate: u64,
+) -> WalletResult<u64> {
+    let requested: Vec<u64> = outputs.iter().map(|(_, amount)| *amount).collect();
+    let weights: Vec<u64> = payers.iter().map(|&index| requested[index]).collect();
+    let mut deducted = 0;
+
+    for _ in 0..32 {
+        for (&index, share) in payers.iter().zip(split_proportionally(deducted, &weights)) {
+            outputs[index].1 = requested[index]
+                .checked_sub(share)
+                .filter(|amount| *amount > 0)
+                .ok_or_else(|| {
+                    WalletError::InvalidParameters(format!(
+                        "The fee exceeds the {} sompi sent to {}",
+                        requested[index], outputs[index].0
+                    ))
+                })?;
+        }
+
+        let tx_outputs: Vec<_> = outputs
+            .iter()
+            .map(|(address, amount)| {
+                TransactionOutput::new(*amount, pay_to_address_script(address))
+            })
+            .chain(change.iter().cloned())
+            .collect();
+        let tx_mass = mass::estimate_mass(inputs, &tx_outputs);
+        mass::check_standard_mass(tx_mass)?;
+        let required = mass::fee_for_mass(tx_mass, fee_rate).saturating_sub(covered);
+        if required <= deducted {
+            return Ok(covered + deducted);
+        }
+        deducted = required;
+    }
+    Err(WalletError::Transaction(
+        "Could not settle the fee deducted from the recipients".to_string(),
+    ))
+}
+
+/// Splits `total` in proportion to `weights`. Rounding leftovers go one
+/// sompi at a time to the first entries, so the parts always add up.
+fn split_proportionally(total: u64, weights: &[u64]) -> Vec<u64> {
+    let weight_sum: u128 = weights.iter().map(|weight| *weight as u128).sum();
+    if weight_sum == 0 {
+        return vec![0; weights.len()];
+    }
+    let mut parts: Vec<u64> = weights
+        .iter()
+        .map(|weight| (total as u128 * *weight as u128 / weight_sum) as u64)
+        .collect();
+    let mut leftover = total - parts.iter().sum::<u64>();
+    for part in parts.iter_mut() {
+        if leftover == 0 {
+            break;
+        }
+        *part += 1;
+        leftover -= 1;
+    }
+    parts
+}
+
+/// Submits any consolidation transactions and then the payment itself,
+/// returning the payment's id.
+pub async fn submit_payment(node: &dyn NodeApi, payment: &Payment) -> WalletResult<TransactionId> {
+    for tx in payment.consolidations.iter().chain([&payment.tx]) {
+        node.submit(&tx.tx)
+            .await
+            .map_err(|e| WalletError::Network(format!("Failed to submit transaction: {}", e)))?;
+    }
+    Ok(payment.id())
+}
+
+/// The node's normal-priority fee rate in sompi per gram, rounded up and
+/// never below the minimum relay rate of 1.
+pub async fn default_fee_rate(node: &dyn NodeApi) -> WalletResult<u64> {
+    let estimate = node
+        .fee_estimate()
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to get fee estimate: {}", e)))?;
+    let bucket = estimate
+        .normal_buckets
+        .first()
+        .unwrap_or(&estimate.priority_bucket);
+    Ok((bucket.feerate.ceil() as u64).max(1))
+}
+
+/// `fee_rate` when the user gave one, otherwise the node's default.
+pub async fn resolve_fee_rate(node: &dyn NodeApi, fee_rate: Option<u64>) -> WalletResult<u64> {
+    match fee_rate {
+        Some(fee_rate) => Ok(fee_rate),
+        None => default_fee_rate(node).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::mock_node::{MockNode, NodeCall};
+    use crate::rpc::RpcError;
+    use crate::signer::verify_input;
+    use crate::test_util::{secret_key, test_wallet};
+    use crate::transaction;
+    use secp256k1::{PublicKey, Secp256k1};
+
+    const FEE_RATE: u64 = 1;
+
+    fn single_payment(to: &Address, amount: u64, strategy: Strategy) -> PaymentRequest {
+        PaymentRequest::new(vec![(to.clone(), Amount::from_sompi(amount))], FEE_RATE)
+            .with_strategy(strategy)
+    }
+
+    #[tokio::test]
+    async fn test_build_and_submit_payment() {
+        let wallet = test_wallet(0x42);
+        let recipient = test_wallet(0x43).address();
+        let node = MockNode::new()
+            .with_utxo(&wallet.address(), SOMPI_PER_KAS)
+            .with_utxo(&wallet.address(), 5 * SOMPI_PER_KAS)
+            .with_utxo(&wallet.address(), 2 * SOMPI_PER_KAS);
+
+        let payment = build_payment(
+            &node,
+            &wallet,
+            &single_payment(&recipient, 6 * SOMPI_PER_KAS, Strategy::LargestFirst),
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.tx.tx.inputs.len(), 2);
+        assert_eq!(payment.input_total(), 7 * SOMPI_PER_KAS);
+        assert_eq!(
+            payment.amount() + payment.change + payment.fee,
+            7 * SOMPI_PER_KAS
+        );
+        assert_eq!(payment.tx.tx.outputs[1].value, payment.change);
+        assert_eq!(payment.fee, mass::fee_for_mass(payment.mass(), FEE_RATE));
+        assert!(payment.fee >= transaction::estimate_fee(2, 2, FEE_RATE));
+
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key(0x42));
+        verify_input(&payment.tx, 0, &public_key).unwrap();
+        verify_input(&payment.tx, 1, &public_key).unwrap();
+
+        assert_eq!(submit_payment(&node, &payment).await.unwrap(), payment.id());
+        assert_eq!(node.balance(&recipient).await.unwrap(), 6 * SOMPI_PER_KAS);
+        assert_eq!(
+            node.balance(&wallet.address()).await.unwrap(),
+            SOMPI_PER_KAS + payment.change
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exact_payment_has_no_change() {
+        let wallet = test_wallet(0x42);
+        let fee = transaction::estimate_fee(1, 1, FEE_RATE);
+        let node = MockNode::new().with_utxo(&wallet.address(), SOMPI_PER_KAS + fee);
+
+        let payment = build_payment(
+            &node,
+            &wallet,
+            &single_payment(
+                &test_wallet(0x43).address(),
+                SOMPI_PER_KAS,
+                Strategy::BranchAndBound,
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.change, 0);
+        assert_eq!(payment.fee, fee);
+        assert_eq!(payment.tx.tx.outputs.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_small_change_is_left_to_the_fee() {
+        // 0.01 KAS of change would need a 0.01 KAS storage fee and put the
+        // transaction over the mass limit, so it goes to the fee instead.
+        let wallet = test_wallet(0x42);
+        let node =
+            MockNode::new().with_utxo(&wallet.address(), SOMPI_PER_KAS + SOMPI_PER_KAS / 100);
+
+        let payment = build_payment(
+            &node,
+            &wallet,
+            &single_payment(
+                &test_wallet(0x43).address(),
+                SOMPI_PER_KAS,
+                Strategy::default(),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.change, 0);
+        assert_eq!(payment.fee, SOMPI_PER_KAS / 100);
+    }
+
+    #[tokio::test]
+    async fn test_payment_over_mass_limit_is_rejected() {
+        // A 0.0001 KAS output has a storage mass of 10^8 grams.
+        let wallet = test_wallet(0x42);
+        let node = MockNode::new().with_utxo(&wallet.address(), 10 * SOMPI_PER_KAS);
+
+        let result = build_payment(
+            &node,
+            &wallet,
+            &single_payment(&test_wallet(0x43).address(), 10_000, Strategy::default()),
+        )
+        .await;
+        assert!(matches!(result, Err(WalletError::MassLimitExceeded { .. })));
+        assert!(node.submitted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_payment_over_input_limit_is_chained() {
+        let wallet = test_wallet(0x42);
+        let recipient = test_wallet(0x43).address();
+        let node = MockNode::new();
+        for _ in 0..150 {
+            node.add_utxo(&wallet.address(), SOMPI_PER_KAS);
+        }
+
+        let payment = build_payment(
+            &node,
+            &wallet,
+            &single_payment(&recipient, 120 * SOMPI_PER_KAS, Strategy::LargestFirst),
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.consolidations.len(), 1);
+        assert!(payment.consolidation_fee() > 0);
+        for tx in payment.consolidations.iter().chain([&payment.tx]) {
+            assert!(mass::transaction_mass(tx).unwrap() <= mass::MAXIMUM_STANDARD_TRANSACTION_MASS);
+        }
+
+        assert_eq!(submit_payment(&node, &payment).await.unwrap(), payment.id());
+        assert_eq!(node.submitted().len(), 2);
+        assert_eq!(node.balance(&recipient).await.unwrap(), 120 * SOMPI_PER_KAS);
+        assert_eq!(
+            node.balance(&wallet.address()).await.unwrap(),
+            30 * SOMPI_PER_KAS - payment.fee - payment.consolidation_fee()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_immature_coinbase_is_not_selected() {
+        let wallet = test_wallet(0x42);
+        let node = MockNode::new();
+        node.add_coinbase_utxo(&wallet.address(), 10 * SOMPI_PER_KAS);
+
+        let result = build_payment(
+            &node,
+            &wallet,
+            &single_payment(
+                &test_wallet(0x43).address(),
+                SOMPI_PER_KAS,
+                Strategy::default(),
+            ),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletError::InsufficientFunds { available: 0, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_outputs_over_mass_limit_are_rejected() {
+        // 300 outputs weigh more than the mass limit with a single input,
+        // so consolidating the inputs cannot help.
+        let wallet = test_wallet(0x42);
+        let recipient = test_wallet(0x43).address();
+        let node = MockNode::new().with_utxo(&wallet.address(), 40_000 * SOMPI_PER_KAS);
+
+        let request = PaymentRequest::new(
+            vec![(recipient, Amount::from_sompi(100 * SOMPI_PER_KAS)); 300],
+            FEE_RATE,
+        );
+        let result = build_payment(&node, &wallet, &request).await;
+        assert!(matches!(result, Err(WalletError::MassLimitExceeded { .. })));
+        assert!(node.submitted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_all() {
+        let wallet = test_wallet(0x42);
+        let recipient = test_wallet(0x43).address();
+        let node = MockNode::new()
+            .with_utxo(&wallet.address(), 3 * SOMPI_PER_KAS)
+            .with_utxo(&wallet.address(), 2 * SOMPI_PER_KAS);
+
+        // The amount of the last output is ignored in send-all mode.
+        let request = PaymentRequest::new(vec![(recipient.clone(), Amount::ZERO)], FEE_RATE)
+            .with_send_all(true);
+        let payment = build_payment(&node, &wallet, &request).await.unwrap();
+        assert_eq!(payment.tx.tx.inputs.len(), 2);
+        assert_eq!(payment.change, 0);
+        assert_eq!(payment.amount() + payment.fee, 5 * SOMPI_PER_KAS);
+        assert_eq!(payment.fee, mass::fee_for_mass(payment.mass(), FEE_RATE));
+
+        submit_payment(&node, &payment).await.unwrap();
+        assert_eq!(node.balance(&wallet.address()).await.unwrap(), 0);
+        assert_eq!(node.balance(&recipient).await.unwrap(), payment.amount());
+    }
+
+    #[tokio::test]
+    async fn test_fee_from_recipients() {
+        let wallet = test_wallet(0x42);
+        let alice = test_wallet(0x43).address();
+        let bob = test_wallet(0x44).address();
+        let node = MockNode::new().with_utxo(&wallet.address(), 10 * SOMPI_PER_KAS);
+
+        let request = PaymentRequest::new(
+            vec![
+                (alice, Amount::from_sompi(3 * SOMPI_PER_KAS)),
+                (bob, Amount::from_sompi(SOMPI_PER_KAS)),
+            ],
+            FEE_RATE,
+        )
+        .with_fee_from_recipients(true);
+        let payment = build_payment(&node, &wallet, &request).await.unwrap();
+        assert_eq!(payment.change, 6 * SOMPI_PER_KAS);
+        assert_eq!(payment.amount() + payment.fee, 4 * SOMPI_PER_KAS);
+        assert_eq!(payment.fee, mass::fee_for_mass(payment.mass(), FEE_RATE));
+
+        // Alice asked for three times as much, so she pays three times the fee.
+        let alice_share = 3 * SOMPI_PER_KAS - payment.outputs[0].1;
+        let bob_share = SOMPI_PER_KAS - payment.outputs[1].1;
+        assert!(alice_share.abs_diff(3 * bob_share) <= 3);
+        assert!(payment.to_string().contains("paid by recipients"));
+    }
+
+    #[test]
+    fn test_split_proportionally() {
+        assert_eq!(split_proportionally(10, &[3, 1]), vec![8, 2]);
+        assert_eq!(split_proportionally(7, &[1, 1, 1]), vec![3, 2, 2]);
+        assert_eq!(split_proportionally(0, &[5, 5]), vec![0, 0]);
+        assert_eq!(
+            split_proportionally(u64::MAX, &[u64::MAX, 1])
+                .iter()
+                .sum::<u64>(),
+            u64::MAX
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_funds_and_node_errors() {
+        let wallet = test_wallet(0x42);
+        let recipient = test_wallet(0x43).address();
+        let node = MockNode::new().with_utxo(&wallet.address(), 5_000);
+
+        let result = build_payment(
+            &node,
+            &wallet,
+            &single_payment(&recipient, 5_000, Strategy::default()),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletError::InsufficientFunds {
+                available: 5_000,
+                ..
+            })
+        ));
+
+        node.fail_next(NodeCall::Utxos, RpcError::Connection("down".to_string()));
+        assert!(matches!(
+            build_payment(
+                &node,
+                &wallet,
+                &single_payment(&recipient, 1_000, Strategy::default())
+            )
+            .await,
+            Err(WalletError::Network(_))
+        ));
+        assert!(node.submitted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_default_fee_rate_uses_normal_bucket() {
+        let node = MockNode::new().with_feerate(1.5);
+        assert_eq!(default_fee_rate(&node).await.unwrap(), 2);
+        let node = MockNode::new().with_feerate(0.0);
+        assert_eq!(default_fee_rate(&node).await.unwrap(), 1);
+        assert_eq!(resolve_fee_rate(&node, Some(7)).await.unwrap(), 7);
+        assert_eq!(resolve_fee_rate(&node, None).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,488 @@
+use crate::address::{pay_to_public_key_script, SignatureScheme};
+use crate::error::{WalletError, WalletResult};
+use kaspa_consensus_core::hashing::sighash::{
+    calc_ecdsa_signature_hash, calc_schnorr_signature_hash, SigHashReusedValuesUnsync,
+};
+use kaspa_consensus_core::hashing::sighash_type::{
+    SigHashType, SIG_HASH_ALL, SIG_HASH_ANY_ONE_CAN_PAY, SIG_HASH_NONE, SIG_HASH_SINGLE,
+};
+use kaspa_consensus_core::tx::{ScriptPublicKey, SignableTransaction};
+use kaspa_hashes::Hash;
+use secp256k1::{ecdsa, schnorr, KeyPair, Message, PublicKey, Secp256k1, SecretKey};
+
+/// OP_DATA_65: pushes a 64-byte signature followed by its sighash type byte.
+const OP_DATA_65: u8 = 65;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SigHashMode {
+    #[default]
+    All,
+    None,
+    Single,
+    AllAnyOneCanPay,
+    NoneAnyOneCanPay,
+    SingleAnyOneCanPay,
+}
+
+impl SigHashMode {
+    pub const ALL_MODES: [SigHashMode; 6] = [
+        SigHashMode::All,
+        SigHashMode::None,
+        SigHashMode::Single,
+        SigHashMode::AllAnyOneCanPay,
+        SigHashMode::NoneAnyOneCanPay,
+        SigHashMode::SingleAnyOneCanPay,
+    ];
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SigHashMode::All => SIG_HASH_ALL.to_u8(),
+            SigHashMode::None => SIG_HASH_NONE.to_u8(),
+            SigHashMode::Single => SIG_HASH_SINGLE.to_u8(),
+            SigHashMode::AllAnyOneCanPay => SIG_HASH_ALL.to_u8() | SIG_HASH_ANY_ONE_CAN_PAY.to_u8(),
+            SigHashMode::NoneAnyOneCanPay => {
+                SIG_HASH_NONE.to_u8() | SIG_HASH_ANY_ONE_CAN_PAY.to_u8()
+            }
+            SigHashMode::SingleAnyOneCanPay => {
+                SIG_HASH_SINGLE.to_u8() | SIG_HASH_ANY_ONE_CAN_PAY.to_u8()
+            }
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL_MODES
+            .into_iter()
+            .find(|mode| mode.to_u8() == value)
+    }
+
+    pub fn sighash_type(self) -> SigHashType {
+        SigHashType::from_u8(self.to_u8())
+            .expect("all SigHashMode values are allowed sighash types")
+    }
+}
+
+fn ensure_populated(tx: &SignableTransaction, input_index: usize) -> WalletResult<()> {
+    if input_index >= tx.tx.inputs.len() {
+        return Err(WalletError::Transaction(
+            "Input index out of bounds".to_string(),
+        ));
+    }
+    if !tx.is_fully_populated() {
+        return Err(WalletError::Transaction(
+            "Missing UTXO entries required for signing".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn signature_hash(
+    tx: &SignableTransaction,
+    input_index: usize,
+    scheme: SignatureScheme,
+    mode: SigHashMode,
+) -> WalletResult<Hash> {
+    ensure_populated(tx, input_index)?;
+
+    let reused_values = SigHashReusedValuesUnsync::new();
+    let verifiable = tx.as_verifiable();
+    Ok(match scheme {
+        SignatureScheme::Schnorr => calc_schnorr_signature_hash(
+            &verifiable,
+            input_index,
+            mode.sighash_type(),
+            &reused_values,
+        ),
+        SignatureScheme::Ecdsa => calc_ecdsa_signature_hash(
+            &verifiable,
+            input_index,
+            mode.sighash_type(),
+            &reused_values,
+        ),
+    })
+}
+
+pub fn signature_script(signature: &[u8; 64], mode: SigHashMode) -> Vec<u8> {
+    std::iter::once(OP_DATA_65)
+        .chain(signature.iter().copied())
+        .chain(std::iter::once(mode.to_u8()))
+        .collect()
+}
+
+/// Returns the scheme of the pay-to-public-key script locking an input to
+/// `public_key`, or `None` when the script belongs to someone else.
+pub fn input_scheme(
+    script_public_key: &ScriptPublicKey,
+    public_key: &PublicKey,
+) -> Option<SignatureScheme> {
+    SignatureScheme::ALL
+        .into_iter()
+        .find(|scheme| pay_to_public_key_script(public_key, *scheme) == *script_public_key)
+}
+
+/// Signs input `input_index` without touching the transaction, returning
+/// the bare 64-byte signature.
+pub fn input_signature(
+    tx: &SignableTransaction,
+    input_index: usize,
+    secret_key: &SecretKey,
+    scheme: SignatureScheme,
+    mode: SigHashMode,
+) -> WalletResult<[u8; 64]> {
+    let sig_hash = signature_hash(tx, input_index, scheme, mode)?;
+    let message = Message::from_slice(&sig_hash.as_bytes())?;
+
+    let secp = Secp256k1::new();
+    Ok(match scheme {
+        SignatureScheme::Schnorr => {
+            let keypair = KeyPair::from_secret_key(&secp, secret_key);
+            *secp.sign_schnorr_no_aux_rand(&message, &keypair).as_ref()
+        }
+        SignatureScheme::Ecdsa => secp.sign_ecdsa(&message, secret_key).serialize_compact(),
+    })
+}
+
+pub fn sign_input(
+    tx: &mut SignableTransaction,
+    input_index: usize,
+    secret_key: &SecretKey,
+    scheme: SignatureScheme,
+    mode: SigHashMode,
+) -> WalletResult<()> {
+    let signature = input_signature(tx, input_index, secret_key, scheme, mode)?;
+    tx.tx.inputs[input_index].signature_script = signature_script(&signature, mode);
+    Ok(())
+}
+
+/// Signs every input locked to `secret_key`, picking Schnorr or ECDSA from
+/// the input's script, and checks each signature before moving on. Inputs
+/// owned by other keys are left untouched. Returns the number of inputs
+/// signed.
+pub fn sign_transaction(
+    tx: &mut SignableTransaction,
+    secret_key: &SecretKey,
+    mode: SigHashMode,
+) -> WalletResult<usize> {
+    if !tx.is_fully_populated() {
+        return Err(WalletError::Transaction(
+            "Missing UTXO entries required for signing".to_string(),
+        ));
+    }
+
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::from_secret_key(&secp, secret_key);
+
+    let mut signed = 0;
+    for input_index in 0..tx.tx.inputs.len() {
+        let script_public_key = &tx.entries[input_index]
+            .as_ref()
+            .expect("entries are populated")
+            .script_public_key;
+        if let Some(scheme) = input_scheme(script_public_key, &public_key) {
+            sign_input(tx, input_index, secret_key, scheme, mode)?;
+            verify_input(tx, input_index, &public_key)?;
+            signed += 1;
+        }
+    }
+    Ok(signed)
+}
+
+pub fn verify_input(
+    tx: &SignableTransaction,
+    input_index: usize,
+    public_key: &PublicKey,
+) -> WalletResult<()> {
+    ensure_populated(tx, input_index)?;
+
+    let entry = tx.entries[input_index]
+        .as_ref()
+        .expect("entries are populated");
+    let scheme = input_scheme(&entry.script_public_key, public_key).ok_or_else(|| {
+        WalletError::Transaction(format!(
+            "Input {} is not locked to this public key",
+            input_index
+        ))
+    })?;
+
+    let script = &tx.tx.inputs[input_index].signature_script;
+    if script.len() != 66 || script[0] != OP_DATA_65 {
+        return Err(WalletError::Transaction(format!(
+            "Input {} has no signature",
+            input_index
+        )));
+    }
+
+    let mode = SigHashMode::from_u8(script[65]).ok_or_else(|| {
+        WalletError::Transaction(format!("Invalid sighash type {:#04x}", script[65]))
+    })?;
+    let signature: [u8; 64] = script[1..65].try_into().expect("64 bytes");
+    verify_signature(tx, input_index, public_key, scheme, mode, &signature)
+}
+
+/// Checks a bare 64-byte signature over input `input_index`, whatever
+/// script the input is locked by.
+pub fn verify_signature(
+    tx: &SignableTransaction,
+    input_index: usize,
+    public_key: &PublicKey,
+    scheme: SignatureScheme,
+    mode: SigHashMode,
+    signature: &[u8; 64],
+) -> WalletResult<()> {
+    let sig_hash = signature_hash(tx, input_index, scheme, mode)?;
+    let message = Message::from_slice(&sig_hash.as_bytes())?;
+
+    let secp = Secp256k1::verification_only();
+    match scheme {
+        SignatureScheme::Schnorr => {
+            let signature = schnorr::Signature::from_slice(signature)?;
+            let (xonly, _) = public_key.x_only_public_key();
+            secp.verify_schnorr(&signature, &message, &xonly)?;
+        }
+        SignatureScheme::Ecdsa => {
+            let signature = ecdsa::Signature::from_compact(signature)?;
+            secp.verify_ecdsa(&message, &signature, public_key)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kaspa_consensus_core::subnets::{SubnetworkId, SUBNETWORK_ID_NATIVE};
+    use kaspa_consensus_core::tx::{
+        ScriptPublicKey, ScriptVec, Transaction, TransactionId, TransactionInput,
+        TransactionOutpoint, TransactionOutput, UtxoEntry,
+    };
+    use std::str::FromStr;
+
+    // Transaction and expected hashes are the `test_signature_hash` vectors
+    // from kaspa-consensus-core (hashing/sighash.rs).
+    fn consensus_test_transaction(subnetwork: bool) -> SignableTransaction {
+        let prev_tx_id = TransactionId::from_str(
+            "880eb9819a31821d9d2399e2f35e2433b72637e393d71ecc9b8d0250f49153c3",
+        )
+        .unwrap();
+        let spk_1 = ScriptPublicKey::new(
+            0,
+            ScriptVec::from_vec(
+                hex::decode("208325613d2eeaf7176ac6c670b13c0043156c427438ed72d74b7800862ad884e8ac")
+                    .unwrap(),
+            ),
+        );
+        let spk_2 = ScriptPublicKey::new(
+            0,
+            ScriptVec::from_vec(
+                hex::decode("20fcef4c106cf11135bbd70f02a726a92162d2fb8b22f0469126f800862ad884e8ac")
+                    .unwrap(),
+            ),
+        );
+
+        let inputs = (0..3)
+            .map(|i| {
+                TransactionInput::new(TransactionOutpoint::new(prev_tx_id, i), vec![], i as u64, 0)
+            })
+            .collect();
+        let outputs = vec![
+            TransactionOutput::new(300, spk_2.clone()),
+            TransactionOutput::new(300, spk_1.clone()),
+        ];
+
+        let mut tx = Transaction::new(
+            0,
+            inputs,
+            outputs,
+            1615462089000,
+            SUBNETWORK_ID_NATIVE,
+            0,
+            vec![],
+        );
+        if subnetwork {
+            tx.subnetwork_id = SubnetworkId::from_bytes([
+                1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ]);
+            tx.gas = 250;
+            tx.payload = vec![10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20];
+        }
+
+        let entries = vec![
+            UtxoEntry::new(100, spk_1, 0, false),
+            UtxoEntry::new(200, spk_2.clone(), 0, false),
+            UtxoEntry::new(300, spk_2, 0, false),
+        ];
+        SignableTransaction::with_entries(tx, entries)
+    }
+
+    #[test]
+    fn test_signature_hash_vectors() {
+        let native = consensus_test_transaction(false);
+        let subnetwork = consensus_test_transaction(true);
+
+        let vectors = [
+            (
+                &native,
+                SigHashMode::All,
+                0,
+                "03b7ac6927b2b67100734c3cc313ff8c2e8b3ce3e746d46dd660b706a916b1f5",
+            ),
+            (
+                &native,
+                SigHashMode::AllAnyOneCanPay,
+                0,
+                "24821e466e53ff8e5fa93257cb17bb06131a48be4ef282e87f59d2bdc9afebc2",
+            ),
+            (
+                &native,
+                SigHashMode::None,
+                0,
+                "38ce4bc93cf9116d2e377b33ff8449c665b7b5e2f2e65303c543b9afdaa4bbba",
+            ),
+            (
+                &native,
+                SigHashMode::NoneAnyOneCanPay,
+                0,
+                "06aa9f4239491e07bb2b6bda6b0657b921aeae51e193d2c5bf9e81439cfeafa0",
+            ),
+            (
+                &native,
+                SigHashMode::Single,
+                0,
+                "44a0b407ff7b239d447743dd503f7ad23db5b2ee4d25279bd3dffaf6b474e005",
+            ),
+            (
+                &native,
+                SigHashMode::Single,
+                2,
+                "022ad967192f39d8d5895d243e025ec14cc7a79708c5e364894d4eff3cecb1b0",
+            ),
+            (
+                &native,
+                SigHashMode::SingleAnyOneCanPay,
+                0,
+                "43b20aba775050cf9ba8d5e48fc7ed2dc6c071d23f30382aea58b7c59cfb8ed7",
+            ),
+            (
+                &native,
+                SigHashMode::SingleAnyOneCanPay,
+                2,
+                "846689131fb08b77f83af1d3901076732ef09d3f8fdff945be89aa4300562e5f",
+            ),
+            (
+                &subnetwork,
+                SigHashMode::All,
+                0,
+                "b2f421c933eb7e1a91f1d9e1efa3f120fe419326c0dbac487752189522550e0c",
+            ),
+        ];
+
+        for (tx, mode, input_index, expected) in vectors {
+            let hash = signature_hash(tx, input_index, SignatureScheme::Schnorr, mode).unwrap();
+            assert_eq!(
+                hash.to_string(),
+                expected,
+                "{:?} input {}",
+                mode,
+                input_index
+            );
+        }
+    }
+
+    fn owned_test_transaction(
+        public_key: &PublicKey,
+        scheme: SignatureScheme,
+    ) -> SignableTransaction {
+        let mut tx = consensus_test_transaction(false);
+        for entry in tx.entries.iter_mut().flatten() {
+            entry.script_public_key = pay_to_public_key_script(public_key, scheme);
+        }
+        for input in tx.tx.inputs.iter_mut() {
+            input.sig_op_count = 1;
+        }
+        tx
+    }
+
+    fn test_keys(seed: u8) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        (secret_key, public_key)
+    }
+
+    #[test]
+    fn test_ecdsa_hash_differs_from_schnorr() {
+        let tx = consensus_test_transaction(false);
+        let schnorr = signature_hash(&tx, 0, SignatureScheme::Schnorr, SigHashMode::All).unwrap();
+        let ecdsa = signature_hash(&tx, 0, SignatureScheme::Ecdsa, SigHashMode::All).unwrap();
+        assert_ne!(schnorr, ecdsa);
+    }
+
+    #[test]
+    fn test_sign_and_verify_all_modes() {
+        let (secret_key, public_key) = test_keys(0x11);
+
+        for scheme in SignatureScheme::ALL {
+            for mode in SigHashMode::ALL_MODES {
+                let mut tx = owned_test_transaction(&public_key, scheme);
+                let signed = sign_transaction(&mut tx, &secret_key, mode).unwrap();
+                assert_eq!(signed, tx.tx.inputs.len());
+
+                for input_index in 0..tx.tx.inputs.len() {
+                    let script = &tx.tx.inputs[input_index].signature_script;
+                    assert_eq!(script.len(), 66);
+                    assert_eq!(script[65], mode.to_u8());
+                    verify_input(&tx, input_index, &public_key).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sign_transaction_picks_scheme_per_input() {
+        let (secret_key, public_key) = test_keys(0x44);
+        let mut tx = owned_test_transaction(&public_key, SignatureScheme::Schnorr);
+        tx.entries[1].as_mut().unwrap().script_public_key =
+            pay_to_public_key_script(&public_key, SignatureScheme::Ecdsa);
+
+        sign_transaction(&mut tx, &secret_key, SigHashMode::All).unwrap();
+        for input_index in 0..tx.tx.inputs.len() {
+            verify_input(&tx, input_index, &public_key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_foreign_inputs_left_unsigned() {
+        let (secret_key, _) = test_keys(0x55);
+        let mut tx = consensus_test_transaction(false);
+
+        assert_eq!(
+            sign_transaction(&mut tx, &secret_key, SigHashMode::All).unwrap(),
+            0
+        );
+        assert!(tx
+            .tx
+            .inputs
+            .iter()
+            .all(|input| input.signature_script.is_empty()));
+    }
+
+    #[test]
+    fn test_tampered_output_fails_verification() {
+        let (secret_key, public_key) = test_keys(0x22);
+
+        for scheme in SignatureScheme::ALL {
+            let mut tx = owned_test_transaction(&public_key, scheme);
+            sign_transaction(&mut tx, &secret_key, SigHashMode::All).unwrap();
+            tx.tx.outputs[0].value += 1;
+
+            assert!(verify_input(&tx, 0, &public_key).is_err());
+        }
+    }
+
+    #[test]
+    fn test_missing_entries_rejected() {
+        let (secret_key, _) = test_keys(0x33);
+        let mut tx = consensus_test_transaction(false);
+        tx.entries[1] = None;
+
+        assert!(sign_transaction(&mut tx, &secret_key, SigHashMode::All).is_err());
+    }
+}
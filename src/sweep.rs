@@ -0,0 +1,229 @@
+use crate::address::{self, SignatureScheme};
+use crate::coin_selection;
+use crate::compound;
+use crate::error::{WalletError, WalletResult};
+use crate::mass;
+use crate::node::NodeApi;
+use crate::wallet::KaspaWallet;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{SignableTransaction, TransactionId, TransactionOutput};
+use kaspa_txscript::pay_to_address_script;
+
+/// Signed transactions moving everything a key holds to `destination`.
+/// More than one is needed when the key has more UTXOs than fit under the
+/// mass limit; they are independent and can be submitted in any order.
+/// UTXOs too small to pay for their own batch stay behind.
+pub struct Sweep {
+    pub sources: Vec<Address>,
+    pub destination: Address,
+    pub transactions: Vec<SignableTransaction>,
+    /// Sompi in the UTXOs that were not swept.
+    pub left_behind: u64,
+    pub left_behind_count: usize,
+}
+
+impl Sweep {
+    /// Amount arriving at the destination after fees.
+    pub fn amount(&self) -> u64 {
+        self.transactions
+            .iter()
+            .flat_map(|tx| &tx.tx.outputs)
+            .map(|output| output.value)
+            .sum()
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(compound::transaction_fee)
+            .sum()
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.transactions.iter().map(|tx| tx.tx.inputs.len()).sum()
+    }
+}
+
+/// Both addresses a single key can receive on: Schnorr and ECDSA.
+pub fn source_addresses(source: &KaspaWallet) -> WalletResult<Vec<Address>> {
+    SignatureScheme::ALL
+        .iter()
+        .map(|scheme| {
+            let address =
+                address::generate_address(source.public_key(), source.get_network_name(), *scheme)?;
+            address::parse_address(&address)
+        })
+        .collect()
+}
+
+/// Finds every UTXO of `source`'s key and builds send-max transactions to
+/// `destination`, each paying `fee_rate` sompi per gram of its mass.
+pub async fn build_sweep(
+    node: &dyn NodeApi,
+    source: &KaspaWallet,
+    destination: &Address,
+    fee_rate: u64,
+) -> WalletResult<Sweep> {
+    let sources = source_addresses(source)?;
+    let mut utxos = coin_selection::spendable_utxos(node, &sources).await?;
+    if utxos.is_empty() {
+        return Err(WalletError::InsufficientFunds {
+            required: 1,
+            available: 0,
+        });
+    }
+
+    let script = pay_to_address_script(destination);
+    let sweep_output = [TransactionOutput::new(0, script.clone())];
+    let mut transactions = Vec::new();
+    let mut left_behind = 0;
+    let mut left_behind_count = 0;
+    // Largest first, so that if the last batch cannot pay for itself only
+    // the smallest outputs are left behind.
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.entry.amount));
+
+    while !utxos.is_empty() {
+        let count = compound::fitting_inputs(&utxos, &sweep_output).max(1);
+        let inputs: Vec<_> = utxos.drain(..count.min(utxos.len())).collect();
+        let input_total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+        let fee = mass::estimate_fee_with_change(&inputs, &[], Some(&script), fee_rate);
+        let Some(amount) = input_total
+            .checked_sub(fee)
+            .filter(|amount| *amount >= coin_selection::DUST_THRESHOLD)
+        else {
+            if transactions.is_empty() {
+                return Err(WalletError::InsufficientFunds {
+                    required: fee.saturating_add(coin_selection::DUST_THRESHOLD),
+                    available: input_total,
+                });
+            }
+            // The rest are smaller still, so they cannot pay either.
+            left_behind = input_total + utxos.iter().map(|utxo| utxo.entry.amount).sum::<u64>();
+            left_behind_count = inputs.len() + utxos.len();
+            break;
+        };
+
+        transactions.push(source.create_transaction(
+            inputs,
+            vec![(destination.to_string(), amount)],
+            fee_rate,
+        )?);
+    }
+
+    Ok(Sweep {
+        sources,
+        destination: destination.clone(),
+        transactions,
+        left_behind,
+        left_behind_count,
+    })
+}
+
+pub async fn submit_sweep(node: &dyn NodeApi, sweep: &Sweep) -> WalletResult<Vec<TransactionId>> {
+    let mut ids = Vec::with_capacity(sweep.transactions.len());
+    for tx in &sweep.transactions {
+        ids.push(
+            node.submit(&tx.tx).await.map_err(|e| {
+                WalletError::Network(format!("Failed to submit transaction: {}", e))
+            })?,
+        );
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::mock_node::MockNode;
+    use crate::test_util::{test_wallet, utxo};
+
+    #[test]
+    fn test_source_addresses_cover_both_schemes() {
+        let source = test_wallet(0x51);
+        let addresses = source_addresses(&source).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0], source.address());
+        assert_eq!(
+            addresses[1],
+            test_wallet(0x51)
+                .with_signature_scheme(SignatureScheme::Ecdsa)
+                .address()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_both_address_kinds() {
+        let source = test_wallet(0x51);
+        let ecdsa = test_wallet(0x51)
+            .with_signature_scheme(SignatureScheme::Ecdsa)
+            .address();
+        let destination = test_wallet(0x42).address();
+        let node = MockNode::new()
+            .with_utxo(&source.address(), 3 * SOMPI_PER_KAS)
+            .with_utxo(&ecdsa, 2 * SOMPI_PER_KAS)
+            .with_utxo(&destination, SOMPI_PER_KAS);
+
+        let sweep = build_sweep(&node, &source, &destination, 1).await.unwrap();
+        assert_eq!(sweep.transactions.len(), 1);
+        assert_eq!(sweep.input_count(), 2);
+        assert_eq!(sweep.amount() + sweep.fee(), 5 * SOMPI_PER_KAS);
+        let tx = &sweep.transactions[0];
+        assert_eq!(
+            sweep.fee(),
+            mass::fee_for_mass(mass::transaction_mass(tx).unwrap(), 1)
+        );
+
+        submit_sweep(&node, &sweep).await.unwrap();
+        assert_eq!(node.balance(&source.address()).await.unwrap(), 0);
+        assert_eq!(node.balance(&ecdsa).await.unwrap(), 0);
+        assert_eq!(
+            node.balance(&destination).await.unwrap(),
+            SOMPI_PER_KAS + sweep.amount()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_splits_and_rejects_empty_keys() {
+        let source = test_wallet(0x51);
+        let destination = test_wallet(0x42).address();
+        let node = MockNode::new();
+        assert!(matches!(
+            build_sweep(&node, &source, &destination, 1).await,
+            Err(WalletError::InsufficientFunds { available: 0, .. })
+        ));
+
+        for _ in 0..100 {
+            node.add_utxo(&source.address(), SOMPI_PER_KAS);
+        }
+        let sweep = build_sweep(&node, &source, &destination, 1).await.unwrap();
+        assert_eq!(sweep.transactions.len(), 2);
+        assert_eq!(sweep.input_count(), 100);
+        assert_eq!(sweep.left_behind, 0);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_reports_dust_left_behind() {
+        let source = test_wallet(0x51);
+        let destination = test_wallet(0x42).address();
+        let full: Vec<_> = (0..100)
+            .map(|index| utxo(index, SOMPI_PER_KAS, source.script_public_key()))
+            .collect();
+        let output = TransactionOutput::new(0, pay_to_address_script(&destination));
+        let per_batch = compound::fitting_inputs(&full, &[output]);
+
+        // One full batch, then a batch of outputs too small to pay a fee.
+        let node = MockNode::new();
+        for _ in 0..per_batch {
+            node.add_utxo(&source.address(), SOMPI_PER_KAS);
+        }
+        node.add_utxo(&source.address(), 1_000);
+        node.add_utxo(&source.address(), 1_000);
+
+        let sweep = build_sweep(&node, &source, &destination, 1).await.unwrap();
+        assert_eq!(sweep.transactions.len(), 1);
+        assert_eq!(sweep.input_count(), per_batch);
+        assert_eq!(sweep.left_behind, 2_000);
+        assert_eq!(sweep.left_behind_count, 2);
+    }
+}
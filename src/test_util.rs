@@ -0,0 +1,25 @@
+use crate::network::NetworkConfig;
+use crate::transaction::{parse_outpoint, Utxo};
+use crate::wallet::KaspaWallet;
+use kaspa_consensus_core::tx::{ScriptPublicKey, UtxoEntry};
+use secp256k1::SecretKey;
+
+/// Id of the funding transaction behind outpoints built by hand.
+pub const TXID: &str = "880eb9819a31821d9d2399e2f35e2433b72637e393d71ecc9b8d0250f49153c3";
+
+/// A testnet-10 wallet whose secret key is `byte` repeated.
+pub fn test_wallet(byte: u8) -> KaspaWallet {
+    KaspaWallet::new(secret_key(byte), NetworkConfig::testnet10())
+}
+
+pub fn secret_key(byte: u8) -> SecretKey {
+    SecretKey::from_slice(&[byte; 32]).unwrap()
+}
+
+/// Output `index` of [`TXID`], holding `amount` sompi locked to `script`.
+pub fn utxo(index: u32, amount: u64, script: ScriptPublicKey) -> Utxo {
+    Utxo::new(
+        parse_outpoint(TXID, index).unwrap(),
+        UtxoEntry::new(amount, script, 0, false),
+    )
+}
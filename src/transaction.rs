@@ -1,152 +1,282 @@
-use crate::error::WalletError;
-use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
-use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TxInput {
-    pub txid: String,
-    pub vout: u32,
-    pub signature: Option<String>,
-    pub public_key: Option<String>,
+use crate::error::{WalletError, WalletResult};
+use crate::mass;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::constants::{LOCK_TIME_THRESHOLD, MAX_TX_IN_SEQUENCE_NUM};
+use kaspa_consensus_core::subnets::SUBNETWORK_ID_NATIVE;
+use kaspa_consensus_core::tx::{
+    SignableTransaction, Transaction, TransactionId, TransactionInput, TransactionOutpoint,
+    TransactionOutput, UtxoEntry,
+};
+use kaspa_txscript::pay_to_address_script;
+use std::fmt;
+use std::str::FromStr;
+
+pub const TX_VERSION: u16 = 0;
+pub const DEFAULT_SEQUENCE: u64 = 0;
+pub const DEFAULT_SIG_OP_COUNT: u8 = 1;
+/// Sequence for inputs of a transaction that relies on its `lock_time`.
+/// An input at `MAX_TX_IN_SEQUENCE_NUM` would let the transaction ignore
+/// its lock time and fail OP_CHECKLOCKTIMEVERIFY; this value stays below
+/// it while keeping the bit that disables relative lock times.
+pub const LOCK_TIME_SEQUENCE: u64 = MAX_TX_IN_SEQUENCE_NUM - 1;
+
+/// Script length of a Schnorr P2PK output: OP_DATA_32, the key, OP_CHECKSIG.
+const P2PK_SCRIPT_LEN: u64 = 34;
+
+#[derive(Debug, Clone)]
+pub struct Utxo {
+    pub outpoint: TransactionOutpoint,
+    pub entry: UtxoEntry,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TxOutput {
-    pub address: String,
-    pub amount: u64,
+impl Utxo {
+    pub fn new(outpoint: TransactionOutpoint, entry: UtxoEntry) -> Self {
+        Self { outpoint, entry }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub version: u32,
-    pub inputs: Vec<TxInput>,
-    pub outputs: Vec<TxOutput>,
-    pub lock_time: u32,
+/// A transaction `lock_time`: a DAA score below `LOCK_TIME_THRESHOLD`, a
+/// Unix time in milliseconds from it on. Zero means no lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockTime {
+    DaaScore(u64),
+    Timestamp(u64),
 }
 
-impl Transaction {
-    pub fn new() -> Self {
-        Self {
-            version: 1,
-            inputs: Vec::new(),
-            outputs: Vec::new(),
-            lock_time: 0,
+impl LockTime {
+    pub fn from_value(value: u64) -> Self {
+        if value < LOCK_TIME_THRESHOLD {
+            LockTime::DaaScore(value)
+        } else {
+            LockTime::Timestamp(value)
         }
     }
 
-    pub fn add_input(&mut self, txid: String, vout: u32) {
-        self.inputs.push(TxInput {
-            txid,
-            vout,
-            signature: None,
-            public_key: None,
-        });
-    }
-
-    pub fn add_output(&mut self, address: String, amount: u64) {
-        self.outputs.push(TxOutput { address, amount });
+    pub fn value(self) -> u64 {
+        match self {
+            LockTime::DaaScore(value) | LockTime::Timestamp(value) => value,
+        }
     }
 
-    pub fn serialize(&self) -> Result<Vec<u8>, WalletError> {
-        let mut buffer = Vec::new();
-
-        buffer.extend_from_slice(&self.version.to_le_bytes());
-
-        buffer.push(self.inputs.len() as u8);
-        for input in &self.inputs {
-            let txid_bytes = hex::decode(&input.txid)?;
-            buffer.extend_from_slice(&txid_bytes);
-            buffer.extend_from_slice(&input.vout.to_le_bytes());
+    /// Reads `daa:<score>`, `time:<unix seconds>` or a bare DAA score.
+    pub fn parse(text: &str) -> WalletResult<Self> {
+        let text = text.trim();
+        let invalid = || {
+            WalletError::InvalidParameters(format!(
+                "Invalid lock time {}; use daa:<score> or time:<unix seconds>",
+                text
+            ))
+        };
+        let lock_time = if let Some(seconds) = text.strip_prefix("time:") {
+            let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+            LockTime::Timestamp(seconds.checked_mul(1000).ok_or_else(invalid)?)
+        } else {
+            let score = text.strip_prefix("daa:").unwrap_or(text);
+            LockTime::DaaScore(score.parse().map_err(|_| invalid())?)
+        };
+        if lock_time.value() == 0 || LockTime::from_value(lock_time.value()) != lock_time {
+            return Err(invalid());
         }
+        Ok(lock_time)
+    }
 
-        buffer.push(self.outputs.len() as u8);
-        for output in &self.outputs {
-            let address_bytes = output.address.as_bytes();
-            buffer.push(address_bytes.len() as u8);
-            buffer.extend_from_slice(address_bytes);
-            buffer.extend_from_slice(&output.amount.to_le_bytes());
+    /// Whether a transaction with this lock time can be accepted once the
+    /// virtual DAA score is `daa_score` and the past median time is
+    /// `past_median_time`. The wall clock runs ahead of the latter, so it
+    /// cannot stand in for it.
+    pub fn is_reached(self, daa_score: u64, past_median_time: u64) -> bool {
+        match self {
+            LockTime::DaaScore(score) => score < daa_score,
+            LockTime::Timestamp(time) => time < past_median_time,
         }
-
-        buffer.extend_from_slice(&self.lock_time.to_le_bytes());
-
-        Ok(buffer)
     }
+}
 
-    pub fn get_signature_hash(&self, input_index: usize) -> Result<Vec<u8>, WalletError> {
-        let mut tx_copy = self.clone();
-
-        for (i, input) in tx_copy.inputs.iter_mut().enumerate() {
-            if i == input_index {
-                input.signature = None;
-                input.public_key = None;
-            } else {
-                input.signature = Some("dummy".to_string());
-                input.public_key = Some("dummy".to_string());
-            }
+impl fmt::Display for LockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockTime::DaaScore(score) => write!(f, "DAA score {}", score),
+            LockTime::Timestamp(time) => write!(f, "Unix time {}", time / 1000),
         }
+    }
+}
 
-        let serialized = tx_copy.serialize()?;
+#[cfg(test)]
+pub fn parse_outpoint(txid: &str, index: u32) -> WalletResult<TransactionOutpoint> {
+    let transaction_id = TransactionId::from_str(txid.trim()).map_err(|e| {
+        WalletError::InvalidParameters(format!("Invalid transaction id {}: {}", txid, e))
+    })?;
+    Ok(TransactionOutpoint::new(transaction_id, index))
+}
 
-        let mut hasher = Sha256::new();
-        hasher.update(&serialized);
-        Ok(hasher.finalize().to_vec())
-    }
+pub fn build_input(outpoint: TransactionOutpoint) -> TransactionInput {
+    TransactionInput::new(outpoint, vec![], DEFAULT_SEQUENCE, DEFAULT_SIG_OP_COUNT)
+}
 
-    pub fn sign_input(
-        &mut self,
-        input_index: usize,
-        secret_key: &SecretKey,
-        public_key: &PublicKey,
-    ) -> Result<(), WalletError> {
-        if input_index >= self.inputs.len() {
-            return Err(crate::error::WalletError::Transaction(
-                "Input index out of bounds".to_string(),
-            ));
-        }
+pub fn build_output(address: &Address, amount: u64) -> TransactionOutput {
+    TransactionOutput::new(amount, pay_to_address_script(address))
+}
 
-        let signature_hash = self.get_signature_hash(input_index)?;
-        let message = Message::from_digest_slice(&signature_hash)?;
+pub fn build_transaction(
+    inputs: Vec<TransactionOutpoint>,
+    outputs: &[(Address, u64)],
+    lock_time: u64,
+) -> WalletResult<Transaction> {
+    if inputs.is_empty() {
+        return Err(WalletError::Transaction(
+            "Transaction has no inputs".to_string(),
+        ));
+    }
+    if outputs.is_empty() {
+        return Err(WalletError::Transaction(
+            "Transaction has no outputs".to_string(),
+        ));
+    }
 
-        let secp = Secp256k1::new();
-        let signature = secp.sign_ecdsa(&message, secret_key);
+    let inputs = inputs.into_iter().map(build_input).collect();
+    let outputs = outputs
+        .iter()
+        .map(|(address, amount)| build_output(address, *amount))
+        .collect();
 
-        self.inputs[input_index].signature = Some(hex::encode(signature.serialize_der()));
-        self.inputs[input_index].public_key = Some(hex::encode(public_key.serialize()));
+    Ok(Transaction::new(
+        TX_VERSION,
+        inputs,
+        outputs,
+        lock_time,
+        SUBNETWORK_ID_NATIVE,
+        0,
+        vec![],
+    ))
+}
 
-        Ok(())
-    }
+pub fn build_signable_transaction(
+    utxos: Vec<Utxo>,
+    outputs: &[(Address, u64)],
+    lock_time: u64,
+) -> WalletResult<SignableTransaction> {
+    let (outpoints, entries): (Vec<_>, Vec<_>) = utxos
+        .into_iter()
+        .map(|utxo| (utxo.outpoint, utxo.entry))
+        .unzip();
+    let tx = build_transaction(outpoints, outputs, lock_time)?;
+    Ok(SignableTransaction::with_entries(tx, entries))
+}
 
-    pub fn estimate_fee(&self, fee_rate: u64) -> u64 {
-        let base_size = 10;
-        let input_size = 32 + 4 + 73 + 33;
-        let output_size = 8 + 1 + 34;
+/// Fee for a P2PK transaction of the given shape at `fee_rate` sompi per
+/// gram. Only compute mass is counted; `mass::estimate_fee_with_change`
+/// also prices storage mass once the amounts are known.
+pub fn estimate_fee(input_count: usize, output_count: usize, fee_rate: u64) -> u64 {
+    let header_size = mass::transaction_serialized_size(&Transaction::new(
+        TX_VERSION,
+        vec![],
+        vec![],
+        0,
+        SUBNETWORK_ID_NATIVE,
+        0,
+        vec![],
+    ));
+    let input_mass = (32 + 4 + 8 + mass::SIGNATURE_SCRIPT_LEN as u64 + 8) * mass::MASS_PER_TX_BYTE
+        + DEFAULT_SIG_OP_COUNT as u64 * mass::MASS_PER_SIG_OP;
+    let output_mass = (8 + 2 + 8 + P2PK_SCRIPT_LEN) * mass::MASS_PER_TX_BYTE
+        + (2 + P2PK_SCRIPT_LEN) * mass::MASS_PER_SCRIPT_PUB_KEY_BYTE;
 
-        let total_size =
-            base_size + (self.inputs.len() * input_size) + (self.outputs.len() * output_size);
-        (total_size as u64 + 999) / 1000 * fee_rate
-    }
+    let total_mass = header_size * mass::MASS_PER_TX_BYTE
+        + input_count as u64 * input_mass
+        + output_count as u64 * output_mass;
+    mass::fee_for_mass(total_mass, fee_rate)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TXID;
+    use kaspa_addresses::{Prefix, Version};
 
-    #[test]
-    fn test_transaction_creation() {
-        let tx = Transaction::new();
-        assert_eq!(tx.version, 1);
-        assert!(tx.inputs.is_empty());
-        assert!(tx.outputs.is_empty());
+    fn test_address() -> Address {
+        Address::new(Prefix::Testnet, Version::PubKey, &[7u8; 32])
     }
 
     #[test]
-    fn test_add_input_output() {
-        let mut tx = Transaction::new();
-        tx.add_input("abc123".to_string(), 0);
-        tx.add_output("kaspa:xyz".to_string(), 1000);
+    fn test_transaction_creation() {
+        let outpoint = parse_outpoint(TXID, 1).unwrap();
+        let address = test_address();
+        let tx = build_transaction(vec![outpoint], &[(address.clone(), 1000)], 0).unwrap();
 
+        assert_eq!(tx.version, TX_VERSION);
+        assert_eq!(tx.subnetwork_id, SUBNETWORK_ID_NATIVE);
+        assert_eq!(tx.gas, 0);
         assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].previous_outpoint, outpoint);
+        assert_eq!(tx.inputs[0].sequence, DEFAULT_SEQUENCE);
+        assert!(tx.inputs[0].signature_script.is_empty());
         assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.outputs[0].value, 1000);
+        assert_eq!(
+            tx.outputs[0].script_public_key,
+            pay_to_address_script(&address)
+        );
+    }
+
+    #[test]
+    fn test_signable_transaction_carries_entries() {
+        let address = test_address();
+        let entry = UtxoEntry::new(2000, pay_to_address_script(&address), 10, false);
+        let utxo = Utxo::new(parse_outpoint(TXID, 0).unwrap(), entry.clone());
+
+        let tx = build_signable_transaction(vec![utxo], &[(address, 1000)], 0).unwrap();
+        assert!(tx.is_fully_populated());
+        assert_eq!(tx.entries[0], Some(entry));
+    }
+
+    #[test]
+    fn test_invalid_outpoint() {
+        assert!(parse_outpoint("abc123", 0).is_err());
+    }
+
+    #[test]
+    fn test_empty_transaction_rejected() {
+        let outpoint = parse_outpoint(TXID, 0).unwrap();
+        assert!(build_transaction(vec![], &[(test_address(), 1000)], 0).is_err());
+        assert!(build_transaction(vec![outpoint], &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_lock_time() {
+        assert_eq!(
+            LockTime::parse("daa:1000").unwrap(),
+            LockTime::DaaScore(1000)
+        );
+        assert_eq!(LockTime::parse("1000").unwrap(), LockTime::DaaScore(1000));
+        let time = LockTime::parse("time:1700000000").unwrap();
+        assert_eq!(time, LockTime::Timestamp(1_700_000_000_000));
+        assert_eq!(LockTime::from_value(time.value()), time);
+        assert_eq!(time.to_string(), "Unix time 1700000000");
+
+        assert!(LockTime::parse("0").is_err());
+        assert!(LockTime::parse("time:1000").is_err());
+        assert!(LockTime::parse(&format!("daa:{}", LOCK_TIME_THRESHOLD)).is_err());
+        assert!(LockTime::parse("soon").is_err());
+
+        assert!(!LockTime::DaaScore(1000).is_reached(1000, u64::MAX));
+        assert!(LockTime::DaaScore(1000).is_reached(1001, 0));
+        assert!(!time.is_reached(u64::MAX, 1_700_000_000_000));
+    }
+
+    #[test]
+    fn test_estimate_fee_matches_compute_mass() {
+        let address = test_address();
+        let outpoints = (0..3)
+            .map(|index| parse_outpoint(TXID, index).unwrap())
+            .collect();
+        let mut tx =
+            build_transaction(outpoints, &[(address.clone(), 1_000), (address, 2_000)], 0).unwrap();
+        for input in &mut tx.inputs {
+            input.signature_script = vec![0; mass::SIGNATURE_SCRIPT_LEN];
+        }
+
+        assert_eq!(estimate_fee(3, 2, 1), mass::compute_mass(&tx));
+        assert_eq!(estimate_fee(1, 1, 1), 94 + 118 + 1000 + 52 + 360);
+        assert_eq!(estimate_fee(3, 2, 2), 2 * estimate_fee(3, 2, 1));
     }
 }
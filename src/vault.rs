@@ -0,0 +1,368 @@
+use crate::address::{address_from_redeem_script, SignatureScheme};
+use crate::error::{WalletError, WalletResult};
+use crate::network::NetworkConfig;
+use crate::node::NodeApi;
+use crate::p2sh::{ScriptSpend, UnlockItem};
+use crate::script::{self, Instruction};
+use crate::transaction::LockTime;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_txscript::opcodes::codes::OpCheckLockTimeVerify;
+use kaspa_txscript::pay_to_address_script;
+use kaspa_txscript::script_builder::{ScriptBuilder, ScriptBuilderResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Savings that only `owner` can spend, and only in a transaction whose
+/// lock time has reached `lock_time`:
+///
+/// ```text
+/// <lock time> OP_CHECKLOCKTIMEVERIFY <owner key> OP_CHECKSIG
+/// ```
+///
+/// Consensus only accepts such a transaction once its lock time has
+/// passed, so the funds stay put until then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vault {
+    owner: Address,
+    lock_time: LockTime,
+}
+
+impl Vault {
+    pub fn new(owner: Address, lock_time: LockTime) -> WalletResult<Self> {
+        if SignatureScheme::from_address_version(owner.version).is_none() {
+            return Err(WalletError::InvalidParameters(format!(
+                "The vault owner {} must be an address that pays to a public key",
+                owner
+            )));
+        }
+        if lock_time.value() == 0 {
+            return Err(WalletError::InvalidParameters(
+                "A vault needs a lock time".to_string(),
+            ));
+        }
+        Ok(Self { owner, lock_time })
+    }
+
+    /// Reads a script built by [`Vault::redeem_script`]; `None` for any
+    /// other script.
+    pub fn parse(redeem_script: &[u8], prefix: Prefix) -> Option<Self> {
+        use Instruction::{Op, Push};
+
+        let instructions = script::instructions(redeem_script).ok()?;
+        if instructions.len() != 4 {
+            return None;
+        }
+        let (Op(OpCheckLockTimeVerify), Push(key), Op(check)) =
+            (instructions[1], instructions[2], instructions[3])
+        else {
+            return None;
+        };
+        let vault = Self::new(
+            script::key_address(key, check, prefix)?,
+            LockTime::from_value(script::pushed_lock_time(instructions[0])?),
+        )
+        .ok()?;
+        (vault.redeem_script().ok()? == redeem_script).then_some(vault)
+    }
+
+    pub fn owner(&self) -> &Address {
+        &self.owner
+    }
+
+    pub fn lock_time(&self) -> LockTime {
+        self.lock_time
+    }
+
+    pub fn redeem_script(&self) -> WalletResult<Vec<u8>> {
+        self.build_script()
+            .map_err(|e| WalletError::Transaction(format!("Vault script error: {}", e)))
+    }
+
+    fn build_script(&self) -> ScriptBuilderResult<Vec<u8>> {
+        Ok(ScriptBuilder::new()
+            .add_lock_time(self.lock_time.value())?
+            .add_op(OpCheckLockTimeVerify)?
+            .add_data(&self.owner.payload)?
+            .add_op(script::check_sig_op(&self.owner))?
+            .drain())
+    }
+
+    pub fn address(&self, prefix: Prefix) -> WalletResult<Address> {
+        Ok(address_from_redeem_script(&self.redeem_script()?, prefix))
+    }
+
+    /// The owner's spend, whose transaction carries the vault's lock time.
+    pub fn spend(&self) -> WalletResult<ScriptSpend> {
+        Ok(
+            ScriptSpend::new(self.redeem_script()?, vec![UnlockItem::Signature])
+                .with_lock_time(self.lock_time),
+        )
+    }
+}
+
+/// What a vault holds.
+#[derive(Debug, Clone)]
+pub struct VaultBalance {
+    pub vault: Vault,
+    pub address: Address,
+    pub amount: u64,
+    pub utxo_count: usize,
+}
+
+/// Looks up the funds in each of `vaults`.
+pub async fn vault_balances(
+    node: &dyn NodeApi,
+    vaults: &[Vault],
+    prefix: Prefix,
+) -> WalletResult<Vec<VaultBalance>> {
+    let addresses = vaults
+        .iter()
+        .map(|vault| vault.address(prefix))
+        .collect::<WalletResult<Vec<_>>>()?;
+    let utxos = node
+        .utxos(&addresses)
+        .await
+        .map_err(|e| WalletError::Network(format!("Failed to fetch UTXOs: {}", e)))?;
+
+    Ok(vaults
+        .iter()
+        .zip(addresses)
+        .map(|(vault, address)| {
+            let script = pay_to_address_script(&address);
+            let held: Vec<_> = utxos
+                .iter()
+                .filter(|utxo| utxo.entry.script_public_key == script)
+                .collect();
+            VaultBalance {
+                vault: vault.clone(),
+                amount: held.iter().map(|utxo| utxo.entry.amount).sum(),
+                utxo_count: held.len(),
+                address,
+            }
+        })
+        .collect())
+}
+
+/// The vaults a wallet has created. A vault's address is only a script
+/// hash, so the script itself has to be kept to find or spend the funds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VaultBook {
+    pub vaults: Vec<VaultRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultRecord {
+    pub network: String,
+    pub address: String,
+    /// Redeem script as hex.
+    pub redeem_script: String,
+}
+
+impl VaultBook {
+    /// Reads the book at `path`, or an empty one if there is no file yet.
+    pub fn load(path: impl AsRef<Path>) -> WalletResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| WalletError::Io(format!("Invalid vault file {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> WalletResult<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| WalletError::Io(format!("Failed to serialize vaults: {}", e)))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Records `vault` unless it is already in the book.
+    pub fn add(&mut self, network: &NetworkConfig, vault: &Vault) -> WalletResult<()> {
+        let record = VaultRecord {
+            network: network.name.clone(),
+            address: vault.address(network.address_prefix())?.to_string(),
+            redeem_script: hex::encode(vault.redeem_script()?),
+        };
+        if !self.vaults.contains(&record) {
+            self.vaults.push(record);
+        }
+        Ok(())
+    }
+
+    /// The recorded vaults on `network`.
+    pub fn vaults(&self, network: &NetworkConfig) -> WalletResult<Vec<Vault>> {
+        self.vaults
+            .iter()
+            .filter(|record| record.network == network.name)
+            .map(|record| {
+                let redeem_script = hex::decode(&record.redeem_script)?;
+                Vault::parse(&redeem_script, network.address_prefix()).ok_or_else(|| {
+                    WalletError::InvalidParameters(format!(
+                        "The recorded script for vault {} is not a vault",
+                        record.address
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    pub fn find(&self, network: &NetworkConfig, address: &Address) -> WalletResult<Vault> {
+        for vault in self.vaults(network)? {
+            if vault.address(address.prefix)? == *address {
+                return Ok(vault);
+            }
+        }
+        Err(WalletError::InvalidParameters(format!(
+            "Vault {} is not recorded on {}",
+            address, network.name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::SOMPI_PER_KAS;
+    use crate::mock_node::{execute_scripts, MockNode};
+    use crate::p2sh;
+    use crate::test_util::test_wallet;
+    use crate::transaction::LOCK_TIME_SEQUENCE;
+    use kaspa_consensus_core::constants::{MAX_TX_IN_SEQUENCE_NUM, SEQUENCE_LOCK_TIME_DISABLED};
+
+    const UNLOCK_SCORE: u64 = 5_000;
+
+    #[test]
+    fn test_parse_vault() {
+        for scheme in SignatureScheme::ALL {
+            let owner = test_wallet(0x71).with_signature_scheme(scheme).address();
+            for lock_time in [
+                LockTime::DaaScore(7),
+                LockTime::DaaScore(UNLOCK_SCORE),
+                LockTime::Timestamp(1_700_000_000_000),
+            ] {
+                let vault = Vault::new(owner.clone(), lock_time).unwrap();
+                let script = vault.redeem_script().unwrap();
+                assert_eq!(Vault::parse(&script, Prefix::Testnet), Some(vault));
+                assert_eq!(script::sig_op_count(&script).unwrap(), 1);
+            }
+        }
+        let owner = test_wallet(0x71).address();
+        assert!(Vault::new(owner.clone(), LockTime::DaaScore(0)).is_err());
+        let vault = Vault::new(owner, LockTime::DaaScore(UNLOCK_SCORE)).unwrap();
+        let p2sh = vault.address(Prefix::Testnet).unwrap();
+        assert!(Vault::new(p2sh, LockTime::DaaScore(UNLOCK_SCORE)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_after_lock_time() {
+        let owner = test_wallet(0x71);
+        let vault = Vault::new(owner.address(), LockTime::DaaScore(UNLOCK_SCORE)).unwrap();
+        let address = vault.address(Prefix::Testnet).unwrap();
+        let node = MockNode::new()
+            .with_utxo(&address, 3 * SOMPI_PER_KAS)
+            .with_utxo(&address, 2 * SOMPI_PER_KAS);
+
+        let balances = vault_balances(&node, std::slice::from_ref(&vault), Prefix::Testnet)
+            .await
+            .unwrap();
+        assert_eq!(
+            (balances[0].amount, balances[0].utxo_count),
+            (5 * SOMPI_PER_KAS, 2)
+        );
+
+        let spend = vault.spend().unwrap();
+        let tx = p2sh::build_script_sweep(
+            &node,
+            &spend,
+            Prefix::Testnet,
+            &owner.address(),
+            Some(&owner),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.tx.lock_time, UNLOCK_SCORE);
+        assert_eq!(tx.tx.inputs.len(), 2);
+        for input in &tx.tx.inputs {
+            assert_eq!(input.sequence, LOCK_TIME_SEQUENCE);
+            assert_ne!(input.sequence & SEQUENCE_LOCK_TIME_DISABLED, 0);
+        }
+        execute_scripts(&tx).unwrap();
+
+        // A finalized input would let the lock time be ignored, so the
+        // script refuses it.
+        let mut finalized = tx.clone();
+        finalized.tx.inputs[0].sequence = MAX_TX_IN_SEQUENCE_NUM;
+        assert!(execute_scripts(&finalized).is_err());
+
+        // Not final until the DAA score passes the lock time.
+        node.set_virtual_daa_score(UNLOCK_SCORE);
+        assert!(node.submit(&tx.tx).await.is_err());
+        node.set_virtual_daa_score(UNLOCK_SCORE + 1);
+        node.submit(&tx.tx).await.unwrap();
+        assert_eq!(
+            node.balance(&owner.address()).await.unwrap(),
+            tx.tx.outputs[0].value
+        );
+        assert_eq!(node.balance(&address).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_lock_follows_past_median_time() {
+        // Long past on the wall clock, but not yet by the network's
+        // median time.
+        let unlock_time = 1_600_000_000_000;
+        let owner = test_wallet(0x71);
+        let vault = Vault::new(owner.address(), LockTime::Timestamp(unlock_time)).unwrap();
+        let address = vault.address(Prefix::Testnet).unwrap();
+        let node = MockNode::new()
+            .with_past_median_time(unlock_time)
+            .with_utxo(&address, 3 * SOMPI_PER_KAS);
+
+        let tx = p2sh::build_script_sweep(
+            &node,
+            &vault.spend().unwrap(),
+            Prefix::Testnet,
+            &owner.address(),
+            Some(&owner),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tx.tx.lock_time, unlock_time);
+        assert!(node.submit(&tx.tx).await.is_err());
+        node.set_past_median_time(unlock_time + 1);
+        node.submit(&tx.tx).await.unwrap();
+    }
+
+    #[test]
+    fn test_vault_book() {
+        let network = NetworkConfig::testnet10();
+        let owner = test_wallet(0x71)
+            .with_signature_scheme(SignatureScheme::Ecdsa)
+            .address();
+        let vault = Vault::new(owner, LockTime::DaaScore(UNLOCK_SCORE)).unwrap();
+        let address = vault.address(network.address_prefix()).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "kasparustwallet-vaults-{}.json",
+            std::process::id()
+        ));
+        let mut book = VaultBook::load(&path).unwrap();
+        assert!(book.vaults.is_empty());
+        book.add(&network, &vault).unwrap();
+        book.add(&network, &vault).unwrap();
+        book.save(&path).unwrap();
+
+        let book = VaultBook::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(book.vaults.len(), 1);
+        assert_eq!(book.vaults(&network).unwrap(), [vault.clone()]);
+        assert_eq!(book.find(&network, &address).unwrap(), vault);
+        assert!(book.vaults(&NetworkConfig::mainnet()).unwrap().is_empty());
+        let other = test_wallet(0x72).address();
+        assert!(book.find(&network, &other).is_err());
+    }
+}
@@ -1,13 +1,29 @@
-use crate::address::{generate_address, validate_address};
+use crate::address::{address_from_public_key, parse_address, SignatureScheme};
 use crate::error::WalletError;
+use crate::keystore::Keystore;
+use crate::mass;
+use crate::mnemonic::secret_key_from_mnemonic;
 use crate::network::NetworkConfig;
-use crate::transaction::Transaction;
+use crate::pskt::Pskt;
+use crate::secret::SecretString;
+use crate::signer::{
+    input_scheme, input_signature, sign_transaction, signature_script, SigHashMode,
+};
+use crate::transaction::{build_signable_transaction, Utxo};
+use bip39::Mnemonic;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{ScriptPublicKey, SignableTransaction};
+use kaspa_txscript::pay_to_address_script;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroizing;
 
 pub struct KaspaWallet {
     secret_key: SecretKey,
     public_key: PublicKey,
     network_config: NetworkConfig,
+    scheme: SignatureScheme,
 }
 
 impl KaspaWallet {
@@ -19,92 +35,188 @@ impl KaspaWallet {
             secret_key,
             public_key,
             network_config,
+            scheme: SignatureScheme::default(),
         }
     }
 
+    pub fn from_mnemonic(
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        network_config: NetworkConfig,
+    ) -> Result<Self, WalletError> {
+        let secret_key = secret_key_from_mnemonic(mnemonic, passphrase)?;
+        Ok(Self::new(secret_key, network_config))
+    }
+
+    pub fn with_signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
     pub fn with_network(secret_key: SecretKey, network: &str) -> Result<Self, WalletError> {
         let network_config = NetworkConfig::from_name(network)?;
         Ok(Self::new(secret_key, network_config))
     }
 
+    /// Decrypts the keystore at `path`; the network and signature scheme are
+    /// taken from the keystore itself.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Result<Self, WalletError> {
+        let keystore = Keystore::load(path)?;
+        let secret = keystore.decrypt(password)?;
+        let secret_key = SecretKey::from_slice(secret.expose_secret())
+            .map_err(|_| WalletError::KeystoreTampered("invalid secret key".to_string()))?;
+
+        Ok(Self::with_network(secret_key, &keystore.network)?
+            .with_signature_scheme(keystore.signature_scheme()?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, password: &str) -> Result<(), WalletError> {
+        Keystore::encrypt(
+            &Zeroizing::new(self.secret_key.secret_bytes())[..],
+            password,
+            &self.network_config.name,
+            self.scheme,
+        )?
+        .save(path)
+    }
+
+    pub fn address(&self) -> Address {
+        address_from_public_key(
+            &self.public_key,
+            self.network_config.address_prefix(),
+            self.scheme,
+        )
+    }
+
     pub fn get_address(&self) -> String {
-        generate_address(&self.public_key, self.network_config.get_prefix())
+        self.address().to_string()
+    }
+
+    pub fn script_public_key(&self) -> ScriptPublicKey {
+        pay_to_address_script(&self.address())
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
     }
 
     pub fn get_public_key(&self) -> String {
         hex::encode(self.public_key.serialize())
     }
 
-    pub fn get_private_key(&self) -> String {
-        hex::encode(self.secret_key.secret_bytes())
+    pub fn get_private_key(&self) -> SecretString {
+        let bytes = Zeroizing::new(self.secret_key.secret_bytes());
+        SecretString::new(hex::encode(&bytes[..]))
     }
 
     pub fn create_transaction(
         &self,
-        inputs: Vec<(String, u32)>,
+        inputs: Vec<Utxo>,
         outputs: Vec<(String, u64)>,
-        _fee_rate: u64,
-    ) -> Result<Transaction, WalletError> {
-        let mut tx = Transaction::new();
-
-        for (txid, vout) in inputs {
-            tx.add_input(txid, vout);
+        fee_rate: u64,
+    ) -> Result<SignableTransaction, WalletError> {
+        for utxo in &inputs {
+            if input_scheme(&utxo.entry.script_public_key, &self.public_key).is_none() {
+                return Err(WalletError::Transaction(format!(
+                    "Input {} is not spendable by this wallet",
+                    utxo.outpoint
+                )));
+            }
         }
 
+        let mut recipients = Vec::with_capacity(outputs.len());
         for (address, amount) in outputs {
-            if !validate_address(&address)? {
-                return Err(crate::error::WalletError::InvalidAddressFormat);
+            let address = parse_address(&address)?;
+            if address.prefix != self.network_config.address_prefix() {
+                return Err(WalletError::InvalidParameters(format!(
+                    "Address {} does not belong to {}",
+                    address, self.network_config.name
+                )));
             }
-            tx.add_output(address, amount);
+            recipients.push((address, amount));
         }
 
-        for i in 0..tx.inputs.len() {
-            tx.sign_input(i, &self.secret_key, &self.public_key)?;
-        }
+        let input_total: u64 = inputs.iter().map(|utxo| utxo.entry.amount).sum();
+        let output_total: u64 = recipients.iter().map(|(_, amount)| amount).sum();
+        let fee = input_total.checked_sub(output_total).ok_or_else(|| {
+            WalletError::Transaction(format!(
+                "Outputs total {} sompi but inputs only {} sompi",
+                output_total, input_total
+            ))
+        })?;
 
-        Ok(tx)
-    }
-
-    pub fn estimate_transaction_fee(input_count: usize, output_count: usize, fee_rate: u64) -> u64 {
-        let mut tx = Transaction::new();
+        let mut tx = build_signable_transaction(inputs, &recipients, 0)?;
+        sign_transaction(&mut tx, &self.secret_key, SigHashMode::All)?;
 
-        for _ in 0..input_count {
-            tx.add_input("dummy".to_string(), 0);
+        // Refuse anything kaspad would not relay rather than find out on submit.
+        let mass = mass::transaction_mass(&tx)?;
+        mass::check_standard_mass(mass)?;
+        let required = mass::fee_for_mass(mass, fee_rate);
+        if fee < required {
+            return Err(WalletError::Transaction(format!(
+                "Fee of {} sompi is below the {} sompi required for mass {} at {} sompi/gram",
+                fee, required, mass, fee_rate
+            )));
         }
+        Ok(tx)
+    }
 
-        for _ in 0..output_count {
-            tx.add_output("dummy".to_string(), 0);
+    /// Adds this wallet's signatures to the inputs of `pskt` it owns and
+    /// returns how many it signed.
+    pub fn sign_pskt(&self, pskt: &mut Pskt) -> Result<usize, WalletError> {
+        if pskt.network != self.network_config.name {
+            return Err(WalletError::InvalidParameters(format!(
+                "PSKT is for {} but the wallet is on {}",
+                pskt.network, self.network_config.name
+            )));
         }
-
-        tx.estimate_fee(fee_rate)
+        pskt.sign(&self.secret_key)
     }
 
-    pub fn validate_private_key(private_key_hex: &str) -> bool {
-        let key_bytes = hex::decode(private_key_hex);
-        if key_bytes.is_err() {
-            return false;
-        }
+    /// Signs input `input_index` of `tx` with this wallet's scheme, whatever
+    /// the input is locked by, and returns the signature push for a custom
+    /// signature script.
+    pub fn sign_script_input(
+        &self,
+        tx: &SignableTransaction,
+        input_index: usize,
+        mode: SigHashMode,
+    ) -> Result<Vec<u8>, WalletError> {
+        let signature = input_signature(tx, input_index, &self.secret_key, self.scheme, mode)?;
+        Ok(signature_script(&signature, mode))
+    }
 
-        let key_bytes = match key_bytes {
-            Ok(bytes) => bytes,
-            Err(_) => return false,
-        };
+    pub fn get_network_name(&self) -> &str {
+        &self.network_config.name
+    }
 
-        if key_bytes.len() != 32 {
-            return false;
-        }
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
 
-        SecretKey::from_slice(&key_bytes).is_ok()
+impl Drop for KaspaWallet {
+    fn drop(&mut self) {
+        self.secret_key.non_secure_erase();
     }
+}
 
-    pub fn get_network_name(&self) -> &str {
-        &self.network_config.name
+impl fmt::Debug for KaspaWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KaspaWallet")
+            .field("secret_key", &"<redacted>")
+            .field("address", &self.get_address())
+            .field("network", &self.network_config.name)
+            .field("scheme", &self.scheme)
+            .finish()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signer::verify_input;
+    use crate::test_util::{secret_key, test_wallet, utxo};
 
     #[test]
     fn test_wallet_creation() {
@@ -129,12 +241,141 @@ mod tests {
     }
 
     #[test]
-    fn test_private_key_validation() {
-        assert!(!KaspaWallet::validate_private_key("invalid"));
-        assert!(!KaspaWallet::validate_private_key(""));
-        assert!(!KaspaWallet::validate_private_key("123"));
-        assert!(KaspaWallet::validate_private_key(
-            "0000000000000000000000000000000000000000000000000000000000000001"
+    fn test_create_transaction() {
+        let secp = Secp256k1::new();
+        let (secret_key, _) = secp.generate_keypair(&mut rand::rngs::OsRng);
+
+        let wallet = KaspaWallet::new(secret_key, NetworkConfig::testnet10());
+        let recipient = wallet.get_address();
+        let utxo = utxo(0, 1_000_000_000, wallet.script_public_key());
+
+        let tx = wallet
+            .create_transaction(vec![utxo.clone()], vec![(recipient, 500_000_000)], 0)
+            .unwrap();
+        assert_eq!(tx.tx.inputs.len(), 1);
+        assert_eq!(tx.tx.outputs[0].value, 500_000_000);
+
+        verify_input(&tx, 0, &wallet.public_key).unwrap();
+
+        let mainnet_address = KaspaWallet::new(secret_key, NetworkConfig::mainnet()).get_address();
+        assert!(wallet
+            .create_transaction(vec![utxo], vec![(mainnet_address, 500_000_000)], 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_create_transaction_checks_mass_and_fee() {
+        let wallet = test_wallet(0x42);
+        let utxo = utxo(0, 1_000_000_000, wallet.script_public_key());
+        let pay = |amount| vec![(wallet.get_address(), amount)];
+
+        // Paying the whole input leaves no fee at all.
+        assert!(matches!(
+            wallet.create_transaction(vec![utxo.clone()], pay(1_000_000_000), 1),
+            Err(WalletError::Transaction(_))
         ));
+        assert!(wallet
+            .create_transaction(vec![utxo.clone()], pay(2_000_000_000), 0)
+            .is_err());
+        // A 10_000 sompi output has a storage mass of 10^8.
+        assert!(matches!(
+            wallet.create_transaction(vec![utxo.clone()], pay(10_000), 0),
+            Err(WalletError::MassLimitExceeded { .. })
+        ));
+
+        let tx = wallet
+            .create_transaction(vec![utxo], pay(999_000_000), 1)
+            .unwrap();
+        assert_eq!(
+            mass::transaction_mass(&tx).unwrap(),
+            mass::compute_mass(&tx.tx)
+        );
+    }
+
+    #[test]
+    fn test_create_transaction_rejects_foreign_inputs() {
+        let secp = Secp256k1::new();
+        let (secret_key, _) = secp.generate_keypair(&mut rand::rngs::OsRng);
+        let (other_key, _) = secp.generate_keypair(&mut rand::rngs::OsRng);
+
+        let wallet = KaspaWallet::new(secret_key, NetworkConfig::testnet10());
+        let other = KaspaWallet::new(other_key, NetworkConfig::testnet10());
+        let utxo = utxo(0, 10_000, other.script_public_key());
+
+        assert!(wallet
+            .create_transaction(vec![utxo], vec![(wallet.get_address(), 5000)], 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_ecdsa_wallet() {
+        let schnorr = test_wallet(0x42);
+        let ecdsa = test_wallet(0x42).with_signature_scheme(SignatureScheme::Ecdsa);
+        assert_ne!(schnorr.get_address(), ecdsa.get_address());
+        assert_eq!(
+            ecdsa.address().version,
+            kaspa_addresses::Version::PubKeyECDSA
+        );
+
+        // Both address kinds of the same key can be spent together.
+        let inputs = vec![
+            utxo(0, 1_000_000_000, schnorr.script_public_key()),
+            utxo(1, 1_000_000_000, ecdsa.script_public_key()),
+        ];
+        let tx = ecdsa
+            .create_transaction(inputs, vec![(ecdsa.get_address(), 1_500_000_000)], 0)
+            .unwrap();
+        verify_input(&tx, 0, &ecdsa.public_key).unwrap();
+        verify_input(&tx, 1, &ecdsa.public_key).unwrap();
+        assert_eq!(tx.tx.inputs[1].signature_script.len(), 66);
+    }
+
+    #[test]
+    fn test_from_mnemonic() {
+        let mnemonic = crate::mnemonic::generate_mnemonic(12).unwrap();
+        let wallet = KaspaWallet::from_mnemonic(&mnemonic, "", NetworkConfig::testnet10()).unwrap();
+        let restored = KaspaWallet::from_mnemonic(
+            &crate::mnemonic::parse_mnemonic(&mnemonic.to_string()).unwrap(),
+            "",
+            NetworkConfig::testnet10(),
+        )
+        .unwrap();
+
+        assert_eq!(wallet.get_address(), restored.get_address());
+        assert!(wallet.get_address().starts_with("kaspatest:"));
+    }
+
+    #[test]
+    fn test_save_and_open() {
+        let wallet = KaspaWallet::new(secret_key(0x42), NetworkConfig::testnet11())
+            .with_signature_scheme(SignatureScheme::Ecdsa);
+        let path = std::env::temp_dir().join(format!(
+            "kasparustwallet-wallet-{}.json",
+            std::process::id()
+        ));
+
+        wallet.save(&path, "hunter2").unwrap();
+        let opened = KaspaWallet::open(&path, "hunter2");
+        let wrong = KaspaWallet::open(&path, "hunter3");
+        std::fs::remove_file(&path).unwrap();
+
+        let opened = opened.unwrap();
+        assert_eq!(opened.get_address(), wallet.get_address());
+        assert_eq!(opened.get_network_name(), wallet.get_network_name());
+        assert_eq!(opened.signature_scheme(), SignatureScheme::Ecdsa);
+        assert!(matches!(wrong, Err(WalletError::WrongPassword)));
+    }
+
+    #[test]
+    fn test_debug_redacts_private_key() {
+        let secret_key = secret_key(0x42);
+        let wallet = test_wallet(0x42);
+        let key_hex = wallet.get_private_key();
+
+        let debug = format!("{:?} {:#?}", wallet, wallet);
+        assert!(!debug.contains(key_hex.expose_secret()));
+        assert!(!debug.contains(&format!("{:?}", secret_key.secret_bytes())));
+        assert!(debug.contains(&wallet.get_address()));
+        assert!(!format!("{:?}", key_hex).contains(key_hex.expose_secret()));
     }
 }
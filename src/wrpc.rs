@@ -0,0 +1,270 @@
+use crate::network::NetworkConfig;
+use crate::rpc::RpcError;
+use crate::transaction::Utxo;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::{Transaction, TransactionId};
+use kaspa_rpc_core::api::rpc::RpcApi;
+use kaspa_rpc_core::{
+    GetServerInfoResponse, RpcFeeEstimate, RpcMempoolEntry, RpcMempoolEntryByAddress,
+    RpcTransaction,
+};
+use kaspa_wrpc_client::client::{ConnectOptions, ConnectStrategy};
+use kaspa_wrpc_client::KaspaRpcClient;
+use std::time::Duration;
+
+pub use kaspa_wrpc_client::WrpcEncoding;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// WebSocket RPC connection to a kaspad node, using the node's own
+/// kaspa-rpc-core request and response types.
+pub struct WrpcClient {
+    url: String,
+    client: KaspaRpcClient,
+}
+
+impl WrpcClient {
+    pub fn new(url: &str, encoding: WrpcEncoding) -> Result<Self, RpcError> {
+        let client = KaspaRpcClient::new(encoding, Some(url), None, None, None)
+            .map_err(|e| RpcError::Connection(e.to_string()))?;
+        Ok(Self {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    /// Uses the local node endpoint configured for `network`.
+    pub fn for_network(network: &NetworkConfig, encoding: WrpcEncoding) -> Result<Self, RpcError> {
+        let url = match encoding {
+            WrpcEncoding::Borsh => &network.wrpc_borsh_url,
+            WrpcEncoding::SerdeJson => &network.wrpc_json_url,
+        };
+        Self::new(url, encoding)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn connect(&self) -> Result<(), RpcError> {
+        let options = ConnectOptions {
+            block_async_connect: true,
+            strategy: ConnectStrategy::Fallback,
+            connect_timeout: Some(CONNECT_TIMEOUT),
+            ..ConnectOptions::default()
+        };
+        self.client
+            .connect(Some(options))
+            .await
+            .map(|_| ())
+            .map_err(|e| RpcError::Connection(format!("{}: {}", self.url, e)))
+    }
+
+    pub async fn disconnect(&self) -> Result<(), RpcError> {
+        self.client
+            .disconnect()
+            .await
+            .map_err(|e| RpcError::Connection(e.to_string()))
+    }
+
+    pub async fn get_server_info(&self) -> Result<GetServerInfoResponse, RpcError> {
+        self.client.get_server_info().await.map_err(rpc_error)
+    }
+
+    pub async fn get_balance_by_address(&self, address: &Address) -> Result<u64, RpcError> {
+        self.client
+            .get_balance_by_address(address.clone())
+            .await
+            .map_err(rpc_error)
+    }
+
+    pub async fn get_utxos_by_addresses(
+        &self,
+        addresses: Vec<Address>,
+    ) -> Result<Vec<Utxo>, RpcError> {
+        let entries = self
+            .client
+            .get_utxos_by_addresses(addresses)
+            .await
+            .map_err(rpc_error)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|entry| Utxo::new(entry.outpoint.into(), entry.utxo_entry.into()))
+            .collect())
+    }
+
+    /// Mempool transactions touching `address`, including orphans.
+    pub async fn get_mempool_entries_by_address(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<RpcMempoolEntryByAddress>, RpcError> {
+        self.client
+            .get_mempool_entries_by_addresses(vec![address.clone()], true, false)
+            .await
+            .map_err(rpc_error)
+    }
+
+    /// A transaction in the node's mempool, including orphans.
+    pub async fn get_mempool_entry(
+        &self,
+        transaction_id: TransactionId,
+    ) -> Result<RpcMempoolEntry, RpcError> {
+        self.client
+            .get_mempool_entry(transaction_id, true, false)
+            .await
+            .map_err(rpc_error)
+    }
+
+    pub async fn get_fee_estimate(&self) -> Result<RpcFeeEstimate, RpcError> {
+        self.client.get_fee_estimate().await.map_err(rpc_error)
+    }
+
+    pub async fn get_virtual_daa_score(&self) -> Result<u64, RpcError> {
+        Ok(self.get_server_info().await?.virtual_daa_score)
+    }
+
+    pub async fn get_past_median_time(&self) -> Result<u64, RpcError> {
+        let info = self.client.get_block_dag_info().await.map_err(rpc_error)?;
+        Ok(info.past_median_time)
+    }
+
+    pub async fn submit_transaction(&self, tx: &Transaction) -> Result<TransactionId, RpcError> {
+        self.client
+            .submit_transaction(RpcTransaction::from(tx), false)
+            .await
+            .map_err(rpc_error)
+    }
+}
+
+fn rpc_error(error: kaspa_rpc_core::RpcError) -> RpcError {
+    RpcError::Rpc(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_wallet, TXID};
+    use crate::transaction::{build_transaction, parse_outpoint};
+    use futures_util::{SinkExt, StreamExt};
+    use kaspa_consensus_core::tx::{TransactionOutpoint, UtxoEntry};
+    use kaspa_rpc_core::{
+        GetBalanceByAddressResponse, GetUtxosByAddressesResponse, RpcUtxosByAddressesEntry,
+        SubmitTransactionResponse,
+    };
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// A stub kaspad speaking the JSON wRPC framing: every request
+    /// `{id, method, params}` is answered with `{id, method, params}` where
+    /// the params come from `respond(method, params)`.
+    async fn stub_server(respond: fn(&str, &Value) -> Value) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(message)) = socket.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let method = request["method"].as_str().unwrap();
+                let reply = json!({
+                    "id": request["id"],
+                    "method": method,
+                    "params": respond(method, &request["params"]),
+                });
+                socket.send(Message::Text(reply.to_string())).await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    fn respond(method: &str, params: &Value) -> Value {
+        let wallet = test_wallet(0x42);
+        match method {
+            "getBalanceByAddress" => {
+                assert_eq!(params["address"], wallet.get_address());
+                serde_json::to_value(GetBalanceByAddressResponse::new(123_456)).unwrap()
+            }
+            "getUtxosByAddresses" => {
+                let entry = RpcUtxosByAddressesEntry {
+                    address: Some(wallet.address()),
+                    outpoint: TransactionOutpoint::new(TXID.parse().unwrap(), 3).into(),
+                    utxo_entry: UtxoEntry::new(5_000, wallet.script_public_key(), 77, false).into(),
+                };
+                serde_json::to_value(GetUtxosByAddressesResponse::new(vec![entry])).unwrap()
+            }
+            "submitTransaction" => {
+                let input = &params["transaction"]["inputs"][0]["previousOutpoint"];
+                assert_eq!(input["transactionId"], TXID);
+                serde_json::to_value(SubmitTransactionResponse::new(TXID.parse().unwrap())).unwrap()
+            }
+            other => panic!("unexpected method {}", other),
+        }
+    }
+
+    #[test]
+    fn test_network_urls() {
+        let network = NetworkConfig::mainnet();
+        let borsh = WrpcClient::for_network(&network, WrpcEncoding::Borsh).unwrap();
+        let json = WrpcClient::for_network(&network, WrpcEncoding::SerdeJson).unwrap();
+        assert_eq!(borsh.url(), "ws://127.0.0.1:17110");
+        assert_eq!(json.url(), "ws://127.0.0.1:18110");
+    }
+
+    #[tokio::test]
+    async fn test_json_client_against_stub_server() {
+        let url = stub_server(respond).await;
+        let client = WrpcClient::new(&url, WrpcEncoding::SerdeJson).unwrap();
+        client.connect().await.unwrap();
+
+        let wallet = test_wallet(0x42);
+        assert_eq!(
+            client
+                .get_balance_by_address(&wallet.address())
+                .await
+                .unwrap(),
+            123_456
+        );
+
+        let utxos = client
+            .get_utxos_by_addresses(vec![wallet.address()])
+            .await
+            .unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].outpoint, parse_outpoint(TXID, 3).unwrap());
+        assert_eq!(utxos[0].entry.amount, 5_000);
+        assert_eq!(utxos[0].entry.script_public_key, wallet.script_public_key());
+
+        let tx = build_transaction(
+            vec![parse_outpoint(TXID, 3).unwrap()],
+            &[(wallet.address(), 4_000)],
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            client.submit_transaction(&tx).await.unwrap().to_string(),
+            TXID
+        );
+
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure_is_connection_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = WrpcClient::new(&url, WrpcEncoding::Borsh).unwrap();
+        assert!(matches!(
+            client.connect().await,
+            Err(RpcError::Connection(_))
+        ));
+    }
+}